@@ -3,6 +3,8 @@ extern crate solanum;
 use solanum::client::Client;
 
 use std::env;
+use std::io;
+use std::io::BufRead;
 use std::process;
 
 fn main() {
@@ -12,7 +14,10 @@ fn main() {
 
   Where COMMAND is one of:
       start : Start a new \
-                  pomodoro");
+                  pomodoro
+      SUBSCRIBE : Print an event each time a pomodoro starts, ends its work, starts its \
+                  break, completes or is aborted
+      -     : Send each line of standard input as a command, all over one connection");
         process::exit(1);
     }
 
@@ -20,5 +25,34 @@ fn main() {
     args.next();
     let command = args.next().expect("command not specified");
 
-    println!("{}", client.send_message(command).unwrap());
+    if command == "-" {
+        let mut session = client.open_session().unwrap();
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            println!("{}", session.send_message(line.unwrap()).unwrap());
+        }
+        return;
+    }
+
+    if command == "SUBSCRIBE" {
+        let mut session = client.open_session().unwrap();
+        match session.subscribe() {
+            Ok(response) => println!("{}", response),
+            Err(e) => {
+                println!("{}", e);
+                process::exit(1);
+            }
+        }
+        while let Some(event) = session.receive_message().unwrap() {
+            println!("{}", event);
+        }
+        return;
+    }
+
+    let response = client.send_message(command).unwrap();
+    println!("{}", response);
+    // Text commands fail with an ERROR line; JSON requests with an error status.
+    if response.starts_with("ERROR ") || response.contains("\"status\":\"error\"") {
+        process::exit(1);
+    }
 }
@@ -1,20 +1,80 @@
-use std::io::{Error, Read, Write};
+use daemon::io::framing;
+use daemon::io::framing::FrameDecoder;
+use daemon::result::Error as DaemonError;
+
+use std::io::{Error, ErrorKind};
 use std::time::Duration;
 use std::os::unix::net::UnixStream;
 
+/// Largest response the client accepts, well beyond anything the daemon sends.
+const MAX_RESPONSE_LENGTH: usize = 16 * 1024 * 1024;
+
 pub struct Client {}
 
 impl Client {
     pub fn new() -> Client {
         Client {}
     }
+
+    /// Sends a single command on a connection of its own.
     pub fn send_message(&self, message: String) -> Result<String, Error> {
-        let mut stream = try!(UnixStream::connect("/tmp/solanum"));
+        self.open_session().and_then(|mut session| session.send_message(message))
+    }
+
+    /// Connects to the daemon for sending any number of commands, e.g. to poll its status
+    /// without reconnecting each time. The daemon closes a session left idle for too long.
+    pub fn open_session(&self) -> Result<Session, Error> {
+        let stream = try!(UnixStream::connect("/tmp/solanum"));
         try!(stream.set_write_timeout(Some(Duration::new(5, 0))));
         try!(stream.set_read_timeout(Some(Duration::new(5, 0))));
-        try!(stream.write_all(message.as_bytes()));
-        let mut response = String::new();
-        try!(stream.read_to_string(&mut response));
+        Ok(Session {
+            stream: stream,
+            decoder: FrameDecoder::new(MAX_RESPONSE_LENGTH),
+        })
+    }
+}
+
+/// A connection to the daemon that commands are sent on one after another.
+pub struct Session {
+    stream: UnixStream,
+    decoder: FrameDecoder,
+}
+
+impl Session {
+    pub fn send_message(&mut self, message: String) -> Result<String, Error> {
+        try!(framing::write_frame(&mut self.stream, message.as_bytes()));
+
+        let response = try!(self.receive_message());
+        response.ok_or(Error::new(ErrorKind::UnexpectedEof,
+                                  "The daemon closed the connection without responding"))
+    }
+
+    /// Subscribes to pomodoro events, which are then read with `receive_message`. No more
+    /// commands can be sent on the session afterwards.
+    pub fn subscribe(&mut self) -> Result<String, Error> {
+        let response = try!(self.send_message(String::from("SUBSCRIBE")));
+        if response.starts_with("ERROR ") {
+            return Err(Error::new(ErrorKind::Other, response));
+        }
+        // Events are waited for however long they take to happen.
+        try!(self.stream.set_read_timeout(None));
         Ok(response)
     }
+
+    /// Reads the next message from the daemon, or None if it has closed the connection.
+    pub fn receive_message(&mut self) -> Result<Option<String>, Error> {
+        let message = try!(framing::read_frame(&mut self.stream, &mut self.decoder)
+            .map_err(|e| match e {
+                DaemonError::IoError(e) => e,
+                e => Error::new(ErrorKind::InvalidData, e.to_string()),
+            }));
+        match message {
+            Some(message) => {
+                String::from_utf8(message)
+                    .map(Some)
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, e))
+            }
+            None => Ok(None),
+        }
+    }
 }
@@ -8,13 +8,15 @@ use solanum::daemon;
 
 use nix::libc;
 
+use std::env;
 use std::ffi::CString;
 use std::fs;
 use std::io;
 use std::io::Write;
 use std::mem;
 use std::os::unix::io::RawFd;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process;
 
 unsafe fn daemonize() {
     let child_pid: libc::pid_t;
@@ -88,11 +90,43 @@ unsafe fn open_signalfd<'a>() -> RawFd {
                    0 as libc::c_int)
 }
 
-fn start_daemon_container<'a>(signalfd: RawFd) -> daemon::result::Result<()> {
-    let mut container = try!(daemon::DaemonContainer::new(&signalfd));
+fn start_daemon_container<'a>(signalfd: RawFd, config: &daemon::Config) -> daemon::result::Result<()> {
+    let mut container = try!(daemon::DaemonContainer::new(&signalfd, config));
     container.start()
 }
 
+/// Reports the pending migrations of the configured PostgreSQL database and, unless this is a
+/// dry run, applies them.
+fn run_migrations(config_path: Option<&Path>, dry_run: bool) -> daemon::result::Result<()> {
+    let config = try!(daemon::Config::load(config_path));
+    let database = match config.storage {
+        daemon::config::StorageBackend::Postgres(ref database) => database,
+        _ => {
+            return Err(daemon::result::Error::from(String::from("solanumd migrate only applies to \
+                                                                 postgres storage")))
+        }
+    };
+
+    let mapper = try!(daemon::PomodoroQueryMapper::new(database));
+    let pending = try!(mapper.pending_migrations());
+    if pending.is_empty() {
+        println!("The database schema is up to date");
+        return Ok(());
+    }
+
+    for migration in pending.iter() {
+        println!("Pending migration {}: {}", migration.version, migration.description);
+    }
+    if dry_run {
+        return Ok(());
+    }
+
+    for migration in try!(mapper.migrate()) {
+        println!("Applied migration {}: {}", migration.version, migration.description);
+    }
+    Ok(())
+}
+
 fn main() {
     // TODO: currently here because daemon chdir's to / and don't want to resolve the relative path
     // at the moment.
@@ -101,6 +135,36 @@ fn main() {
         Err(e)
     });
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    let ephemeral = args.iter().any(|arg| arg == "--ephemeral");
+    let config_path = args.iter()
+        .position(|arg| arg == "--config")
+        .and_then(|index| args.get(index + 1))
+        .map(PathBuf::from);
+
+    if args.first().map(|arg| arg == "migrate").unwrap_or(false) {
+        let dry_run = args.iter().any(|arg| arg == "--dry-run");
+        match run_migrations(config_path.as_ref().map(|path| path.as_path()), dry_run) {
+            Ok(_) => process::exit(0),
+            Err(e) => {
+                error!("{}", e);
+                println!("{}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    let config = match daemon::Config::load(config_path.as_ref().map(|path| path.as_path()))
+        .map(|config| if ephemeral { config.ephemeral() } else { config })
+        .and_then(|config| daemon::DaemonContainer::check_config(&config).map(|_| config)) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("{}", e);
+            println!("{}", e);
+            process::exit(1);
+        }
+    };
+
     let signalfd: RawFd;
 
     unsafe {
@@ -108,7 +172,7 @@ fn main() {
         signalfd = open_signalfd();
     }
 
-    let _ = start_daemon_container(signalfd).or_else(|e| {
+    let _ = start_daemon_container(signalfd, &config).or_else(|e| {
         error!("{}", e);
         Err(e)
     });
@@ -3,6 +3,9 @@ extern crate regex;
 use daemon::chrono::Duration;
 use daemon::chrono::datetime::DateTime;
 use daemon::chrono::offset::utc::UTC;
+use daemon::pomodoro::InterruptionKind;
+use daemon::pomodoro::PomodoroEdit;
+use daemon::pomodoro::PomodoroStatus;
 use daemon::result::Error;
 use daemon::result::Result;
 
@@ -18,6 +21,22 @@ pub enum Command {
     Stop,
     List,
     Status,
+    Pause,
+    Resume,
+    StartBreak,
+    SkipBreak,
+    Extend(Duration),
+    Interrupt(InterruptionKind, Option<String>),
+    /// Adds a note to the pomodoro with the given id, or to the current one.
+    Note(Option<i32>, String),
+    /// Records a finished pomodoro that was not timed by the daemon.
+    Log(DateTime<UTC>, Duration, Duration, Vec<String>, PomodoroStatus),
+    Edit(i32, Vec<PomodoroEdit>),
+    Delete(i32),
+    /// Reverts the changes made by the last command that changed any pomodoro.
+    Undo,
+    /// Keeps the connection open to be told each time a pomodoro moves on to its next step.
+    Subscribe,
 }
 
 impl Display for Command {
@@ -30,7 +49,33 @@ impl Display for Command {
             }
             Command::Stop => write!(f, "STOP"),
             Command::List => write!(f, "LIST"),
-            Command::Status => write!(f, "STATUS")
+            Command::Status => write!(f, "STATUS"),
+            Command::Pause => write!(f, "PAUSE"),
+            Command::Resume => write!(f, "RESUME"),
+            Command::StartBreak => write!(f, "STARTBREAK"),
+            Command::SkipBreak => write!(f, "SKIPBREAK"),
+            Command::Extend(length) => write!(f, "EXTEND {}", length),
+            Command::Interrupt(ref kind, ref note) => {
+                write!(f, "INTERRUPT {} {}", kind, note.clone().unwrap_or(String::new()))
+            }
+            Command::Note(Some(id), ref text) => write!(f, "NOTE #{} {}", id, text),
+            Command::Note(None, ref text) => write!(f, "NOTE {}", text),
+            Command::Log(start_time, work_duration, break_duration, ref tags, ref status) => {
+                write!(f,
+                       "LOG: {} {} {} {} {}",
+                       start_time,
+                       work_duration,
+                       break_duration,
+                       tags.join(","),
+                       status)
+            }
+            Command::Edit(id, ref edits) => {
+                let edits: Vec<String> = edits.iter().map(|edit| edit.to_string()).collect();
+                write!(f, "EDIT {} {}", id, edits.join(" "))
+            }
+            Command::Delete(id) => write!(f, "DELETE {}", id),
+            Command::Undo => write!(f, "UNDO"),
+            Command::Subscribe => write!(f, "SUBSCRIBE"),
         }
     }
 }
@@ -39,8 +84,15 @@ impl Command {
     pub fn from_string(current_time: DateTime<UTC>,
                        string: String)
                        -> Result<Command> {
-        let start_re = regex::Regex::new(r"^START(?: tags ((?:\w+,)*(?:\w+)))?(?: (\d+) (\d+))?")
+        let start_re = regex::Regex::new(r"^START(?: tags ((?:\w+,)*(?:\w+)))?(?: (\d+) (\d+))?$")
+            .unwrap();
+        let extend_re = regex::Regex::new(r"^EXTEND (\d+)$").unwrap();
+        let interrupt_re = regex::Regex::new(r"^INTERRUPT (internal|external)(?: (.+))?$").unwrap();
+        let log_re = regex::Regex::new(r"^LOG (\S+)(?: tags ((?:\w+,)*\w+))? (\d+) (\d+)(?: (\w+))?$")
             .unwrap();
+        let edit_re = regex::Regex::new(r"^EDIT (\d+)((?: \w+=\S*)+)$").unwrap();
+        let delete_re = regex::Regex::new(r"^DELETE (\d+)$").unwrap();
+        let note_re = regex::Regex::new(r"^NOTE(?: #(\d+))?(?: (.*))?$").unwrap();
         if start_re.is_match(string.as_str()) {
             match start_re.captures(string.as_str()) {
                 Some(caps) => {
@@ -74,9 +126,134 @@ impl Command {
             Ok(Command::List)
         } else if string == "STATUS" {
             Ok(Command::Status)
+        } else if string == "PAUSE" {
+            Ok(Command::Pause)
+        } else if string == "RESUME" {
+            Ok(Command::Resume)
+        } else if string == "STARTBREAK" {
+            Ok(Command::StartBreak)
+        } else if string == "SKIPBREAK" {
+            Ok(Command::SkipBreak)
+        } else if string == "UNDO" {
+            Ok(Command::Undo)
+        } else if string == "SUBSCRIBE" {
+            Ok(Command::Subscribe)
+        } else if let Some(caps) = extend_re.captures(string.as_str()) {
+            caps.at(1)
+                .unwrap_or("")
+                .parse::<i64>()
+                .map(|seconds| Command::Extend(Duration::seconds(seconds)))
+                .map_err(|_| Error::InvalidCommandError(format!("Invalid extension: {}", string)))
+        } else if let Some(caps) = interrupt_re.captures(string.as_str()) {
+            let kind = InterruptionKind::from(String::from(caps.at(1).unwrap_or("")));
+            let note = caps.at(2)
+                .map(|note| note.trim())
+                .and_then(|note| if note.is_empty() { None } else { Some(String::from(note)) });
+            Ok(Command::Interrupt(kind, note))
+        } else if let Some(caps) = note_re.captures(string.as_str()) {
+            let id = match caps.at(1) {
+                Some(id) => {
+                    Some(try!(id.parse::<i32>().map_err(|_| {
+                        Error::InvalidCommandError(format!("Invalid pomodoro id: {}", string))
+                    })))
+                }
+                None => None,
+            };
+            let text = caps.at(2).unwrap_or("").trim();
+            if text.is_empty() {
+                return Err(Error::InvalidCommandError(format!("Note has no text: {}", string)));
+            }
+            Ok(Command::Note(id, String::from(text)))
+        } else if let Some(caps) = log_re.captures(string.as_str()) {
+            let start_time = try!(caps.at(1)
+                .unwrap_or("")
+                .parse::<DateTime<UTC>>()
+                .map_err(|_| Error::InvalidCommandError(format!("Invalid start time: {}", string))));
+            let tags = caps.at(2)
+                .map(|tags_csv| tags_csv.split(",").map(String::from).collect())
+                .unwrap_or(vec![]);
+            let work_time = try!(caps.at(3)
+                .unwrap_or("")
+                .parse::<i64>()
+                .map_err(|_| Error::InvalidCommandError(format!("Invalid work length: {}", string))));
+            let break_time = try!(caps.at(4)
+                .unwrap_or("")
+                .parse::<i64>()
+                .map_err(|_| Error::InvalidCommandError(format!("Invalid break length: {}", string))));
+            let status = try!(parse_final_status(caps.at(5).unwrap_or("Completed")));
+
+            Ok(Command::Log(start_time,
+                            Duration::seconds(work_time),
+                            Duration::seconds(break_time),
+                            tags,
+                            status))
+        } else if let Some(caps) = edit_re.captures(string.as_str()) {
+            let id = try!(parse_id(caps.at(1).unwrap_or("")));
+            let edits = try!(caps.at(2)
+                .unwrap_or("")
+                .split_whitespace()
+                .map(|assignment| {
+                    let mut parts = assignment.splitn(2, "=");
+                    parse_edit(parts.next().unwrap_or(""), parts.next().unwrap_or(""))
+                })
+                .collect::<Result<Vec<PomodoroEdit>>>());
+            Ok(Command::Edit(id, edits))
+        } else if let Some(caps) = delete_re.captures(string.as_str()) {
+            parse_id(caps.at(1).unwrap_or("")).map(Command::Delete)
         } else {
-            Err(Error::from(format!("Invalid command string: {}", string)))
+            Err(Error::InvalidCommandError(format!("Invalid command string: {}", string)))
+        }
+    }
+}
+
+fn parse_id(id: &str) -> Result<i32> {
+    id.parse::<i32>().map_err(|_| Error::InvalidCommandError(format!("Invalid pomodoro id: {}", id)))
+}
+
+/// Reads the status a logged pomodoro ended in, which has to be one that finishes it.
+pub fn parse_final_status(status: &str) -> Result<PomodoroStatus> {
+    match status {
+        "Completed" => Ok(PomodoroStatus::Completed),
+        "Aborted" => Ok(PomodoroStatus::Aborted),
+        "BreakSkipped" => Ok(PomodoroStatus::BreakSkipped),
+        _ => Err(Error::InvalidCommandError(format!("Invalid final status: {}", status))),
+    }
+}
+
+/// Reads a `field=value` correction. Times are RFC 3339, or `none` for those that can be
+/// missing; lengths are seconds and tags are comma-separated.
+pub fn parse_edit(field: &str, value: &str) -> Result<PomodoroEdit> {
+    let time = |value: &str| {
+        value.parse::<DateTime<UTC>>()
+            .map_err(|_| Error::InvalidCommandError(format!("Invalid time for {}: {}", field, value)))
+    };
+    let optional_time = |value: &str| if value == "none" { Ok(None) } else { time(value).map(Some) };
+    let seconds = |value: &str| {
+        value.parse::<i64>()
+            .map(Duration::seconds)
+            .map_err(|_| Error::InvalidCommandError(format!("Invalid length for {}: {}", field, value)))
+    };
+
+    match field {
+        "work_start_time" => time(value).map(PomodoroEdit::WorkStartTime),
+        "work_end_time" => optional_time(value).map(PomodoroEdit::WorkEndTime),
+        "break_start_time" => optional_time(value).map(PomodoroEdit::BreakStartTime),
+        "break_end_time" => optional_time(value).map(PomodoroEdit::BreakEndTime),
+        "work_length" => seconds(value).map(PomodoroEdit::WorkLength),
+        "break_length" => seconds(value).map(PomodoroEdit::BreakLength),
+        "tags" => {
+            let tags = value.split(",").filter(|tag| !tag.is_empty()).map(String::from).collect();
+            Ok(PomodoroEdit::Tags(tags))
+        }
+        "status" => {
+            let status = PomodoroStatus::from(String::from(value));
+            if status.to_string() == value {
+                Ok(PomodoroEdit::Status(status))
+            } else {
+                Err(Error::InvalidCommandError(format!("Invalid status: {}", value)))
+            }
         }
+        _ => Err(Error::InvalidCommandError(format!("Cannot edit {}", field))),
     }
 }
 
@@ -152,6 +329,122 @@ mod test {
         assert!(command.unwrap() == Command::Status);
     }
 
+    #[test]
+    fn can_parse_pause_and_resume_commands() {
+        let current_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        assert!(Command::from_string(current_time, String::from("PAUSE")).unwrap() == Command::Pause);
+        assert!(Command::from_string(current_time, String::from("RESUME")).unwrap() == Command::Resume);
+    }
+
+    #[test]
+    fn can_parse_break_commands() {
+        let current_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        assert!(Command::from_string(current_time, String::from("STARTBREAK")).unwrap() ==
+                Command::StartBreak);
+        assert!(Command::from_string(current_time, String::from("SKIPBREAK")).unwrap() ==
+                Command::SkipBreak);
+    }
+
+    #[test]
+    fn can_parse_extend_commands() {
+        let current_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        assert!(Command::from_string(current_time, String::from("EXTEND 300")).unwrap() ==
+                Command::Extend(Duration::seconds(300)));
+        assert!(Command::from_string(current_time, String::from("EXTEND")).is_err());
+    }
+
+    #[test]
+    fn can_parse_interrupt_commands() {
+        let current_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        assert!(Command::from_string(current_time, String::from("INTERRUPT internal")).unwrap() ==
+                Command::Interrupt(InterruptionKind::Internal, None));
+        assert!(Command::from_string(current_time, String::from("INTERRUPT external phone call"))
+                    .unwrap() ==
+                Command::Interrupt(InterruptionKind::External, Some(String::from("phone call"))));
+        assert!(Command::from_string(current_time, String::from("INTERRUPT sometimes")).is_err());
+    }
+
+    #[test]
+    fn can_parse_note_commands() {
+        let current_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        assert!(Command::from_string(current_time, String::from("NOTE wrote the parser")).unwrap() ==
+                Command::Note(None, String::from("wrote the parser")));
+        assert!(Command::from_string(current_time, String::from("NOTE #12 fixed a test")).unwrap() ==
+                Command::Note(Some(12), String::from("fixed a test")));
+        assert!(Command::from_string(current_time, String::from("NOTE   ")).is_err());
+        assert!(Command::from_string(current_time, String::from("NOTE #12")).is_err());
+    }
+
+    #[test]
+    fn can_parse_log_commands() {
+        let current_time = "2000-01-02T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let start_time = "2000-01-01T09:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        assert!(Command::from_string(current_time,
+                                     String::from("LOG 2000-01-01T09:00:00+00:00 1500 300"))
+                    .unwrap() ==
+                Command::Log(start_time,
+                             Duration::seconds(1500),
+                             Duration::seconds(300),
+                             vec![],
+                             PomodoroStatus::Completed));
+        assert!(Command::from_string(current_time,
+                                     String::from("LOG 2000-01-01T09:00:00+00:00 tags foo,bar 600 0 \
+                                                   Aborted"))
+                    .unwrap() ==
+                Command::Log(start_time,
+                             Duration::seconds(600),
+                             Duration::seconds(0),
+                             vec![String::from("foo"), String::from("bar")],
+                             PomodoroStatus::Aborted));
+        assert!(Command::from_string(current_time,
+                                     String::from("LOG 2000-01-01T09:00:00+00:00 1500 300 Paused"))
+            .is_err());
+        assert!(Command::from_string(current_time, String::from("LOG yesterday 1500 300")).is_err());
+    }
+
+    #[test]
+    fn can_parse_edit_and_delete_commands() {
+        let current_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let string = String::from("EDIT 3 work_end_time=2000-01-01T00:25:00+00:00 break_start_time=none \
+                                   tags=foo,bar status=Aborted");
+
+        assert!(Command::from_string(current_time, string).unwrap() ==
+                Command::Edit(3,
+                              vec![PomodoroEdit::WorkEndTime(Some("2000-01-01T00:25:00+00:00"
+                                       .parse::<DateTime<UTC>>()
+                                       .unwrap())),
+                                   PomodoroEdit::BreakStartTime(None),
+                                   PomodoroEdit::Tags(vec![String::from("foo"), String::from("bar")]),
+                                   PomodoroEdit::Status(PomodoroStatus::Aborted)]));
+        assert!(Command::from_string(current_time, String::from("DELETE 3")).unwrap() ==
+                Command::Delete(3));
+        assert!(Command::from_string(current_time, String::from("EDIT 3 status=Finished")).is_err());
+        assert!(Command::from_string(current_time, String::from("EDIT 3 id=4")).is_err());
+        assert!(Command::from_string(current_time, String::from("EDIT 3")).is_err());
+    }
+
+    #[test]
+    fn can_parse_undo_commands() {
+        let current_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        assert!(Command::from_string(current_time, String::from("UNDO")).unwrap() == Command::Undo);
+        assert!(Command::from_string(current_time, String::from("UNDO 2")).is_err());
+    }
+
+    #[test]
+    fn can_parse_subscribe_commands() {
+        let current_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        assert!(Command::from_string(current_time, String::from("SUBSCRIBE")).unwrap() ==
+                Command::Subscribe);
+    }
+
     #[test]
     fn returns_error_when_given_invalid_string() {
         let current_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
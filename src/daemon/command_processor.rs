@@ -5,115 +5,620 @@ use daemon::chrono::offset::utc::UTC;
 use daemon::clock::Clock;
 use daemon::Command;
 use daemon::PomodoroTransitioner;
+use daemon::config::CycleConfig;
+use daemon::pomodoro::InterruptionKind;
+use daemon::pomodoro::Pomodoro;
+use daemon::pomodoro::PomodoroEdit;
 use daemon::pomodoro::PomodoroStatus;
-use daemon::pomodoros::Pomodoros;
+use daemon::pomodoro_event::{EventPublisher, PomodoroEvent};
+use daemon::pomodoros::{PomodoroQuery, Pomodoros};
+use daemon::response::{format_time, Response};
 use daemon::result::Error;
 use daemon::result::Result;
 
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard};
+
+/// Number of commands that UNDO can go back through.
+const UNDO_HISTORY_LENGTH: usize = 20;
+
 pub struct CommandProcessor<C: Clock, P: Pomodoros> {
     clock: C,
     pomodoros: P,
+    cycle: CycleConfig,
+    events: EventPublisher,
+    /// What each of the last commands to change anything changed, most recent last.
+    history: Mutex<VecDeque<Vec<RecordedChange>>>,
 }
 
 impl<C: Clock, P: Pomodoros> CommandProcessor<C, P> {
     pub fn new(clock: C, pomodoros: P) -> CommandProcessor<C, P> {
+        CommandProcessor::with_cycle(clock, pomodoros, CycleConfig::default())
+    }
+
+    pub fn with_cycle(clock: C, pomodoros: P, cycle: CycleConfig) -> CommandProcessor<C, P> {
+        CommandProcessor::with_events(clock, pomodoros, cycle, EventPublisher::none())
+    }
+
+    /// Publishes what each command does to the pomodoros it changes to `events`.
+    pub fn with_events(clock: C,
+                       pomodoros: P,
+                       cycle: CycleConfig,
+                       events: EventPublisher)
+                       -> CommandProcessor<C, P> {
         CommandProcessor {
             clock: clock,
             pomodoros: pomodoros,
+            cycle: cycle,
+            events: events,
+            history: Mutex::new(VecDeque::new()),
         }
     }
 
+    /// Handles a command, answering it in text.
     pub fn handle_command(&self, command: Command) -> Result<String> {
+        self.handle(command).map(|response| response.to_string())
+    }
+
+    pub fn handle(&self, command: Command) -> Result<Response> {
         info!("Handling command: {}", command);
         match command {
-            Command::Start(start_time, work_duration, break_duration, _) => {
-                self.handle_start(start_time, work_duration, break_duration)
+            Command::Start(start_time, work_duration, break_duration, tags) => {
+                self.handle_start(start_time, work_duration, break_duration, tags)
             }
             Command::Stop => self.handle_stop(),
             Command::List => self.handle_list(),
             Command::Status => self.handle_status(),
+            Command::Pause => self.handle_pause(),
+            Command::Resume => self.handle_resume(),
+            Command::StartBreak => self.handle_start_break(),
+            Command::SkipBreak => self.handle_skip_break(),
+            Command::Extend(length) => self.handle_extend(length),
+            Command::Interrupt(kind, note) => self.handle_interrupt(kind, note),
+            Command::Note(id, text) => self.handle_note(id, text),
+            Command::Log(start_time, work_duration, break_duration, tags, status) => {
+                self.handle_log(start_time, work_duration, break_duration, tags, status)
+            }
+            Command::Edit(id, edits) => self.handle_edit(id, edits),
+            Command::Delete(id) => self.handle_delete(id),
+            Command::Undo => self.handle_undo(),
+            // Streaming the events is up to the connection the command came in on.
+            Command::Subscribe => {
+                Ok(Response::Done(String::from("Subscribed to pomodoro events"), None))
+            }
         }
     }
 
     fn handle_start(&self,
                     start_time: DateTime<UTC>,
                     work_duration: Duration,
-                    break_duration: Duration)
-                    -> Result<String> {
-        let ref pomodoros = self.pomodoros;
-        match pomodoros.most_recent() {
-            Some(last_pomodoro) => {
-                let now = self.clock.current_time();
+                    break_duration: Duration,
+                    tags: Vec<String>)
+                    -> Result<Response> {
+        let now = self.clock.current_time();
+        let mut started = None;
+
+        // Closing the previous pomodoro and opening the new one is a single unit of work, so
+        // neither a crash nor a racing client can leave one done without the other.
+        try!(self.recorded_transaction(&mut |pomodoros: &Pomodoros| {
+            let mut cycle_position = 1;
+            if let Some(last_pomodoro) = try!(pomodoros.most_recent()) {
                 let mut updated_pomodoro = PomodoroTransitioner::transition(now, &last_pomodoro);
                 if updated_pomodoro.status == PomodoroStatus::BreakPending {
-                    updated_pomodoro = PomodoroTransitioner::transition(now, &updated_pomodoro);
-                    updated_pomodoro = PomodoroTransitioner::transition(now, &updated_pomodoro);
+                    updated_pomodoro = try!(PomodoroTransitioner::skip_break(now, &updated_pomodoro));
                 }
+                cycle_position = self.next_cycle_position(&updated_pomodoro, start_time);
                 try!(pomodoros.update(updated_pomodoro.id, updated_pomodoro));
             }
-            None => {}
+
+            let break_duration = if cycle_position == self.cycle.length {
+                self.cycle.long_break_length
+            } else {
+                break_duration
+            };
+            let pomodoro = started_pomodoro(start_time,
+                                            work_duration,
+                                            break_duration,
+                                            tags.clone(),
+                                            cycle_position);
+            let id = try!(pomodoros.insert(pomodoro.clone()));
+            started = Some(Pomodoro { id: id, ..pomodoro });
+            Ok(())
+        }));
+
+        started.ok_or(Error::from(String::from("Could not get the newly created pomodoro.")))
+            .map(|pomodoro| {
+                let message = format!("Pomodoro started at {}",
+                                      pomodoro.work_start_time.format("%F %H:%M:%S").to_string());
+                Response::Done(message, Some(pomodoro))
+            })
+    }
+
+    /// Works out where a pomodoro started at `start_time` falls in the cycle that `previous`
+    /// belongs to. Only a pomodoro whose work was finished carries its cycle on, and only if the
+    /// next one is started within the configured idle time of its break ending.
+    fn next_cycle_position(&self, previous: &Pomodoro, start_time: DateTime<UTC>) -> i32 {
+        if previous.cycle_position >= self.cycle.length {
+            return 1;
         }
 
-        try!(pomodoros .create(start_time, work_duration, break_duration));
-        pomodoros
-            .most_recent()
-            .ok_or(Error::from(String::from("Could not get the newly created pomodoro.")))
-            .map(|pomodoro| format!(
-                    "Pomodoro started at {}",
-                    pomodoro.work_start_time.format("%F %H:%M:%S").to_string()
-                    )
-                )
+        let rested_until = match previous.status {
+            PomodoroStatus::Completed => {
+                // A break is only closed once the next command arrives, so it is taken to have
+                // ended when it was due to, unless it was cut short.
+                let break_due = previous.break_start_time
+                    .map(|start| start + previous.break_length + previous.break_paused_time(start_time));
+                match (break_due, previous.break_end_time) {
+                    (Some(due), Some(ended)) if ended < due => Some(ended),
+                    (due, ended) => due.or(ended),
+                }
+            }
+            PomodoroStatus::BreakSkipped => previous.work_end_time,
+            _ => return 1,
+        };
+
+        match rested_until {
+            Some(rested_until) if start_time - rested_until <= self.cycle.reset_after => {
+                previous.cycle_position + 1
+            }
+            _ => 1,
+        }
     }
 
-    fn handle_stop(&self) -> Result<String> {
-        self.pomodoros
-            .most_recent()
-            .ok_or(Error::from(String::from("No pomodoro to stop.")))
-            .map(|pomodoro| PomodoroTransitioner::transition(self.clock.current_time(), &pomodoro))
-            .and_then(|pomodoro| self.pomodoros.update(pomodoro.id, pomodoro))
-            .map(|_| String::from("Pomodoro aborted"))
+    /// Changes the most recent pomodoro within a transaction, so that a change made by another
+    /// command in the meantime, such as a note, is not overwritten with a stale copy.
+    fn change_most_recent<F>(&self, missing: &str, change: F) -> Result<Pomodoro>
+        where F: Fn(DateTime<UTC>, &Pomodoro) -> Result<Pomodoro>
+    {
+        let now = self.clock.current_time();
+        let mut changed_pomodoro = None;
+
+        try!(self.recorded_transaction(&mut |pomodoros: &Pomodoros| {
+            let pomodoro = try!(try!(pomodoros.most_recent())
+                .ok_or(Error::NotFoundError(String::from(missing))));
+            let changed = try!(change(now, &pomodoro));
+            changed_pomodoro = Some(changed.clone());
+            pomodoros.update(changed.id, changed)
+        }));
+
+        changed_pomodoro.ok_or(Error::from(String::from("Could not change the pomodoro.")))
+    }
+
+    fn handle_stop(&self) -> Result<Response> {
+        self.change_most_recent("No pomodoro to stop.", |now, pomodoro| {
+                Ok(PomodoroTransitioner::transition(now, pomodoro))
+            })
+            .map(|pomodoro| Response::Done(String::from("Pomodoro aborted"), Some(pomodoro)))
+    }
+
+    fn handle_pause(&self) -> Result<Response> {
+        self.change_most_recent("No pomodoro to pause.", PomodoroTransitioner::pause)
+            .map(|pomodoro| Response::Done(String::from("Pomodoro paused"), Some(pomodoro)))
+    }
+
+    fn handle_resume(&self) -> Result<Response> {
+        self.change_most_recent("No pomodoro to resume.", PomodoroTransitioner::resume)
+            .map(|pomodoro| Response::Done(String::from("Pomodoro resumed"), Some(pomodoro)))
+    }
+
+    fn handle_start_break(&self) -> Result<Response> {
+        self.change_most_recent("No pomodoro to take a break from.", PomodoroTransitioner::begin_break)
+            .map(|pomodoro| Response::Done(String::from("Break started"), Some(pomodoro)))
+    }
+
+    fn handle_skip_break(&self) -> Result<Response> {
+        self.change_most_recent("No pomodoro to skip the break of.", PomodoroTransitioner::skip_break)
+            .map(|pomodoro| Response::Done(String::from("Break skipped"), Some(pomodoro)))
+    }
+
+    fn handle_extend(&self, length: Duration) -> Result<Response> {
+        self.change_most_recent("No pomodoro to extend.", |now, pomodoro| {
+                PomodoroTransitioner::extend(now, pomodoro, length)
+            })
+            .map(|pomodoro| {
+                Response::Done(format!("Pomodoro extended by {}", format_time(length)), Some(pomodoro))
+            })
+    }
+
+    fn handle_interrupt(&self, kind: InterruptionKind, note: Option<String>) -> Result<Response> {
+        self.change_most_recent("No pomodoro to interrupt.", |now, pomodoro| {
+                PomodoroTransitioner::interrupt(now, pomodoro, kind.clone(), note.clone())
+            })
+            .map(|pomodoro| Response::Done(String::from("Interruption recorded"), Some(pomodoro)))
+    }
+
+    /// Adds a note to the pomodoro with the given id, or to the most recent one, after any
+    /// notes it already has.
+    fn handle_note(&self, id: Option<i32>, text: String) -> Result<Response> {
+        let mut noted = None;
+
+        try!(self.recorded_transaction(&mut |pomodoros: &Pomodoros| {
+            let pomodoro = try!(match id {
+                Some(id) => pomodoros.find(id),
+                None => pomodoros.most_recent(),
+            });
+            let mut pomodoro = try!(pomodoro.ok_or(match id {
+                Some(id) => Error::NotFoundError(format!("No pomodoro with id {}.", id)),
+                None => Error::NotFoundError(String::from("No pomodoro to add a note to.")),
+            }));
+
+            pomodoro.add_note(&text);
+            noted = Some(pomodoro.clone());
+            pomodoros.update(pomodoro.id, pomodoro)
+        }));
+
+        noted.ok_or(Error::from(String::from("Could not add the note.")))
+            .map(|pomodoro| {
+                Response::Done(format!("Note added to pomodoro {}", pomodoro.id), Some(pomodoro))
+            })
     }
 
-    fn handle_list(&self) -> Result<String> {
+    /// Records a finished pomodoro that was not timed by the daemon, as long as it is over and
+    /// does not overlap any pomodoro already recorded.
+    fn handle_log(&self,
+                  start_time: DateTime<UTC>,
+                  work_duration: Duration,
+                  break_duration: Duration,
+                  tags: Vec<String>,
+                  status: PomodoroStatus)
+                  -> Result<Response> {
+        let logged = logged_pomodoro(start_time, work_duration, break_duration, tags, status);
+        let end_time = logged.end_time().unwrap_or(start_time + work_duration);
+        if end_time > self.clock.current_time() {
+            return Err(Error::InvalidCommandError(String::from("Cannot log a pomodoro that has not \
+                                                                ended yet.")));
+        }
+
+        let mut logged_id = 0;
+        try!(self.recorded_transaction(&mut |pomodoros: &Pomodoros| {
+            try!(check_overlap(pomodoros, start_time, Some(end_time), None));
+            logged_id = try!(pomodoros.insert(logged.clone()));
+            Ok(())
+        }));
+
+        Ok(Response::Done(format!("Pomodoro logged at {}", start_time.format("%F %H:%M:%S")),
+                          Some(Pomodoro { id: logged_id, ..logged })))
+    }
+
+    /// Corrects a recorded pomodoro. Only the most recent pomodoro may be left unfinished, since
+    /// every other one was closed when the next was started, and, as with LOG, a pomodoro cannot
+    /// be moved over any other.
+    fn handle_edit(&self, id: i32, edits: Vec<PomodoroEdit>) -> Result<Response> {
+        let mut edited_pomodoro = None;
+
+        try!(self.recorded_transaction(&mut |pomodoros: &Pomodoros| {
+            let pomodoro = try!(try!(pomodoros.find(id))
+                .ok_or(Error::NotFoundError(format!("No pomodoro with id {}.", id))));
+            let edited = try!(PomodoroTransitioner::edit(&pomodoro, &edits));
+            try!(check_overlap(pomodoros, edited.work_start_time, edited.end_time(), Some(id)));
+            let unfinished = edited.end_time().is_none();
+            edited_pomodoro = Some(edited.clone());
+            try!(pomodoros.update(id, edited));
+
+            let most_recent_id = try!(pomodoros.most_recent()).map(|pomodoro| pomodoro.id);
+            if unfinished && most_recent_id != Some(id) {
+                return Err(Error::InvalidStateError(String::from("Only the most recent pomodoro can be \
+                                                                  left unfinished.")));
+            }
+            Ok(())
+        }));
+
+        Ok(Response::Done(format!("Pomodoro {} edited", id), edited_pomodoro))
+    }
+
+    fn handle_delete(&self, id: i32) -> Result<Response> {
+        try!(self.recorded_transaction(&mut |pomodoros: &Pomodoros| {
+            let pomodoro = try!(pomodoros.find(id));
+            try!(pomodoro.ok_or(Error::NotFoundError(format!("No pomodoro with id {}.", id))));
+            pomodoros.delete(id)
+        }));
+
+        Ok(Response::Done(format!("Pomodoro {} deleted", id), None))
+    }
+
+    /// Puts back the records changed by the last command that changed any, removing those it
+    /// created. A record changed since, such as by the ticker, is not overwritten; the change is
+    /// refused and dropped from the history instead. Otherwise the change stays in the history
+    /// if it cannot be undone.
+    fn handle_undo(&self) -> Result<Response> {
+        let mut history = try!(self.lock_history());
+        let changes = try!(history.pop_back()
+            .ok_or(Error::NotFoundError(String::from("Nothing to undo."))));
+
+        let mut changed_since = false;
+        let result = self.observed_transaction(&mut |pomodoros: &Pomodoros| {
+            for change in changes.iter() {
+                if try!(pomodoros.find(change.id)) != change.after {
+                    changed_since = true;
+                    return Err(Error::InvalidStateError(format!("Pomodoro {} has changed since the \
+                                                                 last change, which can no longer \
+                                                                 be undone.",
+                                                                change.id)));
+                }
+            }
+            for change in changes.iter().rev() {
+                try!(match change.before {
+                    Some(ref before) => pomodoros.restore(before.clone()),
+                    None => pomodoros.delete(change.id),
+                });
+            }
+            Ok(())
+        });
+
+        match result {
+            Ok(_) => Ok(Response::Done(String::from("Last change undone"), None)),
+            Err(e) => {
+                if !changed_since {
+                    history.push_back(changes);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Runs `work` in a transaction, remembering the records it changes so that UNDO can put
+    /// them back. Only changes that were kept are remembered.
+    fn recorded_transaction(&self, work: &mut FnMut(&Pomodoros) -> Result<()>) -> Result<()> {
+        let changes = try!(self.observed_transaction(work));
+        if !changes.is_empty() {
+            let mut history = try!(self.lock_history());
+            history.push_back(changes);
+            while history.len() > UNDO_HISTORY_LENGTH {
+                history.pop_front();
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs `work` in a transaction and publishes what it did to the pomodoros it changed, once
+    /// those changes are kept. Returns the changes.
+    fn observed_transaction(&self,
+                            work: &mut FnMut(&Pomodoros) -> Result<()>)
+                            -> Result<Vec<RecordedChange>> {
+        let changes = RefCell::new(Vec::new());
+        try!(self.pomodoros.transaction(&mut |pomodoros: &Pomodoros| {
+            try!(work(&RecordingPomodoros {
+                pomodoros: pomodoros,
+                changes: &changes,
+            }));
+
+            // Records are remembered as stored, which may be less precise than as written, e.g.
+            // with times kept to the millisecond, so that UNDO can tell whether they have
+            // changed since.
+            for change in changes.borrow_mut().iter_mut() {
+                if change.after.is_some() {
+                    change.after = try!(pomodoros.find(change.id));
+                }
+            }
+            Ok(())
+        }));
+
+        let changes = changes.into_inner();
+        self.events.publish(changes.iter()
+            .filter_map(|change| {
+                change.after
+                    .as_ref()
+                    .and_then(|after| PomodoroEvent::between(change.before.as_ref(), after))
+            })
+            .collect());
+        Ok(changes)
+    }
+
+    fn lock_history(&self) -> Result<MutexGuard<VecDeque<Vec<RecordedChange>>>> {
+        self.history
+            .lock()
+            .map_err(|_| Error::from(String::from("Undo history lock was poisoned")))
+    }
+
+    fn handle_list(&self) -> Result<Response> {
         self.pomodoros
             .last(5)
-            .and_then(|pomodoros| {
-                Ok(pomodoros.into_iter().fold(String::from(""), |acc, pomodoro| {
-                    acc +
-                        &format!("[{}]: {} ({})\n",
-                        pomodoro.work_start_time.format("%F %H:%M:%S").to_string(),
-                        pomodoro.status,
-                        pomodoro.tags)
-                }))
-            })
+            .map(|pomodoros| Response::List(pomodoros, self.cycle.length))
     }
 
-    fn handle_status(&self) -> Result<String> {
+    fn handle_status(&self) -> Result<Response> {
         let now = self.clock.current_time();
         self.pomodoros
             .most_recent()
-            .ok_or(Error::from(String::from("No pomodoro to get the status of.")))
+            .and_then(|pomodoro| {
+                pomodoro.ok_or(Error::NotFoundError(String::from("No pomodoro to get the status of.")))
+            })
             .map(|pomodoro| {
-
-                let work_time_remaining = (pomodoro.work_start_time + pomodoro.work_length) - now;
-                let work_minutes_remaining = work_time_remaining.num_minutes();
-                let work_seconds_remaining = work_time_remaining.num_seconds() -
-                    work_minutes_remaining * 60;
-                let break_time_remaining = pomodoro.break_start_time
-                    .map(|start_time| (start_time + pomodoro.break_length) - now)
+                let work_remaining = pomodoro.work_deadline(now) - now;
+                let break_remaining = pomodoro.break_deadline(now)
+                    .map(|deadline| deadline - now)
                     .unwrap_or(pomodoro.break_length);
-                let break_minutes_remaining = break_time_remaining.num_minutes();
-                let break_seconds_remaining = break_time_remaining.num_seconds() -
-                    break_minutes_remaining * 60;
-                format!("{:02}:{:02} | {:02}:{:02}",
-                        work_minutes_remaining,
-                        work_seconds_remaining,
-                        break_minutes_remaining,
-                        break_seconds_remaining)
+                Response::Status {
+                    pomodoro: pomodoro,
+                    work_remaining: work_remaining,
+                    break_remaining: break_remaining,
+                    cycle_length: self.cycle.length,
+                }
             })
     }
 }
 
+/// A record as it was before a command changed it, or None if the command created it, and as
+/// the command left it, or None if the command deleted it.
+struct RecordedChange {
+    id: i32,
+    before: Option<Pomodoro>,
+    after: Option<Pomodoro>,
+}
+
+/// Passes everything through to a transaction's view of the store, noting the first and last
+/// states of each record that is changed.
+struct RecordingPomodoros<'a> {
+    pomodoros: &'a Pomodoros,
+    changes: &'a RefCell<Vec<RecordedChange>>,
+}
+
+impl<'a> RecordingPomodoros<'a> {
+    fn record(&self, id: i32, before: Option<Pomodoro>, after: Option<Pomodoro>) {
+        let mut changes = self.changes.borrow_mut();
+        if let Some(change) = changes.iter_mut().find(|change| change.id == id) {
+            change.after = after;
+            return;
+        }
+        changes.push(RecordedChange {
+            id: id,
+            before: before,
+            after: after,
+        });
+    }
+}
+
+impl<'a> Pomodoros for RecordingPomodoros<'a> {
+    /// A pomodoro is created as it starts, so it is the most recent one once created.
+    fn create(&self,
+              start_time: DateTime<UTC>,
+              work_duration: Duration,
+              break_duration: Duration,
+              tags: Vec<String>)
+              -> Result<()> {
+        try!(self.pomodoros.create(start_time, work_duration, break_duration, tags));
+        if let Some(created) = try!(self.pomodoros.most_recent()) {
+            self.record(created.id, None, Some(created.clone()));
+        }
+        Ok(())
+    }
+
+    fn last(&self, count: usize) -> Result<Vec<Pomodoro>> {
+        self.pomodoros.last(count)
+    }
+
+    fn query(&self, query: PomodoroQuery) -> Result<Vec<Pomodoro>> {
+        self.pomodoros.query(query)
+    }
+
+    fn most_recent(&self) -> Result<Option<Pomodoro>> {
+        self.pomodoros.most_recent()
+    }
+
+    fn find(&self, id: i32) -> Result<Option<Pomodoro>> {
+        self.pomodoros.find(id)
+    }
+
+    fn update(&self, id: i32, pomodoro: Pomodoro) -> Result<()> {
+        if let Some(before) = try!(self.pomodoros.find(id)) {
+            self.record(id, Some(before), Some(pomodoro.clone()));
+        }
+        self.pomodoros.update(id, pomodoro)
+    }
+
+    fn insert(&self, pomodoro: Pomodoro) -> Result<i32> {
+        let id = try!(self.pomodoros.insert(pomodoro.clone()));
+        self.record(id, None, Some(Pomodoro { id: id, ..pomodoro }));
+        Ok(id)
+    }
+
+    fn delete(&self, id: i32) -> Result<()> {
+        if let Some(before) = try!(self.pomodoros.find(id)) {
+            self.record(id, Some(before), None);
+        }
+        self.pomodoros.delete(id)
+    }
+
+    fn restore(&self, pomodoro: Pomodoro) -> Result<()> {
+        let before = try!(self.pomodoros.find(pomodoro.id));
+        self.record(pomodoro.id, before, Some(pomodoro.clone()));
+        self.pomodoros.restore(pomodoro)
+    }
+
+    /// Already within a transaction, which the nested one joins.
+    fn transaction(&self, work: &mut FnMut(&Pomodoros) -> Result<()>) -> Result<()> {
+        work(self)
+    }
+}
+
+/// Refuses a pomodoro running from `start_time` until `end_time`, or on from then if it is
+/// unfinished, if it would overlap any recorded pomodoro other than the one with id `replacing`.
+/// Recorded pomodoros follow one another, so only the last one started before it ends can reach
+/// into it.
+fn check_overlap(pomodoros: &Pomodoros,
+                 start_time: DateTime<UTC>,
+                 end_time: Option<DateTime<UTC>>,
+                 replacing: Option<i32>)
+                 -> Result<()> {
+    let previous = try!(pomodoros.query(PomodoroQuery {
+            limit: 2,
+            offset: 0,
+            since: None,
+            until: end_time,
+        }))
+        .into_iter()
+        .find(|previous| Some(previous.id) != replacing);
+
+    if let Some(previous) = previous {
+        let overlaps = previous.end_time().map(|end| end > start_time).unwrap_or(true);
+        if overlaps {
+            return Err(Error::InvalidStateError(format!("Overlaps the pomodoro started at {}.",
+                                                        previous.work_start_time
+                                                            .format("%F %H:%M:%S"))));
+        }
+    }
+    Ok(())
+}
+
+/// Builds the record of a pomodoro whose work is just starting, at the given place in its cycle.
+fn started_pomodoro(start_time: DateTime<UTC>,
+                    work_duration: Duration,
+                    break_duration: Duration,
+                    tags: Vec<String>,
+                    cycle_position: i32)
+                    -> Pomodoro {
+    Pomodoro {
+        id: 0,
+        work_start_time: start_time,
+        work_end_time: None,
+        break_start_time: None,
+        break_end_time: None,
+        work_length: work_duration,
+        break_length: break_duration,
+        tags: tags,
+        pauses: vec![],
+        cycle_position: cycle_position,
+        extensions: vec![],
+        interruptions: vec![],
+        notes: None,
+        status: PomodoroStatus::InProgress,
+    }
+}
+
+/// Builds the record of a finished pomodoro from its lengths, taking it to have run without
+/// pauses and to have gone straight from work to its break.
+fn logged_pomodoro(start_time: DateTime<UTC>,
+                   work_duration: Duration,
+                   break_duration: Duration,
+                   tags: Vec<String>,
+                   status: PomodoroStatus)
+                   -> Pomodoro {
+    let work_end_time = start_time + work_duration;
+    let (break_start_time, break_end_time) = if status == PomodoroStatus::Completed {
+        (Some(work_end_time), Some(work_end_time + break_duration))
+    } else {
+        (None, None)
+    };
+
+    Pomodoro {
+        id: 0,
+        work_start_time: start_time,
+        work_end_time: Some(work_end_time),
+        break_start_time: break_start_time,
+        break_end_time: break_end_time,
+        work_length: work_duration,
+        break_length: break_duration,
+        tags: tags,
+        pauses: vec![],
+        cycle_position: 1,
+        extensions: vec![],
+        interruptions: vec![],
+        notes: None,
+        status: status,
+    }
+}
+
 #[cfg(test)]
 mod test {
     extern crate mockers;
@@ -121,73 +626,191 @@ mod test {
     use super::*;
 
     use daemon::Command;
+    use daemon::InMemoryPomodoros;
+    use daemon::PomodoroTicker;
     use daemon::clock::Clock;
+    use daemon::io::mio::channel;
     use daemon::pomodoro::Pomodoro;
     use daemon::pomodoro::PomodoroStatus;
-    use daemon::pomodoros::Pomodoros;
+    use daemon::pomodoro_event::PomodoroEventKind;
+    use daemon::pomodoros::{PomodoroQuery, Pomodoros};
+
+    use std::io;
 
-    struct PomodorosStub {}
+    struct ClockStub {
+        fake_time: DateTime<UTC>,
+    }
 
-    impl PomodorosStub {
-        fn new() -> PomodorosStub {
-            PomodorosStub {}
+    impl ClockStub {
+        fn new(fake_time: DateTime<UTC>) -> ClockStub {
+            ClockStub { fake_time: fake_time }
         }
     }
 
-    impl Pomodoros for PomodorosStub {
-        fn create(&self, _: DateTime<UTC>, __: Duration, ___: Duration) -> Result<()> {
-            Ok(())
+    impl Clock for ClockStub {
+        fn current_time(&self) -> DateTime<UTC> {
+            self.fake_time
         }
+    }
 
-        fn last(&self, _: usize) -> Result<Vec<Pomodoro>> {
-            Ok(Vec::new())
-        }
-
-        fn most_recent(&self) -> Option<Pomodoro> {
-            Some(Pomodoro {
-                id: 0,
-                work_start_time: "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap(),
-                work_end_time: None,
-                break_start_time: None,
-                break_end_time: None,
-                work_length: Duration::seconds(5),
-                break_length: Duration::seconds(5),
-                tags: String::from(""),
-                status: PomodoroStatus::InProgress,
-            })
+    /// Refuses to create pomodoros, including within a transaction, and otherwise passes
+    /// everything through to the wrapped store.
+    struct FailingCreate<'a> {
+        pomodoros: &'a Pomodoros,
+    }
+
+    impl<'a> Pomodoros for FailingCreate<'a> {
+        fn create(&self, _: DateTime<UTC>, _: Duration, _: Duration, _: Vec<String>) -> Result<()> {
+            Err(Error::from(String::from("Disk full")))
         }
 
-        fn update(&self, _: i32, __: Pomodoro) -> Result<()> {
-            Ok(())
+        fn last(&self, count: usize) -> Result<Vec<Pomodoro>> {
+            self.pomodoros.last(count)
+        }
+
+        fn query(&self, query: PomodoroQuery) -> Result<Vec<Pomodoro>> {
+            self.pomodoros.query(query)
+        }
+
+        fn most_recent(&self) -> Result<Option<Pomodoro>> {
+            self.pomodoros.most_recent()
+        }
+
+        fn find(&self, id: i32) -> Result<Option<Pomodoro>> {
+            self.pomodoros.find(id)
+        }
+
+        fn update(&self, id: i32, pomodoro: Pomodoro) -> Result<()> {
+            self.pomodoros.update(id, pomodoro)
+        }
+
+        fn insert(&self, _: Pomodoro) -> Result<i32> {
+            Err(Error::from(String::from("Disk full")))
+        }
+
+        fn delete(&self, id: i32) -> Result<()> {
+            self.pomodoros.delete(id)
+        }
+
+        fn restore(&self, pomodoro: Pomodoro) -> Result<()> {
+            self.pomodoros.restore(pomodoro)
+        }
+
+        fn transaction(&self, work: &mut FnMut(&Pomodoros) -> Result<()>) -> Result<()> {
+            self.pomodoros.transaction(&mut |pomodoros: &Pomodoros| {
+                work(&FailingCreate { pomodoros: pomodoros })
+            })
         }
     }
 
-    struct ClockStub {
-        fake_time: DateTime<UTC>,
+    /// Hands the wrapped store itself to transactions, so that a mock sees the calls made
+    /// within them as it would any other.
+    struct PassThroughTransactions<'a> {
+        pomodoros: &'a Pomodoros,
     }
 
-    impl ClockStub {
-        fn new(fake_time: DateTime<UTC>) -> ClockStub {
-            ClockStub { fake_time: fake_time }
+    impl<'a> Pomodoros for PassThroughTransactions<'a> {
+        fn create(&self,
+                  start_time: DateTime<UTC>,
+                  work_duration: Duration,
+                  break_duration: Duration,
+                  tags: Vec<String>)
+                  -> Result<()> {
+            self.pomodoros.create(start_time, work_duration, break_duration, tags)
+        }
+
+        fn last(&self, count: usize) -> Result<Vec<Pomodoro>> {
+            self.pomodoros.last(count)
+        }
+
+        fn query(&self, query: PomodoroQuery) -> Result<Vec<Pomodoro>> {
+            self.pomodoros.query(query)
+        }
+
+        fn most_recent(&self) -> Result<Option<Pomodoro>> {
+            self.pomodoros.most_recent()
+        }
+
+        fn find(&self, id: i32) -> Result<Option<Pomodoro>> {
+            self.pomodoros.find(id)
+        }
+
+        fn update(&self, id: i32, pomodoro: Pomodoro) -> Result<()> {
+            self.pomodoros.update(id, pomodoro)
+        }
+
+        fn insert(&self, pomodoro: Pomodoro) -> Result<i32> {
+            self.pomodoros.insert(pomodoro)
+        }
+
+        fn delete(&self, id: i32) -> Result<()> {
+            self.pomodoros.delete(id)
+        }
+
+        fn restore(&self, pomodoro: Pomodoro) -> Result<()> {
+            self.pomodoros.restore(pomodoro)
+        }
+
+        fn transaction(&self, work: &mut FnMut(&Pomodoros) -> Result<()>) -> Result<()> {
+            work(self.pomodoros)
         }
     }
 
-    impl Clock for ClockStub {
-        fn current_time(&self) -> DateTime<UTC> {
-            self.fake_time
+    /// Fails every read and write, as a database that has gone away would.
+    struct UnavailableStorage {}
+
+    impl Pomodoros for UnavailableStorage {
+        fn create(&self, _: DateTime<UTC>, _: Duration, _: Duration, _: Vec<String>) -> Result<()> {
+            Err(Error::from(io::Error::new(io::ErrorKind::ConnectionRefused, "database is down")))
+        }
+
+        fn last(&self, _: usize) -> Result<Vec<Pomodoro>> {
+            Err(Error::from(io::Error::new(io::ErrorKind::ConnectionRefused, "database is down")))
+        }
+
+        fn query(&self, _: PomodoroQuery) -> Result<Vec<Pomodoro>> {
+            Err(Error::from(io::Error::new(io::ErrorKind::ConnectionRefused, "database is down")))
+        }
+
+        fn most_recent(&self) -> Result<Option<Pomodoro>> {
+            Err(Error::from(io::Error::new(io::ErrorKind::ConnectionRefused, "database is down")))
+        }
+
+        fn find(&self, _: i32) -> Result<Option<Pomodoro>> {
+            Err(Error::from(io::Error::new(io::ErrorKind::ConnectionRefused, "database is down")))
+        }
+
+        fn update(&self, _: i32, _: Pomodoro) -> Result<()> {
+            Err(Error::from(io::Error::new(io::ErrorKind::ConnectionRefused, "database is down")))
+        }
+
+        fn insert(&self, _: Pomodoro) -> Result<i32> {
+            Err(Error::from(io::Error::new(io::ErrorKind::ConnectionRefused, "database is down")))
+        }
+
+        fn delete(&self, _: i32) -> Result<()> {
+            Err(Error::from(io::Error::new(io::ErrorKind::ConnectionRefused, "database is down")))
+        }
+
+        fn restore(&self, _: Pomodoro) -> Result<()> {
+            Err(Error::from(io::Error::new(io::ErrorKind::ConnectionRefused, "database is down")))
+        }
+
+        fn transaction(&self, work: &mut FnMut(&Pomodoros) -> Result<()>) -> Result<()> {
+            work(self)
         }
     }
 
     #[test]
     fn creates_a_new_pomodoro() {
-        let pomodoros_stub = PomodorosStub::new();
+        let pomodoros = InMemoryPomodoros::new();
         let clock_stub =
             ClockStub::new("2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap());
         let command = Command::Start(clock_stub.current_time(),
                                      Duration::seconds(5),
                                      Duration::seconds(5),
                                      vec![]);
-        let processor = CommandProcessor::new(clock_stub, pomodoros_stub);
+        let processor = CommandProcessor::new(clock_stub, pomodoros);
 
         let result = processor.handle_command(command).unwrap();
 
@@ -201,6 +824,7 @@ mod test {
         let current_time = "2000-01-01T00:00:01+00:00".parse::<DateTime<UTC>>().unwrap();
         let clock_stub = ClockStub::new(current_time);
         let most_recent_pomodoro = create_pomodoro(
+            1,
             "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap(),
             None,
             None,
@@ -208,39 +832,40 @@ mod test {
             PomodoroStatus::InProgress,
         );
         let expected_update = create_pomodoro(
+            1,
             "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap(),
             Some("2000-01-01T00:00:01+00:00".parse::<DateTime<UTC>>().unwrap()),
             None,
             None,
             PomodoroStatus::Aborted,
         );
+        let expected_insert = create_pomodoro(0, current_time, None, None, None, PomodoroStatus::InProgress);
         let command = Command::Start(current_time, Duration::seconds(5), Duration::seconds(5), vec![]);
 
         scenario.expect(pomodoros_handle.most_recent()
-            .and_return_clone(Some(most_recent_pomodoro))
+            .and_return(Ok(Some(most_recent_pomodoro.clone()))));
+        scenario.expect(pomodoros_handle.find(expected_update.id)
+            .and_call_clone(move |_| Ok(Some(most_recent_pomodoro.clone())))
             .times(2));
         scenario.expect(pomodoros_handle.update(expected_update.id, expected_update)
             .and_return(Ok(())));
-        scenario.expect(pomodoros_handle.create(
-            current_time,
-            Duration::seconds(5),
-            Duration::seconds(5)
-            ).
-            and_return(Ok(()))
-        );
+        scenario.expect(pomodoros_handle.insert(expected_insert.clone()).and_return(Ok(2)));
+        scenario.expect(pomodoros_handle.find(2)
+            .and_return(Ok(Some(Pomodoro { id: 2, ..expected_insert }))));
 
-        let processor = CommandProcessor::new(clock_stub, pomodoros);
+        let processor = CommandProcessor::new(clock_stub,
+                                              PassThroughTransactions { pomodoros: &pomodoros });
         processor.handle_command(command).unwrap();
     }
 
     #[test]
-    fn completes_last_pomodoro_if_it_was_in_progress_past_work_length_before_creating_a_new_one
-        () {
+    fn skips_the_break_of_the_last_pomodoro_if_its_work_was_done_before_creating_a_new_one() {
         let mut scenario = mockers::Scenario::new();
         let (pomodoros, pomodoros_handle) = scenario.create_mock_for::<Pomodoros>();
         let current_time = "2000-01-01T12:34:56+00:00".parse::<DateTime<UTC>>().unwrap();
         let clock_stub = ClockStub::new(current_time);
         let most_recent_pomodoro = create_pomodoro(
+            1,
             "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap(),
             None,
             None,
@@ -248,32 +873,721 @@ mod test {
             PomodoroStatus::InProgress
         );
         let expected_update = create_pomodoro(
+            1,
             "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap(),
             Some("2000-01-01T00:00:05+00:00".parse::<DateTime<UTC>>().unwrap()),
-            Some(current_time),
-            Some(current_time),
-            PomodoroStatus::Completed,
+            None,
+            None,
+            PomodoroStatus::BreakSkipped,
         );
+        let expected_insert = create_pomodoro(0, current_time, None, None, None, PomodoroStatus::InProgress);
         let command = Command::Start(current_time, Duration::seconds(5), Duration::seconds(5), vec![]);
 
         scenario.expect(pomodoros_handle.most_recent()
-            .and_return_clone(Some(most_recent_pomodoro))
+            .and_return(Ok(Some(most_recent_pomodoro.clone()))));
+        scenario.expect(pomodoros_handle.find(expected_update.id)
+            .and_call_clone(move |_| Ok(Some(most_recent_pomodoro.clone())))
             .times(2));
         scenario.expect(pomodoros_handle.update(expected_update.id, expected_update)
             .and_return(Ok(())));
-        scenario.expect(pomodoros_handle.create(
-            current_time,
-            Duration::seconds(5),
-            Duration::seconds(5)
-            ).
-            and_return(Ok(()))
+        scenario.expect(pomodoros_handle.insert(expected_insert.clone()).and_return(Ok(2)));
+        scenario.expect(pomodoros_handle.find(2)
+            .and_return(Ok(Some(Pomodoro { id: 2, ..expected_insert }))));
+
+        let processor = CommandProcessor::new(clock_stub,
+                                              PassThroughTransactions { pomodoros: &pomodoros });
+        processor.handle_command(command).unwrap();
+    }
+
+    #[test]
+    fn keeps_the_last_pomodoro_closed_and_the_new_one_open_once_started() {
+        let pomodoros = InMemoryPomodoros::new();
+        let current_time = "2000-01-01T00:00:01+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create("2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap(),
+                    Duration::seconds(5),
+                    Duration::seconds(5),
+                    vec![])
+            .unwrap();
+        let expected_update = create_pomodoro(
+            1,
+            "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap(),
+            Some(current_time),
+            None,
+            None,
+            PomodoroStatus::Aborted,
         );
+        let command = Command::Start(current_time, Duration::seconds(5), Duration::seconds(5), vec![]);
+
+        let processor = CommandProcessor::new(ClockStub::new(current_time), &pomodoros);
+        processor.handle_command(command).unwrap();
+
+        let history = pomodoros.last(5).unwrap();
+        assert!(history.len() == 2);
+        assert!(history[0].status == PomodoroStatus::InProgress);
+        assert!(history[1] == expected_update);
+    }
+
+    #[test]
+    fn leaves_the_last_pomodoro_untouched_if_the_new_one_cannot_be_created() {
+        let current_time = "2000-01-01T00:00:01+00:00".parse::<DateTime<UTC>>().unwrap();
+        let pomodoros = InMemoryPomodoros::new();
+        pomodoros.create("2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap(),
+                    Duration::seconds(5),
+                    Duration::seconds(5),
+                    vec![])
+            .unwrap();
+        let original = pomodoros.most_recent().unwrap().unwrap();
+        let command = Command::Start(current_time, Duration::seconds(5), Duration::seconds(5), vec![]);
+
+        let processor = CommandProcessor::new(ClockStub::new(current_time),
+                                              FailingCreate { pomodoros: &pomodoros });
+
+        assert!(processor.handle_command(command).is_err());
+        assert!(pomodoros.last(5).unwrap() == vec![original]);
+    }
+
+    #[test]
+    fn reports_that_there_is_nothing_to_stop_when_no_pomodoro_exists() {
+        let clock_stub =
+            ClockStub::new("2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap());
+        let processor = CommandProcessor::new(clock_stub, InMemoryPomodoros::new());
+
+        let error = processor.handle_command(Command::Stop).unwrap_err();
+
+        assert!(error.code() == "NOT_FOUND");
+    }
+
+    #[test]
+    fn reports_storage_errors_rather_than_a_missing_pomodoro() {
+        let clock_stub =
+            ClockStub::new("2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap());
+        let processor = CommandProcessor::new(clock_stub, UnavailableStorage {});
+
+        assert!(processor.handle_command(Command::Stop).unwrap_err().code() == "STORAGE");
+        assert!(processor.handle_command(Command::Status).unwrap_err().code() == "STORAGE");
+    }
+
+    #[test]
+    fn does_not_start_a_pomodoro_when_the_previous_one_cannot_be_read() {
+        let current_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let processor = CommandProcessor::new(ClockStub::new(current_time), UnavailableStorage {});
+        let command = Command::Start(current_time, Duration::seconds(5), Duration::seconds(5), vec![]);
+
+        assert!(processor.handle_command(command).unwrap_err().code() == "STORAGE");
+    }
+
+    #[test]
+    fn stops_the_work_countdown_while_paused() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(60), Duration::seconds(30), vec![]).unwrap();
+        CommandProcessor::new(ClockStub::new(start_time + Duration::seconds(10)), &pomodoros)
+            .handle_command(Command::Pause)
+            .unwrap();
+
+        let status = CommandProcessor::new(ClockStub::new(start_time + Duration::seconds(40)),
+                                           &pomodoros)
+            .handle_command(Command::Status)
+            .unwrap();
+
+        assert!(status == "00:50 | 00:30 | 1/4");
+        assert!(pomodoros.most_recent().unwrap().unwrap().status == PomodoroStatus::Paused);
+    }
+
+    #[test]
+    fn excludes_time_spent_paused_from_the_remaining_work_time_after_resuming() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(60), Duration::seconds(30), vec![]).unwrap();
+        CommandProcessor::new(ClockStub::new(start_time + Duration::seconds(10)), &pomodoros)
+            .handle_command(Command::Pause)
+            .unwrap();
+        CommandProcessor::new(ClockStub::new(start_time + Duration::seconds(40)), &pomodoros)
+            .handle_command(Command::Resume)
+            .unwrap();
+
+        let status = CommandProcessor::new(ClockStub::new(start_time + Duration::seconds(45)),
+                                           &pomodoros)
+            .handle_command(Command::Status)
+            .unwrap();
+
+        assert!(status == "00:45 | 00:30 | 1/4");
+    }
+
+    #[test]
+    fn refuses_to_resume_a_pomodoro_that_is_not_paused() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(60), Duration::seconds(30), vec![]).unwrap();
+        let processor = CommandProcessor::new(ClockStub::new(start_time), &pomodoros);
+
+        assert!(processor.handle_command(Command::Resume).unwrap_err().code() == "INVALID_STATE");
+    }
 
+    #[test]
+    fn passes_tags_from_the_start_command_to_storage() {
+        let pomodoros = InMemoryPomodoros::new();
+        let clock_stub =
+            ClockStub::new("2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap());
+        let command = Command::Start(clock_stub.current_time(),
+                                     Duration::seconds(5),
+                                     Duration::seconds(5),
+                                     vec![String::from("foo"), String::from("bar")]);
         let processor = CommandProcessor::new(clock_stub, pomodoros);
         processor.handle_command(command).unwrap();
+
+        let result = processor.handle_command(Command::List).unwrap();
+
+        assert!(result ==
+                "#1 [2000-01-01 00:00:00]: InProgress 1/4 (foo,bar) interruptions: 0 internal, 0 external\n");
+    }
+
+    #[test]
+    fn counts_interruptions_in_the_list() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(60), Duration::seconds(30), vec![]).unwrap();
+        let processor = CommandProcessor::new(ClockStub::new(start_time + Duration::seconds(10)),
+                                              &pomodoros);
+
+        let response = processor.handle_command(Command::Interrupt(InterruptionKind::Internal, None))
+            .unwrap();
+        processor.handle_command(Command::Interrupt(InterruptionKind::External,
+                                                    Some(String::from("phone"))))
+            .unwrap();
+        processor.handle_command(Command::Interrupt(InterruptionKind::External, None)).unwrap();
+
+        assert!(response == "Interruption recorded");
+        assert!(processor.handle_command(Command::List).unwrap() ==
+                "#1 [2000-01-01 00:00:00]: InProgress 1/4 () interruptions: 1 internal, 2 external\n");
+    }
+
+    #[test]
+    fn adds_notes_to_the_current_pomodoro() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(60), Duration::seconds(30), vec![]).unwrap();
+        let processor = CommandProcessor::new(ClockStub::new(start_time + Duration::seconds(10)),
+                                              &pomodoros);
+
+        let response = processor.handle_command(Command::Note(None, String::from("wrote the parser")))
+            .unwrap();
+        processor.handle_command(Command::Note(None, String::from("fixed a test"))).unwrap();
+
+        assert!(response == "Note added to pomodoro 1");
+        assert!(pomodoros.most_recent().unwrap().unwrap().notes ==
+                Some(String::from("wrote the parser\nfixed a test")));
+    }
+
+    #[test]
+    fn adds_notes_to_the_pomodoro_with_the_given_id() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(60), Duration::seconds(30), vec![]).unwrap();
+        pomodoros.create(start_time + Duration::seconds(100),
+                         Duration::seconds(60),
+                         Duration::seconds(30),
+                         vec![])
+            .unwrap();
+        let processor = CommandProcessor::new(ClockStub::new(start_time + Duration::seconds(110)),
+                                              &pomodoros);
+
+        processor.handle_command(Command::Note(Some(1), String::from("wrote the parser"))).unwrap();
+
+        assert!(pomodoros.find(1).unwrap().unwrap().notes == Some(String::from("wrote the parser")));
+        assert!(pomodoros.find(2).unwrap().unwrap().notes == None);
+        assert!(processor.handle_command(Command::Note(Some(3), String::from("lost"))).is_err());
+    }
+
+    #[test]
+    fn keeps_notes_when_the_pomodoro_changes_afterwards() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(60), Duration::seconds(30), vec![]).unwrap();
+        let processor = CommandProcessor::new(ClockStub::new(start_time + Duration::seconds(10)),
+                                              &pomodoros);
+
+        processor.handle_command(Command::Note(None, String::from("wrote the parser"))).unwrap();
+        processor.handle_command(Command::Pause).unwrap();
+        processor.handle_command(Command::Stop).unwrap();
+
+        let pomodoro = pomodoros.most_recent().unwrap().unwrap();
+        assert!(pomodoro.status == PomodoroStatus::Aborted);
+        assert!(pomodoro.notes == Some(String::from("wrote the parser")));
+    }
+
+    #[test]
+    fn shows_notes_on_one_line_in_the_list_and_cuts_long_ones_short() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(60), Duration::seconds(30), vec![]).unwrap();
+        let processor = CommandProcessor::new(ClockStub::new(start_time + Duration::seconds(10)),
+                                              &pomodoros);
+
+        processor.handle_command(Command::Note(None, String::from("wrote the parser"))).unwrap();
+        let short_list = processor.handle_command(Command::List).unwrap();
+        processor.handle_command(Command::Note(None, String::from("fixed the flaky test in the poller")))
+            .unwrap();
+        let long_list = processor.handle_command(Command::List).unwrap();
+
+        assert!(short_list ==
+                "#1 [2000-01-01 00:00:00]: InProgress 1/4 () interruptions: 0 internal, 0 external \
+                 notes: wrote the parser\n");
+        assert!(long_list ==
+                "#1 [2000-01-01 00:00:00]: InProgress 1/4 () interruptions: 0 internal, 0 external \
+                 notes: wrote the parser / fixed the flaky test ...\n");
+    }
+
+    #[test]
+    fn logs_a_pomodoro_done_away_from_the_daemon() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T12:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(1500), Duration::seconds(300), vec![])
+            .unwrap();
+        let processor = CommandProcessor::new(ClockStub::new(start_time + Duration::seconds(10)),
+                                              &pomodoros);
+        let logged_start = "2000-01-01T09:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        let response = processor.handle_command(Command::Log(logged_start,
+                                                 Duration::seconds(1500),
+                                                 Duration::seconds(300),
+                                                 vec![String::from("offline")],
+                                                 PomodoroStatus::Completed))
+            .unwrap();
+
+        let logged = pomodoros.find(2).unwrap().unwrap();
+        assert!(response == "Pomodoro logged at 2000-01-01 09:00:00");
+        assert!(logged.work_start_time == logged_start);
+        assert!(logged.work_end_time == Some(logged_start + Duration::seconds(1500)));
+        assert!(logged.break_start_time == Some(logged_start + Duration::seconds(1500)));
+        assert!(logged.break_end_time == Some(logged_start + Duration::seconds(1800)));
+        assert!(logged.tags == vec![String::from("offline")]);
+        assert!(logged.status == PomodoroStatus::Completed);
+        assert!(pomodoros.most_recent().unwrap().unwrap().id == 1);
+    }
+
+    #[test]
+    fn refuses_to_log_a_pomodoro_that_overlaps_the_history() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T12:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(1500), Duration::seconds(300), vec![])
+            .unwrap();
+        let processor = CommandProcessor::new(ClockStub::new(start_time + Duration::seconds(3600)),
+                                              &pomodoros);
+        let log = |offset: i64, status: PomodoroStatus| {
+            processor.handle_command(Command::Log(start_time + Duration::seconds(offset),
+                                                  Duration::seconds(1500),
+                                                  Duration::seconds(300),
+                                                  vec![],
+                                                  status))
+        };
+
+        assert!(log(-1000, PomodoroStatus::Aborted).is_err());
+        assert!(log(600, PomodoroStatus::Completed).is_err());
+        assert!(log(-1500, PomodoroStatus::BreakSkipped).is_ok());
+        assert!(log(-2000, PomodoroStatus::BreakSkipped).is_err());
+        assert!(log(3000, PomodoroStatus::Completed).is_err());
+        assert!(pomodoros.last(5).unwrap().len() == 2);
+    }
+
+    #[test]
+    fn edits_the_pomodoro_with_the_given_id() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let clock_stub = ClockStub::new(start_time + Duration::seconds(7200));
+        let stopped_at = start_time + Duration::seconds(1200);
+        pomodoros.create(start_time, Duration::seconds(1500), Duration::seconds(300), vec![])
+            .unwrap();
+        let processor = CommandProcessor::new(clock_stub, &pomodoros);
+
+        let edits = vec![PomodoroEdit::WorkEndTime(Some(stopped_at)),
+                         PomodoroEdit::Status(PomodoroStatus::Aborted),
+                         PomodoroEdit::Tags(vec![String::from("foo")])];
+
+        let response = processor.handle_command(Command::Edit(1, edits)).unwrap();
+
+        let edited = pomodoros.find(1).unwrap().unwrap();
+        assert!(response == "Pomodoro 1 edited");
+        assert!(edited.work_end_time == Some(stopped_at));
+        assert!(edited.status == PomodoroStatus::Aborted);
+        assert!(edited.tags == vec![String::from("foo")]);
+    }
+
+    #[test]
+    fn refuses_to_reopen_a_pomodoro_other_than_the_most_recent() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let clock_stub = ClockStub::new(start_time + Duration::seconds(7200));
+        start_pomodoros_at(&pomodoros, cycle(4), &[0, 1800]);
+        let processor = CommandProcessor::new(clock_stub, &pomodoros);
+        let original = pomodoros.find(1).unwrap();
+        let edits = vec![PomodoroEdit::WorkEndTime(None),
+                         PomodoroEdit::BreakStartTime(None),
+                         PomodoroEdit::BreakEndTime(None),
+                         PomodoroEdit::Status(PomodoroStatus::InProgress)];
+
+        let result = processor.handle_command(Command::Edit(1, edits));
+
+        assert!(result.is_err());
+        assert!(pomodoros.find(1).unwrap() == original);
+    }
+
+    #[test]
+    fn refuses_to_move_a_pomodoro_over_its_neighbours() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let clock_stub = ClockStub::new(start_time + Duration::seconds(7200));
+        start_pomodoros_at(&pomodoros, cycle(4), &[0, 1800, 3600]);
+        let processor = CommandProcessor::new(clock_stub, &pomodoros);
+        let original = pomodoros.find(2).unwrap();
+        let at = |offset: i64| start_time + Duration::seconds(offset);
+        let edit = |edit: PomodoroEdit| processor.handle_command(Command::Edit(2, vec![edit]));
+
+        assert!(edit(PomodoroEdit::WorkStartTime(at(30))).unwrap_err().code() == "INVALID_STATE");
+        assert!(edit(PomodoroEdit::WorkEndTime(Some(at(3700)))).unwrap_err().code() == "INVALID_STATE");
+        assert!(pomodoros.find(2).unwrap() == original);
+        assert!(edit(PomodoroEdit::WorkStartTime(at(1700))).is_ok());
+    }
+
+    #[test]
+    fn deletes_the_pomodoro_with_the_given_id() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let clock_stub = ClockStub::new(start_time + Duration::seconds(1900));
+        start_pomodoros_at(&pomodoros, cycle(4), &[0, 1800]);
+        let processor = CommandProcessor::new(clock_stub, &pomodoros);
+
+        let response = processor.handle_command(Command::Delete(1)).unwrap();
+
+        assert!(response == "Pomodoro 1 deleted");
+        assert!(processor.handle_command(Command::List).unwrap() ==
+                "#2 [2000-01-01 00:30:00]: InProgress 2/4 () interruptions: 0 internal, 0 external\n");
+        assert!(processor.handle_command(Command::Delete(1)).is_err());
+    }
+
+    #[test]
+    fn answers_with_the_pomodoro_as_changed() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(1500), Duration::seconds(300), vec![]).unwrap();
+        let processor = CommandProcessor::new(ClockStub::new(start_time + Duration::seconds(1200)),
+                                              &pomodoros);
+
+        let response = processor.handle(Command::Stop).unwrap();
+
+        assert!(response ==
+                Response::Done(String::from("Pomodoro aborted"), pomodoros.most_recent().unwrap()));
+        assert!(processor.handle(Command::Status).unwrap().to_string() == "05:00 | 05:00 | 1/4");
+    }
+
+    #[test]
+    fn reopens_a_pomodoro_stopped_by_mistake() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(1500), Duration::seconds(300), vec![]).unwrap();
+        let original = pomodoros.most_recent().unwrap().unwrap();
+        let processor = CommandProcessor::new(ClockStub::new(start_time + Duration::seconds(1200)),
+                                              &pomodoros);
+        processor.handle_command(Command::Stop).unwrap();
+
+        let response = processor.handle_command(Command::Undo).unwrap();
+
+        assert!(response == "Last change undone");
+        assert!(pomodoros.most_recent().unwrap() == Some(original));
+    }
+
+    #[test]
+    fn undoing_a_start_removes_the_new_pomodoro_and_reopens_the_previous_one() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(60), Duration::seconds(30), vec![]).unwrap();
+        let original = pomodoros.most_recent().unwrap().unwrap();
+        let next_start = start_time + Duration::seconds(200);
+        let processor = CommandProcessor::new(ClockStub::new(next_start), &pomodoros);
+        let command = Command::Start(next_start, Duration::seconds(60), Duration::seconds(30), vec![]);
+        processor.handle_command(command).unwrap();
+
+        processor.handle_command(Command::Undo).unwrap();
+
+        assert!(pomodoros.last(5).unwrap() == vec![original]);
+    }
+
+    #[test]
+    fn brings_back_a_deleted_pomodoro_under_its_own_id() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        start_pomodoros_at(&pomodoros, cycle(4), &[0, 1800]);
+        let deleted = pomodoros.find(1).unwrap();
+        let processor = CommandProcessor::new(ClockStub::new(start_time + Duration::seconds(1900)),
+                                              &pomodoros);
+        processor.handle_command(Command::Delete(1)).unwrap();
+
+        processor.handle_command(Command::Undo).unwrap();
+
+        assert!(pomodoros.find(1).unwrap() == deleted);
+        assert!(pomodoros.last(5).unwrap().len() == 2);
+    }
+
+    #[test]
+    fn undoes_only_as_far_back_as_the_history_goes() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(1500), Duration::seconds(300), vec![]).unwrap();
+        let processor = CommandProcessor::new(ClockStub::new(start_time + Duration::seconds(60)),
+                                              &pomodoros);
+        for note in 0..UNDO_HISTORY_LENGTH + 1 {
+            processor.handle_command(Command::Note(None, format!("note {}", note))).unwrap();
+        }
+        assert!(processor.handle_command(Command::Resume).is_err());
+
+        for _ in 0..UNDO_HISTORY_LENGTH {
+            processor.handle_command(Command::Undo).unwrap();
+        }
+
+        assert!(processor.handle_command(Command::Undo).unwrap_err().code() == "NOT_FOUND");
+        assert!(pomodoros.most_recent().unwrap().unwrap().notes == Some(String::from("note 0")));
+    }
+
+    #[test]
+    fn refuses_to_undo_over_a_change_made_by_the_ticker() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let expired_at = start_time + Duration::seconds(70);
+        let command = Command::Start(start_time, Duration::seconds(60), Duration::seconds(30), vec![]);
+        let processor = CommandProcessor::new(ClockStub::new(expired_at), &pomodoros);
+        processor.handle_command(command).unwrap();
+        PomodoroTicker::new(ClockStub::new(expired_at), &pomodoros).tick().unwrap();
+        let expired = pomodoros.most_recent().unwrap();
+
+        let result = processor.handle_command(Command::Undo);
+
+        assert!(result.unwrap_err().code() == "INVALID_STATE");
+        assert!(pomodoros.most_recent().unwrap() == expired);
+        assert!(processor.handle_command(Command::Undo).unwrap_err().code() == "NOT_FOUND");
+    }
+
+    #[test]
+    fn publishes_each_step_a_command_moves_a_pomodoro_on_to() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(1500), Duration::seconds(300), vec![]).unwrap();
+        let (sender, receiver) = channel::channel();
+        let processor = CommandProcessor::with_events(ClockStub::new(start_time + Duration::seconds(1600)),
+                                                      &pomodoros,
+                                                      CycleConfig::default(),
+                                                      EventPublisher::new(sender));
+
+        processor.handle(Command::StartBreak).unwrap();
+        processor.handle(Command::Pause).unwrap();
+        processor.handle(Command::Resume).unwrap();
+        processor.handle(Command::Start(start_time + Duration::seconds(1600),
+                                    Duration::seconds(1500),
+                                    Duration::seconds(300),
+                                    vec![]))
+            .unwrap();
+        processor.handle(Command::Undo).unwrap();
+
+        let kinds: Vec<PomodoroEventKind> = (0..4).map(|_| receiver.try_recv().unwrap().kind).collect();
+        assert!(kinds ==
+                vec![PomodoroEventKind::BreakStarted,
+                     PomodoroEventKind::Completed,
+                     PomodoroEventKind::Started,
+                     PomodoroEventKind::Reopened]);
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn reopens_rather_than_starts_a_pomodoro_extended_after_its_work_ran_out() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(60), Duration::seconds(30), vec![]).unwrap();
+        let (sender, receiver) = channel::channel();
+        let processor = CommandProcessor::with_events(ClockStub::new(start_time + Duration::seconds(70)),
+                                                      &pomodoros,
+                                                      CycleConfig::default(),
+                                                      EventPublisher::new(sender));
+
+        processor.handle(Command::Extend(Duration::seconds(300))).unwrap();
+
+        let event = receiver.try_recv().unwrap();
+        assert!(event.kind == PomodoroEventKind::Reopened);
+        assert!(event.pomodoro.status == PomodoroStatus::InProgress);
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn reopens_rather_than_starts_a_pomodoro_when_its_abort_is_undone() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(60), Duration::seconds(30), vec![]).unwrap();
+        let (sender, receiver) = channel::channel();
+        let processor = CommandProcessor::with_events(ClockStub::new(start_time + Duration::seconds(10)),
+                                                      &pomodoros,
+                                                      CycleConfig::default(),
+                                                      EventPublisher::new(sender));
+
+        processor.handle(Command::Stop).unwrap();
+        processor.handle(Command::Undo).unwrap();
+
+        let kinds: Vec<PomodoroEventKind> = (0..2).map(|_| receiver.try_recv().unwrap().kind).collect();
+        assert!(kinds == vec![PomodoroEventKind::Aborted, PomodoroEventKind::Reopened]);
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn records_the_break_taken_before_the_next_pomodoro() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(60), Duration::seconds(30), vec![]).unwrap();
+        let break_clock = ClockStub::new(start_time + Duration::seconds(70));
+        let response = CommandProcessor::new(break_clock, &pomodoros)
+            .handle_command(Command::StartBreak)
+            .unwrap();
+        let next_start = start_time + Duration::seconds(90);
+        let command = Command::Start(next_start, Duration::seconds(60), Duration::seconds(30), vec![]);
+        CommandProcessor::new(ClockStub::new(next_start), &pomodoros)
+            .handle_command(command)
+            .unwrap();
+
+        let previous = pomodoros.last(2).unwrap().pop().unwrap();
+        assert!(response == "Break started");
+        assert!(previous.status == PomodoroStatus::Completed);
+        assert!(previous.work_end_time == Some(start_time + Duration::seconds(60)));
+        assert!(previous.break_start_time == Some(start_time + Duration::seconds(70)));
+        assert!(previous.break_end_time == Some(next_start));
+    }
+
+    #[test]
+    fn starts_the_pending_break_when_sent_startbreak() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(60), Duration::seconds(30), vec![]).unwrap();
+        let now = start_time + Duration::seconds(70);
+        let command = Command::from_string(now, String::from("STARTBREAK")).unwrap();
+
+        let response = CommandProcessor::new(ClockStub::new(now), &pomodoros)
+            .handle_command(command)
+            .unwrap();
+
+        let history = pomodoros.last(5).unwrap();
+        assert!(response == "Break started");
+        assert!(history.len() == 1);
+        assert!(history[0].status == PomodoroStatus::Break);
+    }
+
+    #[test]
+    fn skips_a_pending_break() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(60), Duration::seconds(30), vec![]).unwrap();
+
+        let clock_stub = ClockStub::new(start_time + Duration::seconds(70));
+        let response = CommandProcessor::new(clock_stub, &pomodoros)
+            .handle_command(Command::SkipBreak)
+            .unwrap();
+
+        assert!(response == "Break skipped");
+        assert!(pomodoros.most_recent().unwrap().unwrap().status == PomodoroStatus::BreakSkipped);
+    }
+
+    #[test]
+    fn refuses_to_start_a_break_while_still_working() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(60), Duration::seconds(30), vec![]).unwrap();
+        let clock_stub = ClockStub::new(start_time + Duration::seconds(10));
+        let processor = CommandProcessor::new(clock_stub, &pomodoros);
+
+        assert!(processor.handle_command(Command::StartBreak).unwrap_err().code() == "INVALID_STATE");
+    }
+
+    #[test]
+    fn shows_extended_time_in_the_status() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(60), Duration::seconds(30), vec![]).unwrap();
+        let clock_stub = ClockStub::new(start_time + Duration::seconds(50));
+        let processor = CommandProcessor::new(clock_stub, &pomodoros);
+
+        let response = processor.handle_command(Command::Extend(Duration::seconds(300))).unwrap();
+        let status = processor.handle_command(Command::Status).unwrap();
+
+        assert!(response == "Pomodoro extended by 05:00");
+        assert!(status == "05:10 (+05:00) | 00:30 | 1/4");
+    }
+
+    fn cycle(length: i32) -> CycleConfig {
+        CycleConfig {
+            length: length,
+            long_break_length: Duration::seconds(900),
+            reset_after: Duration::seconds(3600),
+        }
+    }
+
+    /// Starts a pomodoro at each of the given offsets from midnight, in seconds, each lasting
+    /// 60 seconds with a 30 second break.
+    fn start_pomodoros_at(pomodoros: &InMemoryPomodoros, cycle: CycleConfig, offsets: &[i64]) {
+        let midnight = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        for offset in offsets {
+            let start_time = midnight + Duration::seconds(*offset);
+            let command = Command::Start(start_time, Duration::seconds(60), Duration::seconds(30), vec![]);
+            CommandProcessor::with_cycle(ClockStub::new(start_time), pomodoros, cycle.clone())
+                .handle_command(command)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn gives_a_long_break_to_the_pomodoro_that_ends_a_cycle() {
+        let pomodoros = InMemoryPomodoros::new();
+
+        start_pomodoros_at(&pomodoros, cycle(3), &[0, 90, 180, 1080]);
+
+        let history = pomodoros.last(5).unwrap();
+        assert!(history.iter().map(|p| p.cycle_position).collect::<Vec<i32>>() == vec![1, 3, 2, 1]);
+        assert!(history.iter().map(|p| p.break_length).collect::<Vec<Duration>>() ==
+                vec![Duration::seconds(30),
+                     Duration::seconds(900),
+                     Duration::seconds(30),
+                     Duration::seconds(30)]);
+    }
+
+    #[test]
+    fn starts_a_new_cycle_after_an_aborted_pomodoro() {
+        let pomodoros = InMemoryPomodoros::new();
+
+        start_pomodoros_at(&pomodoros, cycle(4), &[0, 90, 120]);
+
+        let history = pomodoros.last(5).unwrap();
+        assert!(history.iter().map(|p| p.cycle_position).collect::<Vec<i32>>() == vec![1, 2, 1]);
+        assert!(history[1].status == PomodoroStatus::Aborted);
+    }
+
+    #[test]
+    fn starts_a_new_cycle_after_a_long_idle_gap() {
+        let pomodoros = InMemoryPomodoros::new();
+
+        start_pomodoros_at(&pomodoros, cycle(4), &[0, 90, 180 + 3600 + 1]);
+
+        let history = pomodoros.last(5).unwrap();
+        assert!(history.iter().map(|p| p.cycle_position).collect::<Vec<i32>>() == vec![1, 2, 1]);
+    }
+
+    #[test]
+    fn shows_the_cycle_position_in_the_status() {
+        let pomodoros = InMemoryPomodoros::new();
+        start_pomodoros_at(&pomodoros, cycle(4), &[0, 90]);
+        let now = "2000-01-01T00:01:40+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        let status = CommandProcessor::with_cycle(ClockStub::new(now), &pomodoros, cycle(4))
+            .handle_command(Command::Status)
+            .unwrap();
+
+        assert!(status == "00:50 | 00:30 | 2/4");
     }
 
     fn create_pomodoro(
+        id: i32,
         work_start_time: DateTime<UTC>,
         work_end_time: Option<DateTime<UTC>>,
         break_start_time: Option<DateTime<UTC>>,
@@ -281,14 +1595,19 @@ mod test {
         status: PomodoroStatus
         ) -> Pomodoro {
         Pomodoro {
-            id: 0,
+            id: id,
             work_start_time: work_start_time,
             work_end_time: work_end_time,
             break_start_time: break_start_time,
             break_end_time: break_end_time,
             work_length: Duration::seconds(5),
             break_length: Duration::seconds(5),
-            tags: String::from(""),
+            tags: vec![],
+            pauses: vec![],
+            cycle_position: 1,
+            extensions: vec![],
+            interruptions: vec![],
+            notes: None,
             status: status
         }
     }
@@ -0,0 +1,548 @@
+use daemon::chrono::Duration;
+use daemon::regex::Regex;
+
+use daemon::result::Error;
+use daemon::result::Result;
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::iter::FromIterator;
+use std::path::{Path, PathBuf};
+use std::result::Result as StdResult;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SslMode {
+    Disable,
+    Prefer,
+    Require,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub ssl_mode: SslMode,
+    pub schema: Option<String>,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    pub pool_size: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StorageBackend {
+    Journal(PathBuf),
+    Memory,
+    Postgres(DatabaseConfig),
+    Sqlite(PathBuf),
+}
+
+/// How pomodoros are grouped into cycles. The pomodoro at position `length` of a cycle gets a
+/// long break, and the next one starts a new cycle. A cycle also starts over when a pomodoro is
+/// aborted, or when no pomodoro is started for `reset_after` once the last break ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CycleConfig {
+    pub length: i32,
+    pub long_break_length: Duration,
+    pub reset_after: Duration,
+}
+
+impl Default for CycleConfig {
+    fn default() -> CycleConfig {
+        CycleConfig {
+            length: 4,
+            long_break_length: Duration::minutes(15),
+            reset_after: Duration::hours(1),
+        }
+    }
+}
+
+/// Limits on requests read from the command socket. A request over `max_request_length` bytes
+/// is refused, and a client that takes longer than `request_timeout` to send one, or to take in
+/// a response, is dropped. A client may keep its connection open for more requests, but it is
+/// closed once no request has started for `idle_timeout`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FramingConfig {
+    pub max_request_length: usize,
+    pub request_timeout: Duration,
+    pub idle_timeout: Duration,
+}
+
+impl Default for FramingConfig {
+    fn default() -> FramingConfig {
+        FramingConfig {
+            max_request_length: 64 * 1024,
+            request_timeout: Duration::seconds(5),
+            idle_timeout: Duration::seconds(60),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub storage: StorageBackend,
+    pub cycle: CycleConfig,
+    pub framing: FramingConfig,
+}
+
+/// Settings recognised in the configuration file. Each one can be overridden by the environment
+/// variable of the same name in upper case, prefixed with SOLANUM_ (e.g. SOLANUM_DATABASE_URL).
+const SETTINGS: &'static [&'static str] = &["storage",
+                                            "sqlite_path",
+                                            "journal_path",
+                                            "database_url",
+                                            "database_sslmode",
+                                            "database_schema",
+                                            "database_user",
+                                            "database_password",
+                                            "database_pool_size",
+                                            "cycle_length",
+                                            "long_break_length",
+                                            "cycle_reset_after",
+                                            "max_request_length",
+                                            "request_timeout",
+                                            "session_idle_timeout"];
+
+const DEFAULT_DATABASE_URL: &'static str = "postgres://postgres@localhost:5432/solanum_test";
+
+impl Config {
+    /// Loads the daemon configuration from a configuration file and the SOLANUM_* environment
+    /// variables, which take precedence over the file.
+    ///
+    /// The file is read from `path` if one is given, otherwise from $SOLANUM_CONFIG, otherwise
+    /// from $XDG_CONFIG_HOME/solanum/solanumd.conf (or ~/.config/solanum/solanumd.conf) if it
+    /// exists. It holds one `setting = value` pair per line; lines starting with # are ignored.
+    ///
+    /// `storage` selects the backend ("postgres", "sqlite", "journal" or "memory", defaulting to
+    /// "postgres"). `sqlite_path` and `journal_path` override the location of the SQLite data
+    /// file and the journal, which otherwise live in $XDG_DATA_HOME/solanum (or
+    /// ~/.local/share/solanum). The `database_*` settings configure the PostgreSQL connection.
+    ///
+    /// `cycle_length` is the number of pomodoros in a cycle, `long_break_length` the length in
+    /// seconds of the break that ends one, and `cycle_reset_after` the number of idle seconds
+    /// after which the cycle starts over.
+    ///
+    /// `max_request_length` is the largest request in bytes the daemon accepts,
+    /// `request_timeout` the number of seconds a client has to send the whole of one, and
+    /// `session_idle_timeout` the number of seconds a connection is kept open without a request.
+    pub fn load(path: Option<&Path>) -> Result<Config> {
+        let environment: HashMap<String, String> = HashMap::from_iter(env::vars());
+        let explicit_path = path.map(|path| path.to_path_buf())
+            .or(environment.get("SOLANUM_CONFIG").map(PathBuf::from));
+
+        let mut vars = match explicit_path {
+            Some(path) => try!(Config::read_file(&path)),
+            None => {
+                match Config::config_dir(&environment).map(|dir| dir.join("solanumd.conf")) {
+                    Some(ref path) if path.exists() => try!(Config::read_file(path)),
+                    _ => HashMap::new(),
+                }
+            }
+        };
+        vars.extend(environment);
+
+        Config::from_vars(vars)
+    }
+
+    pub fn from_vars(vars: HashMap<String, String>) -> Result<Config> {
+        let storage = match vars.get("SOLANUM_STORAGE").map(|s| s.as_str()) {
+            None | Some("postgres") => StorageBackend::Postgres(try!(Config::database_config(&vars))),
+            Some("journal") => {
+                StorageBackend::Journal(try!(Config::data_file(&vars, "SOLANUM_JOURNAL_PATH", "journal.jsonl")))
+            }
+            Some("memory") => StorageBackend::Memory,
+            Some("sqlite") => {
+                StorageBackend::Sqlite(try!(Config::data_file(&vars, "SOLANUM_SQLITE_PATH", "solanum.sqlite3")))
+            }
+            Some(other) => {
+                return Err(Error::ConfigError(format!("Unknown storage backend: {}", other)))
+            }
+        };
+
+        Ok(Config {
+            storage: storage,
+            cycle: try!(Config::cycle_config(&vars)),
+            framing: try!(Config::framing_config(&vars)),
+        })
+    }
+
+    /// Keeps all pomodoros in memory for the lifetime of the daemon, regardless of any configured
+    /// storage backend.
+    pub fn ephemeral(self) -> Config {
+        Config { storage: StorageBackend::Memory, ..self }
+    }
+
+    /// Parses a configuration file into the SOLANUM_* variables its settings correspond to.
+    fn read_file(path: &Path) -> Result<HashMap<String, String>> {
+        let mut contents = String::new();
+        try!(fs::File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|e| Error::ConfigError(format!("Could not read {}: {}", path.display(), e))));
+
+        Config::parse(&contents)
+            .map_err(|e| Error::ConfigError(format!("{}: {}", path.display(), e)))
+    }
+
+    fn parse(contents: &str) -> StdResult<HashMap<String, String>, String> {
+        let mut vars = HashMap::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("#") {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, "=");
+            let key = parts.next().unwrap_or("").trim();
+            let value = try!(parts.next()
+                    .ok_or(format!("line {}: expected `setting = value`", number + 1)))
+                .trim()
+                .trim_matches('"');
+
+            if !SETTINGS.contains(&key) {
+                return Err(format!("line {}: unknown setting {}", number + 1, key));
+            }
+            vars.insert(format!("SOLANUM_{}", key.to_uppercase()), String::from(value));
+        }
+
+        Ok(vars)
+    }
+
+    fn database_config(vars: &HashMap<String, String>) -> Result<DatabaseConfig> {
+        let ssl_mode = match vars.get("SOLANUM_DATABASE_SSLMODE").map(|s| s.as_str()) {
+            None | Some("disable") => SslMode::Disable,
+            Some("prefer") => SslMode::Prefer,
+            Some("require") => SslMode::Require,
+            Some(other) => {
+                return Err(Error::ConfigError(format!("Unknown database_sslmode {}; expected disable, \
+                                                       prefer or require",
+                                                      other)))
+            }
+        };
+
+        let schema = vars.get("SOLANUM_DATABASE_SCHEMA").cloned();
+        if let Some(ref schema) = schema {
+            let identifier = Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*$").unwrap();
+            if !identifier.is_match(schema) {
+                return Err(Error::ConfigError(format!("Invalid database_schema {}; expected a plain \
+                                                       SQL identifier",
+                                                      schema)));
+            }
+        }
+
+        let url = vars.get("SOLANUM_DATABASE_URL")
+            .cloned()
+            .unwrap_or(String::from(DEFAULT_DATABASE_URL));
+        if !url.starts_with("postgres://") && !url.starts_with("postgresql://") {
+            return Err(Error::ConfigError(format!("Invalid database_url {}; expected a postgres:// URL",
+                                                  url)));
+        }
+
+        let pool_size = match vars.get("SOLANUM_DATABASE_POOL_SIZE") {
+            None => 4,
+            Some(size) => {
+                match size.parse::<usize>() {
+                    Ok(size) if size > 0 => size,
+                    _ => {
+                        return Err(Error::ConfigError(format!("Invalid database_pool_size {}; expected a \
+                                                               positive number",
+                                                              size)))
+                    }
+                }
+            }
+        };
+
+        Ok(DatabaseConfig {
+            url: url,
+            ssl_mode: ssl_mode,
+            schema: schema,
+            user: vars.get("SOLANUM_DATABASE_USER").cloned(),
+            password: vars.get("SOLANUM_DATABASE_PASSWORD").cloned(),
+            pool_size: pool_size,
+        })
+    }
+
+    fn cycle_config(vars: &HashMap<String, String>) -> Result<CycleConfig> {
+        let defaults = CycleConfig::default();
+        let length = try!(Config::positive_number(vars, "cycle_length", defaults.length as i64));
+        let long_break_length = try!(Config::positive_number(vars,
+                                                             "long_break_length",
+                                                             defaults.long_break_length.num_seconds()));
+        let reset_after = try!(Config::positive_number(vars,
+                                                       "cycle_reset_after",
+                                                       defaults.reset_after.num_seconds()));
+
+        Ok(CycleConfig {
+            length: length as i32,
+            long_break_length: Duration::seconds(long_break_length),
+            reset_after: Duration::seconds(reset_after),
+        })
+    }
+
+    fn framing_config(vars: &HashMap<String, String>) -> Result<FramingConfig> {
+        let defaults = FramingConfig::default();
+        let max_request_length = try!(Config::positive_number(vars,
+                                                              "max_request_length",
+                                                              defaults.max_request_length as i64));
+        let request_timeout = try!(Config::positive_number(vars,
+                                                           "request_timeout",
+                                                           defaults.request_timeout.num_seconds()));
+        let idle_timeout = try!(Config::positive_number(vars,
+                                                        "session_idle_timeout",
+                                                        defaults.idle_timeout.num_seconds()));
+
+        Ok(FramingConfig {
+            max_request_length: max_request_length as usize,
+            request_timeout: Duration::seconds(request_timeout),
+            idle_timeout: Duration::seconds(idle_timeout),
+        })
+    }
+
+    fn positive_number(vars: &HashMap<String, String>, setting: &str, default: i64) -> Result<i64> {
+        match vars.get(&format!("SOLANUM_{}", setting.to_uppercase())) {
+            None => Ok(default),
+            Some(value) => {
+                match value.parse::<i64>() {
+                    Ok(number) if number > 0 && number <= i32::max_value() as i64 => Ok(number),
+                    _ => {
+                        Err(Error::ConfigError(format!("Invalid {} {}; expected a positive number",
+                                                       setting,
+                                                       value)))
+                    }
+                }
+            }
+        }
+    }
+
+    /// Gives the data file named by `variable`, or `file_name` in the data directory. A relative
+    /// path is taken from the directory the daemon was started in, since the daemon moves to /
+    /// once it detaches.
+    fn data_file(vars: &HashMap<String, String>, variable: &str, file_name: &str) -> Result<PathBuf> {
+        let path = match vars.get(variable) {
+            Some(path) => PathBuf::from(path),
+            None => try!(Config::data_dir(vars)).join(file_name),
+        };
+        if path.is_absolute() {
+            return Ok(path);
+        }
+
+        env::current_dir()
+            .map(|dir| dir.join(path))
+            .map_err(|e| Error::ConfigError(format!("Could not resolve {}: {}", variable, e)))
+    }
+
+    fn data_dir(vars: &HashMap<String, String>) -> Result<PathBuf> {
+        match (vars.get("XDG_DATA_HOME"), vars.get("HOME")) {
+            (Some(data_home), _) if !data_home.is_empty() => Ok(PathBuf::from(data_home).join("solanum")),
+            (_, Some(home)) => Ok(PathBuf::from(home).join(".local/share/solanum")),
+            _ => Err(Error::ConfigError(String::from("Could not determine a data directory; set HOME or XDG_DATA_HOME"))),
+        }
+    }
+
+    fn config_dir(vars: &HashMap<String, String>) -> Option<PathBuf> {
+        match (vars.get("XDG_CONFIG_HOME"), vars.get("HOME")) {
+            (Some(config_home), _) if !config_home.is_empty() => Some(PathBuf::from(config_home).join("solanum")),
+            (_, Some(home)) => Some(PathBuf::from(home).join(".config/solanum")),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        HashMap::from_iter(pairs.iter().map(|&(k, v)| (String::from(k), String::from(v))))
+    }
+
+    #[test]
+    fn defaults_to_postgres_storage_on_the_test_database() {
+        let config = Config::from_vars(vars(&[("HOME", "/home/user")])).unwrap();
+
+        assert!(config.storage ==
+                StorageBackend::Postgres(DatabaseConfig {
+                    url: String::from("postgres://postgres@localhost:5432/solanum_test"),
+                    ssl_mode: SslMode::Disable,
+                    schema: None,
+                    user: None,
+                    password: None,
+                    pool_size: 4,
+                }));
+    }
+
+    #[test]
+    fn reads_database_settings() {
+        let config = Config::from_vars(vars(&[("SOLANUM_DATABASE_URL", "postgres://db.example.com/solanum"),
+                                              ("SOLANUM_DATABASE_SSLMODE", "require"),
+                                              ("SOLANUM_DATABASE_SCHEMA", "production"),
+                                              ("SOLANUM_DATABASE_USER", "solanum"),
+                                              ("SOLANUM_DATABASE_PASSWORD", "hunter2"),
+                                              ("SOLANUM_DATABASE_POOL_SIZE", "8")]))
+            .unwrap();
+
+        assert!(config.storage ==
+                StorageBackend::Postgres(DatabaseConfig {
+                    url: String::from("postgres://db.example.com/solanum"),
+                    ssl_mode: SslMode::Require,
+                    schema: Some(String::from("production")),
+                    user: Some(String::from("solanum")),
+                    password: Some(String::from("hunter2")),
+                    pool_size: 8,
+                }));
+    }
+
+    #[test]
+    fn rejects_unknown_ssl_modes() {
+        let config = Config::from_vars(vars(&[("SOLANUM_DATABASE_SSLMODE", "sometimes")]));
+
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn rejects_empty_connection_pools() {
+        let config = Config::from_vars(vars(&[("SOLANUM_DATABASE_POOL_SIZE", "0")]));
+
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn rejects_schema_names_that_are_not_identifiers() {
+        let config = Config::from_vars(vars(&[("SOLANUM_DATABASE_SCHEMA", "public; DROP TABLE pomodoros")]));
+
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn rejects_urls_that_are_not_postgres_urls() {
+        let config = Config::from_vars(vars(&[("SOLANUM_DATABASE_URL", "mysql://localhost/solanum")]));
+
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn parses_settings_from_a_configuration_file() {
+        let contents = "# production daemon\n\
+                        storage = postgres\n\
+                        database_url = \"postgres://db.example.com/solanum\"\n";
+
+        let settings = Config::parse(contents).unwrap();
+
+        assert!(settings ==
+                vars(&[("SOLANUM_STORAGE", "postgres"),
+                       ("SOLANUM_DATABASE_URL", "postgres://db.example.com/solanum")]));
+    }
+
+    #[test]
+    fn reports_the_line_of_an_unknown_setting() {
+        let error = Config::parse("storage = postgres\ndatabse_url = postgres://localhost/solanum")
+            .unwrap_err();
+
+        assert!(error == "line 2: unknown setting databse_url");
+    }
+
+    #[test]
+    fn places_sqlite_data_file_in_the_users_data_directory() {
+        let config = Config::from_vars(vars(&[("SOLANUM_STORAGE", "sqlite"), ("HOME", "/home/user")]))
+            .unwrap();
+
+        assert!(config.storage ==
+                StorageBackend::Sqlite(PathBuf::from("/home/user/.local/share/solanum/solanum.sqlite3")));
+    }
+
+    #[test]
+    fn prefers_an_explicit_sqlite_path() {
+        let config = Config::from_vars(vars(&[("SOLANUM_STORAGE", "sqlite"),
+                                              ("SOLANUM_SQLITE_PATH", "/tmp/pomodoros.db"),
+                                              ("HOME", "/home/user")]))
+            .unwrap();
+
+        assert!(config.storage == StorageBackend::Sqlite(PathBuf::from("/tmp/pomodoros.db")));
+    }
+
+    #[test]
+    fn resolves_a_relative_data_file_against_the_directory_the_daemon_started_in() {
+        let config = Config::from_vars(vars(&[("SOLANUM_STORAGE", "journal"),
+                                              ("SOLANUM_JOURNAL_PATH", "data/journal.jsonl")]))
+            .unwrap();
+
+        assert!(config.storage ==
+                StorageBackend::Journal(env::current_dir().unwrap().join("data/journal.jsonl")));
+    }
+
+    #[test]
+    fn places_the_journal_in_the_users_data_directory() {
+        let config = Config::from_vars(vars(&[("SOLANUM_STORAGE", "journal"),
+                                              ("XDG_DATA_HOME", "/home/user/data")]))
+            .unwrap();
+
+        assert!(config.storage ==
+                StorageBackend::Journal(PathBuf::from("/home/user/data/solanum/journal.jsonl")));
+    }
+
+    #[test]
+    fn ephemeral_mode_overrides_the_configured_backend() {
+        let config = Config::from_vars(vars(&[("SOLANUM_STORAGE", "sqlite"), ("HOME", "/home/user")]))
+            .unwrap()
+            .ephemeral();
+
+        assert!(config.storage == StorageBackend::Memory);
+    }
+
+    #[test]
+    fn defaults_to_a_long_break_after_four_pomodoros() {
+        let config = Config::from_vars(vars(&[("HOME", "/home/user")])).unwrap();
+
+        assert!(config.cycle ==
+                CycleConfig {
+                    length: 4,
+                    long_break_length: Duration::seconds(900),
+                    reset_after: Duration::seconds(3600),
+                });
+    }
+
+    #[test]
+    fn reads_cycle_settings() {
+        let config = Config::from_vars(vars(&[("SOLANUM_CYCLE_LENGTH", "3"),
+                                              ("SOLANUM_LONG_BREAK_LENGTH", "1200"),
+                                              ("SOLANUM_CYCLE_RESET_AFTER", "7200")]))
+            .unwrap();
+
+        assert!(config.cycle ==
+                CycleConfig {
+                    length: 3,
+                    long_break_length: Duration::seconds(1200),
+                    reset_after: Duration::seconds(7200),
+                });
+    }
+
+    #[test]
+    fn reads_request_limits() {
+        let config = Config::from_vars(vars(&[("SOLANUM_MAX_REQUEST_LENGTH", "4096"),
+                                              ("SOLANUM_REQUEST_TIMEOUT", "2"),
+                                              ("SOLANUM_SESSION_IDLE_TIMEOUT", "600")]))
+            .unwrap();
+
+        assert!(config.framing ==
+                FramingConfig {
+                    max_request_length: 4096,
+                    request_timeout: Duration::seconds(2),
+                    idle_timeout: Duration::seconds(600),
+                });
+        assert!(Config::from_vars(vars(&[("SOLANUM_MAX_REQUEST_LENGTH", "0")])).is_err());
+    }
+
+    #[test]
+    fn rejects_empty_cycles() {
+        let config = Config::from_vars(vars(&[("SOLANUM_CYCLE_LENGTH", "0")]));
+
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_storage_backends() {
+        let config = Config::from_vars(vars(&[("SOLANUM_STORAGE", "mongodb")]));
+
+        assert!(config.is_err());
+    }
+}
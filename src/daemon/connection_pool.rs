@@ -0,0 +1,216 @@
+use daemon::result::Error;
+use daemon::result::Result;
+
+use std::ops::{Deref, DerefMut};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+struct PoolState<C> {
+    idle: Vec<C>,
+    open: usize,
+}
+
+/// A small, fixed-size pool of connections shared by concurrently running event handlers.
+///
+/// Idle connections are health-checked when they are checked out; a connection that fails the
+/// check (e.g. because the database server restarted) is discarded and replaced by a new one.
+/// Once `max_size` connections are open, callers wait up to `checkout_timeout` for one to be
+/// returned rather than opening more.
+pub struct ConnectionPool<C> {
+    connect: Box<Fn() -> Result<C> + Send + Sync>,
+    is_healthy: Box<Fn(&C) -> bool + Send + Sync>,
+    max_size: usize,
+    checkout_timeout: Duration,
+    state: Mutex<PoolState<C>>,
+    returned: Condvar,
+}
+
+pub struct PooledConnection<'a, C: 'a> {
+    pool: &'a ConnectionPool<C>,
+    connection: Option<C>,
+}
+
+impl<C> ConnectionPool<C> {
+    pub fn new(max_size: usize,
+               checkout_timeout: Duration,
+               connect: Box<Fn() -> Result<C> + Send + Sync>,
+               is_healthy: Box<Fn(&C) -> bool + Send + Sync>)
+               -> ConnectionPool<C> {
+        ConnectionPool {
+            connect: connect,
+            is_healthy: is_healthy,
+            max_size: max_size,
+            checkout_timeout: checkout_timeout,
+            state: Mutex::new(PoolState {
+                idle: Vec::new(),
+                open: 0,
+            }),
+            returned: Condvar::new(),
+        }
+    }
+
+    pub fn get(&self) -> Result<PooledConnection<C>> {
+        let deadline = Instant::now() + self.checkout_timeout;
+        let mut state = try!(self.lock_state());
+
+        loop {
+            while let Some(connection) = state.idle.pop() {
+                if (self.is_healthy)(&connection) {
+                    return Ok(PooledConnection {
+                        pool: self,
+                        connection: Some(connection),
+                    });
+                }
+                warn!("Discarding unhealthy pooled connection");
+                state.open -= 1;
+            }
+
+            if state.open < self.max_size {
+                state.open += 1;
+                drop(state);
+                return self.open_connection();
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::from(format!("Timed out waiting for one of {} database connections",
+                                               self.max_size)));
+            }
+            state = try!(self.returned
+                .wait_timeout(state, deadline - now)
+                .map(|(state, _)| state)
+                .map_err(|_| Error::from(String::from("Connection pool lock was poisoned"))));
+        }
+    }
+
+    /// Opens a connection for a slot that has already been reserved in `open`.
+    fn open_connection(&self) -> Result<PooledConnection<C>> {
+        match (self.connect)() {
+            Ok(connection) => {
+                Ok(PooledConnection {
+                    pool: self,
+                    connection: Some(connection),
+                })
+            }
+            Err(e) => {
+                if let Ok(mut state) = self.lock_state() {
+                    state.open -= 1;
+                }
+                self.returned.notify_one();
+                Err(e)
+            }
+        }
+    }
+
+    fn release(&self, connection: C) {
+        if let Ok(mut state) = self.lock_state() {
+            state.idle.push(connection);
+        }
+        self.returned.notify_one();
+    }
+
+    fn lock_state(&self) -> Result<MutexGuard<PoolState<C>>> {
+        self.state
+            .lock()
+            .map_err(|_| Error::from(String::from("Connection pool lock was poisoned")))
+    }
+}
+
+impl<'a, C> Deref for PooledConnection<'a, C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        self.connection.as_ref().unwrap()
+    }
+}
+
+impl<'a, C> DerefMut for PooledConnection<'a, C> {
+    fn deref_mut(&mut self) -> &mut C {
+        self.connection.as_mut().unwrap()
+    }
+}
+
+impl<'a, C> Drop for PooledConnection<'a, C> {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            self.pool.release(connection);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn counting_pool(max_size: usize,
+                     connections_opened: Arc<AtomicUsize>,
+                     healthy: bool)
+                     -> ConnectionPool<usize> {
+        ConnectionPool::new(max_size,
+                            Duration::from_millis(10),
+                            Box::new(move || Ok(connections_opened.fetch_add(1, Ordering::SeqCst))),
+                            Box::new(move |_| healthy))
+    }
+
+    #[test]
+    fn reuses_returned_connections() {
+        let connections_opened = Arc::new(AtomicUsize::new(0));
+        let pool = counting_pool(2, connections_opened.clone(), true);
+
+        {
+            let _connection = pool.get().unwrap();
+        }
+        let connection = pool.get().unwrap();
+
+        assert!(*connection == 0);
+        assert!(connections_opened.load(Ordering::SeqCst) == 1);
+    }
+
+    #[test]
+    fn replaces_connections_that_fail_the_health_check() {
+        let connections_opened = Arc::new(AtomicUsize::new(0));
+        let pool = counting_pool(1, connections_opened.clone(), false);
+
+        {
+            let _connection = pool.get().unwrap();
+        }
+        let connection = pool.get().unwrap();
+
+        assert!(*connection == 1);
+        assert!(connections_opened.load(Ordering::SeqCst) == 2);
+    }
+
+    #[test]
+    fn never_opens_more_than_max_size_connections() {
+        let connections_opened = Arc::new(AtomicUsize::new(0));
+        let pool = counting_pool(1, connections_opened.clone(), true);
+
+        let _connection = pool.get().unwrap();
+
+        assert!(pool.get().is_err());
+        assert!(connections_opened.load(Ordering::SeqCst) == 1);
+    }
+
+    #[test]
+    fn frees_the_slot_of_a_failed_connection_attempt() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let pool: ConnectionPool<usize> =
+            ConnectionPool::new(1,
+                                Duration::from_millis(10),
+                                Box::new(move || {
+                                    if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                                        Err(Error::from(String::from("server is restarting")))
+                                    } else {
+                                        Ok(0)
+                                    }
+                                }),
+                                Box::new(|_| true));
+
+        assert!(pool.get().is_err());
+        assert!(pool.get().is_ok());
+    }
+}
@@ -2,45 +2,111 @@ use daemon::io::mio;
 use daemon::io::mio_uds;
 
 use daemon::CommandProcessor;
+use daemon::Config;
+use daemon::InMemoryPomodoros;
+use daemon::JournalPomodoros;
 use daemon::PomodoroQueryMapper;
+use daemon::PomodoroTicker;
+use daemon::SqlitePomodoroQueryMapper;
+use daemon::config::StorageBackend;
 use daemon::io::CommandEventSubscriber;
+use daemon::io::EventBroadcaster;
 use daemon::io::SignalEventSubscriber;
+use daemon::io::TimerEventSubscriber;
 use daemon::io::EventPoller;
+use daemon::pomodoro_event::EventPublisher;
+use daemon::pomodoros::Pomodoros;
 use daemon::system_clock::SystemClock;
+use daemon::result::Error;
 use daemon::result::Result;
 
 use std::os::unix::io::RawFd;
+use std::sync::Arc;
+
+/// Storage shared by the command processor and the ticker.
+pub type StoragePomodoros = Arc<Pomodoros + Send + Sync>;
 
 pub struct DaemonContainer<'a> {
     event_poller: EventPoller<'a>,
-    command_event_subscriber: CommandEventSubscriber<SystemClock, PomodoroQueryMapper>,
+    command_event_subscriber: CommandEventSubscriber<SystemClock, StoragePomodoros>,
     signal_event_subscriber: SignalEventSubscriber<'a>,
+    timer_event_subscriber: TimerEventSubscriber<SystemClock, StoragePomodoros>,
 }
 
 impl<'a> DaemonContainer<'a> {
-    pub fn new(signalfd: &'a RawFd) -> Result<DaemonContainer<'a>> {
+    pub fn new(signalfd: &'a RawFd, config: &Config) -> Result<DaemonContainer<'a>> {
         let system_clock = SystemClock::new();
-        let query_mapper = PomodoroQueryMapper::new();
-        let command_processor = CommandProcessor::new(system_clock, query_mapper);
+        let pomodoros = try!(DaemonContainer::create_pomodoros(config));
+        let (event_sender, event_receiver) = mio::channel::channel();
+
+        let ticker = PomodoroTicker::with_events(SystemClock::new(),
+                                                 pomodoros.clone(),
+                                                 EventPublisher::new(event_sender.clone()));
+        let timer_event_subscriber = TimerEventSubscriber::new(ticker, mio::Token(4));
+        let command_processor = CommandProcessor::with_events(system_clock,
+                                                              pomodoros,
+                                                              config.cycle.clone(),
+                                                              EventPublisher::new(event_sender));
         let uds_listener = try!(mio_uds::UnixListener::bind("/tmp/solanum"));
-        let command_event_subscriber: CommandEventSubscriber<SystemClock, PomodoroQueryMapper> =
-            try!(CommandEventSubscriber::new(uds_listener, command_processor, mio::Token(0)));
+        let command_event_subscriber: CommandEventSubscriber<SystemClock, StoragePomodoros> =
+            try!(CommandEventSubscriber::new(uds_listener,
+                                             command_processor,
+                                             config.framing.clone(),
+                                             mio::Token(0)));
 
         let evented_signalfd = mio::unix::EventedFd(&signalfd);
         let signalfd_subscriber = SignalEventSubscriber::new(evented_signalfd, mio::Token(1));
 
-        let event_poller = try!(EventPoller::new());
+        let mut event_poller = try!(EventPoller::new());
+        try!(event_poller.broadcast(EventBroadcaster::new(event_receiver, mio::Token(5))));
 
         Ok(DaemonContainer {
             event_poller: event_poller,
             command_event_subscriber: command_event_subscriber,
             signal_event_subscriber: signalfd_subscriber,
+            timer_event_subscriber: timer_event_subscriber,
         })
     }
 
+    /// Opens the configured storage once, so that unusable settings can be reported before the
+    /// daemon detaches from the terminal.
+    pub fn check_config(config: &Config) -> Result<()> {
+        DaemonContainer::create_pomodoros(config).map(|_| ())
+    }
+
+    fn create_pomodoros(config: &Config) -> Result<StoragePomodoros> {
+        match config.storage {
+            StorageBackend::Journal(ref path) => {
+                info!("Using journal storage at {}", path.display());
+                JournalPomodoros::open(path).map(|journal| Arc::new(journal) as StoragePomodoros)
+            }
+            StorageBackend::Memory => {
+                info!("Using ephemeral in-memory storage");
+                Ok(Arc::new(InMemoryPomodoros::new()))
+            }
+            StorageBackend::Postgres(ref database) => {
+                info!("Using PostgreSQL storage at {}", database.url);
+                let mapper = try!(PomodoroQueryMapper::new(database));
+                let pending = try!(mapper.pending_migrations());
+                if !pending.is_empty() {
+                    return Err(Error::from(format!("The database schema is {} migration(s) behind; \
+                                                    run `solanumd migrate` to upgrade it",
+                                                   pending.len())));
+                }
+                Ok(Arc::new(mapper))
+            }
+            StorageBackend::Sqlite(ref path) => {
+                info!("Using SQLite storage at {}", path.display());
+                SqlitePomodoroQueryMapper::open(path)
+                    .map(|mapper| Arc::new(mapper) as StoragePomodoros)
+            }
+        }
+    }
+
     pub fn start(&'a mut self) -> Result<()> {
         try!(self.event_poller.listen_for(&self.signal_event_subscriber));
-        try!(self.event_poller.listen_for(&self.command_event_subscriber));
+        try!(self.event_poller.listen_for_requests(&self.command_event_subscriber));
+        try!(self.event_poller.schedule(&self.timer_event_subscriber));
 
         self.event_poller.start_polling()
     }
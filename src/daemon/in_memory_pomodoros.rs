@@ -0,0 +1,306 @@
+use daemon::chrono::Duration;
+use daemon::chrono::datetime::DateTime;
+use daemon::chrono::offset::utc::UTC;
+
+use daemon::pomodoro::Pomodoro;
+use daemon::pomodoro::PomodoroStatus;
+use daemon::pomodoros::{PomodoroQuery, Pomodoros};
+
+use daemon::result::Error;
+use daemon::result::Result;
+
+use std::cmp;
+use std::option::Option;
+use std::sync::{Mutex, MutexGuard};
+
+#[derive(Clone)]
+struct InMemoryState {
+    next_id: i32,
+    pomodoros: Vec<Pomodoro>,
+}
+
+/// Keeps pomodoros in process memory only; everything is lost when the daemon exits.
+/// Ids are allocated and records are ordered the same way as in PomodoroQueryMapper.
+pub struct InMemoryPomodoros {
+    state: Mutex<InMemoryState>,
+}
+
+impl InMemoryPomodoros {
+    pub fn new() -> InMemoryPomodoros {
+        InMemoryPomodoros::with_pomodoros(1, Vec::new())
+    }
+
+    /// Starts from existing records; `next_id` is the id given to the next pomodoro created.
+    pub fn with_pomodoros(next_id: i32, pomodoros: Vec<Pomodoro>) -> InMemoryPomodoros {
+        InMemoryPomodoros {
+            state: Mutex::new(InMemoryState {
+                next_id: next_id,
+                pomodoros: pomodoros,
+            }),
+        }
+    }
+
+    /// Gives back the id the next pomodoro would have received, along with every record.
+    pub fn into_pomodoros(self) -> Result<(i32, Vec<Pomodoro>)> {
+        self.state
+            .into_inner()
+            .map(|state| (state.next_id, state.pomodoros))
+            .map_err(|_| Error::from(String::from("In-memory pomodoro store lock was poisoned")))
+    }
+
+    fn lock_state(&self) -> Result<MutexGuard<InMemoryState>> {
+        self.state
+            .lock()
+            .map_err(|_| Error::from(String::from("In-memory pomodoro store lock was poisoned")))
+    }
+}
+
+impl Pomodoros for InMemoryPomodoros {
+    fn create(&self,
+              start_time: DateTime<UTC>,
+              work_duration: Duration,
+              break_duration: Duration,
+              tags: Vec<String>)
+              -> Result<()> {
+        let mut state = try!(self.lock_state());
+        let id = state.next_id;
+        state.next_id += 1;
+        state.pomodoros.push(Pomodoro {
+            id: id,
+            work_start_time: start_time,
+            work_end_time: None,
+            break_start_time: None,
+            break_end_time: None,
+            work_length: work_duration,
+            break_length: break_duration,
+            tags: tags,
+            pauses: vec![],
+            cycle_position: 1,
+            extensions: vec![],
+            interruptions: vec![],
+            notes: None,
+            status: PomodoroStatus::InProgress,
+        });
+        Ok(())
+    }
+
+    fn last(&self, count: usize) -> Result<Vec<Pomodoro>> {
+        self.query(PomodoroQuery::most_recent(count))
+    }
+
+    fn query(&self, query: PomodoroQuery) -> Result<Vec<Pomodoro>> {
+        let state = try!(self.lock_state());
+        Ok(query.apply(&state.pomodoros))
+    }
+
+    fn most_recent(&self) -> Result<Option<Pomodoro>> {
+        self.last(1).map(|mut pomodoros| pomodoros.pop())
+    }
+
+    fn find(&self, id: i32) -> Result<Option<Pomodoro>> {
+        let state = try!(self.lock_state());
+        Ok(state.pomodoros.iter().find(|pomodoro| pomodoro.id == id).cloned())
+    }
+
+    fn update(&self, id: i32, pomodoro: Pomodoro) -> Result<()> {
+        let mut state = try!(self.lock_state());
+        for stored in state.pomodoros.iter_mut().filter(|stored| stored.id == id) {
+            *stored = Pomodoro { id: id, ..pomodoro.clone() };
+        }
+        Ok(())
+    }
+
+    fn insert(&self, pomodoro: Pomodoro) -> Result<i32> {
+        let mut state = try!(self.lock_state());
+        let id = state.next_id;
+        state.next_id += 1;
+        state.pomodoros.push(Pomodoro { id: id, ..pomodoro });
+        Ok(id)
+    }
+
+    fn delete(&self, id: i32) -> Result<()> {
+        let mut state = try!(self.lock_state());
+        state.pomodoros.retain(|stored| stored.id != id);
+        Ok(())
+    }
+
+    fn restore(&self, pomodoro: Pomodoro) -> Result<()> {
+        let mut state = try!(self.lock_state());
+        state.pomodoros.retain(|stored| stored.id != pomodoro.id);
+        state.next_id = cmp::max(state.next_id, pomodoro.id + 1);
+        state.pomodoros.push(pomodoro);
+        Ok(())
+    }
+
+    /// Runs `work` against a copy of the records, which replaces the originals if it succeeds.
+    fn transaction(&self, work: &mut FnMut(&Pomodoros) -> Result<()>) -> Result<()> {
+        let mut state = try!(self.lock_state());
+        let staged = InMemoryPomodoros { state: Mutex::new(state.clone()) };
+
+        try!(work(&staged));
+        let (next_id, pomodoros) = try!(staged.into_pomodoros());
+        state.next_id = next_id;
+        state.pomodoros = pomodoros;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn allocates_increasing_ids_starting_at_one() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+        pomodoros.create(start_time + Duration::seconds(10), Duration::seconds(5), Duration::seconds(5), vec![])
+            .unwrap();
+
+        let ids: Vec<i32> = pomodoros.last(5).unwrap().into_iter().map(|p| p.id).collect();
+
+        assert!(ids == vec![2, 1]);
+    }
+
+    #[test]
+    fn orders_pomodoros_by_work_start_time_rather_than_creation_order() {
+        let pomodoros = InMemoryPomodoros::new();
+        let earlier = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let later = "2000-01-01T01:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(later, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+        pomodoros.create(earlier, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+
+        let most_recent = pomodoros.most_recent().unwrap().unwrap();
+
+        assert!(most_recent.work_start_time == later);
+        assert!(pomodoros.last(1).unwrap().len() == 1);
+    }
+
+    #[test]
+    fn updates_the_pomodoro_with_the_given_id() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+        let mut pomodoro = pomodoros.most_recent().unwrap().unwrap();
+        pomodoro.work_end_time = Some(start_time + Duration::seconds(1));
+        pomodoro.status = PomodoroStatus::Aborted;
+
+        pomodoros.update(pomodoro.id, pomodoro.clone()).unwrap();
+
+        assert!(pomodoros.most_recent().unwrap() == Some(pomodoro));
+    }
+
+    #[test]
+    fn keeps_the_tags_given_at_creation() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let tags = vec![String::from("foo"), String::from("bar")];
+        pomodoros.create(start_time, Duration::seconds(5), Duration::seconds(5), tags.clone()).unwrap();
+
+        assert!(pomodoros.most_recent().unwrap().unwrap().tags == tags);
+    }
+
+    #[test]
+    fn keeps_every_change_made_in_a_successful_transaction() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        pomodoros.transaction(&mut |pomodoros: &Pomodoros| {
+                try!(pomodoros.create(start_time, Duration::seconds(5), Duration::seconds(5), vec![]));
+                pomodoros.create(start_time + Duration::seconds(10),
+                                 Duration::seconds(5),
+                                 Duration::seconds(5),
+                                 vec![])
+            })
+            .unwrap();
+        pomodoros.create(start_time + Duration::seconds(20), Duration::seconds(5), Duration::seconds(5), vec![])
+            .unwrap();
+
+        let ids: Vec<i32> = pomodoros.last(5).unwrap().into_iter().map(|p| p.id).collect();
+        assert!(ids == vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn discards_every_change_made_in_a_failed_transaction() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+        let original = pomodoros.most_recent().unwrap().unwrap();
+
+        let result = pomodoros.transaction(&mut |pomodoros: &Pomodoros| {
+            let mut aborted = original.clone();
+            aborted.status = PomodoroStatus::Aborted;
+            try!(pomodoros.update(aborted.id, aborted));
+            try!(pomodoros.create(start_time + Duration::seconds(10),
+                                  Duration::seconds(5),
+                                  Duration::seconds(5),
+                                  vec![]));
+            Err(Error::from(String::from("interrupted")))
+        });
+
+        assert!(result.is_err());
+        assert!(pomodoros.last(5).unwrap() == vec![original.clone()]);
+    }
+
+    #[test]
+    fn inserts_complete_records_under_new_ids() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+        let mut logged = pomodoros.most_recent().unwrap().unwrap();
+        logged.work_start_time = start_time - Duration::seconds(10);
+        logged.status = PomodoroStatus::Aborted;
+
+        let id = pomodoros.insert(logged.clone()).unwrap();
+
+        assert!(id == 2);
+        assert!(pomodoros.find(2).unwrap() == Some(Pomodoro { id: 2, ..logged }));
+        assert!(pomodoros.most_recent().unwrap().unwrap().id == 1);
+    }
+
+    #[test]
+    fn deletes_the_pomodoro_with_the_given_id() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+        pomodoros.create(start_time + Duration::seconds(10), Duration::seconds(5), Duration::seconds(5), vec![])
+            .unwrap();
+
+        pomodoros.delete(2).unwrap();
+        pomodoros.delete(42).unwrap();
+
+        let ids: Vec<i32> = pomodoros.last(5).unwrap().into_iter().map(|p| p.id).collect();
+        assert!(ids == vec![1]);
+    }
+
+    #[test]
+    fn restores_records_under_their_own_ids() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+        let mut restored = pomodoros.most_recent().unwrap().unwrap();
+        restored.id = 7;
+
+        pomodoros.restore(restored.clone()).unwrap();
+        pomodoros.create(start_time + Duration::seconds(10), Duration::seconds(5), Duration::seconds(5), vec![])
+            .unwrap();
+
+        let ids: Vec<i32> = pomodoros.last(5).unwrap().into_iter().map(|p| p.id).collect();
+        assert!(ids == vec![8, 7, 1]);
+        assert!(pomodoros.find(7).unwrap() == Some(restored));
+    }
+
+    #[test]
+    fn ignores_updates_to_unknown_ids() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+        let original = pomodoros.most_recent().unwrap().unwrap();
+        let mut changed = original.clone();
+        changed.status = PomodoroStatus::Aborted;
+
+        pomodoros.update(42, changed).unwrap();
+
+        assert!(pomodoros.most_recent().unwrap() == Some(original));
+    }
+}
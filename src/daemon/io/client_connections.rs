@@ -0,0 +1,556 @@
+use daemon::config::FramingConfig;
+use daemon::io::RequestSubscriber;
+use daemon::io::event_broadcaster::{EventBroadcaster, EventFormat, EventStream};
+use daemon::io::framing;
+use daemon::io::framing::FrameDecoder;
+use daemon::result::Error;
+
+use super::mio::{Poll, PollOpt, Ready, Token};
+use super::mio_uds::UnixStream;
+
+use std::collections::HashMap;
+use std::io;
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+use std::usize;
+
+/// Clients' connections are given tokens from here up, well clear of those given to
+/// subscribers' connections and to the other subscriptions of the event poller.
+const FIRST_CONNECTION_TOKEN: usize = usize::MAX / 2;
+
+/// A request read in full from a client, to be answered by the request subscriber.
+pub type Request = (Token, Vec<u8>);
+
+/// An answer to a request, along with how to send events to the client if it subscribed.
+pub type Reply = (String, Option<EventFormat>);
+
+/// What a connection is waiting on after being moved on as far as it can go.
+enum Progress {
+    Waiting,
+    Request(Vec<u8>),
+    Subscribed(EventFormat),
+    Closed,
+}
+
+/// A client's connection, which is only ever read or written as far as it can be without
+/// blocking.
+struct Connection {
+    stream: UnixStream,
+    decoder: FrameDecoder,
+    /// Framed responses yet to be written, in the order they are to be sent.
+    output: Vec<u8>,
+    /// Whether one of the client's requests is being answered. Requests are answered one at a
+    /// time, in the order they were sent.
+    answering: bool,
+    /// How to send events to the client once its responses are written, if it subscribed.
+    subscription: Option<EventFormat>,
+    /// Whether to close the connection once its responses are written.
+    closing: bool,
+    /// Whether the client sent its request as plain text rather than framed, as clients did
+    /// before requests were framed. Such a client is answered in plain text, then the
+    /// connection is closed, since it reads its answer up to the end of the stream.
+    unframed: bool,
+    /// When the connection last moved on, which its timeouts count from.
+    since: Instant,
+}
+
+impl Connection {
+    /// When the client is given up on if it does nothing more, or None while one of its
+    /// requests is being answered.
+    fn deadline(&self, framing: &FramingConfig) -> Option<Instant> {
+        if self.answering {
+            return None;
+        }
+
+        // A client may take its time to start a request, but not to finish one or to take in
+        // the response.
+        let timeout = if self.decoder.is_partial() || !self.output.is_empty() {
+            framing.request_timeout
+        } else {
+            framing.idle_timeout
+        };
+        Some(self.since + timeout.to_std().unwrap_or(Duration::from_secs(0)))
+    }
+
+    /// Writes responses, then reads until there is a whole request to answer or nothing more
+    /// to read. Nothing is read while a response is outstanding, so that a client cannot pile
+    /// up requests faster than it takes in the answers.
+    fn advance(&mut self, subscriber: &RequestSubscriber, now: Instant) -> Progress {
+        if !self.flush(now) {
+            return Progress::Closed;
+        }
+        if self.answering || !self.output.is_empty() {
+            return Progress::Waiting;
+        }
+        if self.closing {
+            return Progress::Closed;
+        }
+        if let Some(format) = self.subscription {
+            return Progress::Subscribed(format);
+        }
+
+        let mut buf: [u8; 1024] = [0; 1024];
+        loop {
+            self.unframed = self.decoder.is_unframed();
+            match self.decoder.next_frame() {
+                Ok(Some(request)) => {
+                    self.answering = true;
+                    return Progress::Request(request);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    warn!("Refused request: {}", e);
+                    self.refuse(subscriber, &e);
+                    return if !self.flush(now) || self.output.is_empty() {
+                        Progress::Closed
+                    } else {
+                        Progress::Waiting
+                    };
+                }
+            }
+
+            match self.stream.read(&mut buf) {
+                Ok(0) => {
+                    if let Some(request) = self.decoder.finish_unframed() {
+                        self.answering = true;
+                        return Progress::Request(request);
+                    }
+                    if self.decoder.is_partial() {
+                        warn!("Client hung up part way through a request");
+                    }
+                    return Progress::Closed;
+                }
+                Ok(read) => {
+                    if !self.decoder.is_partial() {
+                        self.since = now;
+                    }
+                    self.decoder.push(&buf[..read]);
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    // A client that does not frame its request sends it in one go and waits for
+                    // the answer without ending it, so what it has sent by now is the request.
+                    return match self.decoder.finish_unframed() {
+                        Some(request) => {
+                            self.answering = true;
+                            Progress::Request(request)
+                        }
+                        None => Progress::Waiting,
+                    };
+                }
+                Err(e) => {
+                    warn!("Could not read from a client: {}", e);
+                    return Progress::Closed;
+                }
+            }
+        }
+    }
+
+    /// Answers a request that could not be read, and closes the connection once the answer is
+    /// written, since whatever follows the request cannot be made sense of.
+    fn refuse(&mut self, subscriber: &RequestSubscriber, error: &Error) {
+        self.queue(&subscriber.refuse(error));
+        self.closing = true;
+    }
+
+    /// Queues a response to be written, framed unless the client sent its request as plain
+    /// text.
+    fn queue(&mut self, response: &str) {
+        if self.unframed {
+            self.output.extend_from_slice(response.as_bytes());
+        } else {
+            self.output.extend(framing::encode(response.as_bytes()));
+        }
+    }
+
+    /// Writes as much of the outstanding responses as the client will take. Returns false if
+    /// the connection has failed.
+    fn flush(&mut self, now: Instant) -> bool {
+        while !self.output.is_empty() {
+            match self.stream.write(&self.output) {
+                Ok(0) => return false,
+                Ok(written) => {
+                    self.output = self.output.split_off(written);
+                    self.since = now;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return true,
+                Err(e) => {
+                    warn!("Could not write to a client: {}", e);
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Keeps the connections of the clients of a request subscriber, reading their requests and
+/// writing the answers on the polling thread without ever waiting on a client. Each client is
+/// timed out on its own, and a client whose connection fails is dropped without affecting any
+/// other.
+pub struct ClientConnections<'a> {
+    subscriber: &'a (RequestSubscriber + Sync),
+    framing: FramingConfig,
+    connections: HashMap<Token, Connection>,
+    next_token: usize,
+}
+
+impl<'a> ClientConnections<'a> {
+    pub fn new(subscriber: &'a (RequestSubscriber + Sync)) -> ClientConnections<'a> {
+        ClientConnections {
+            subscriber: subscriber,
+            framing: subscriber.framing().clone(),
+            connections: HashMap::new(),
+            next_token: FIRST_CONNECTION_TOKEN,
+        }
+    }
+
+    pub fn subscriber(&self) -> &'a (RequestSubscriber + Sync) {
+        self.subscriber
+    }
+
+    pub fn register(&self, poll: &Poll) -> io::Result<()> {
+        poll.register(self.subscriber.io(),
+                      self.subscriber.token(),
+                      Ready::readable(),
+                      PollOpt::edge())
+    }
+
+    /// Whether an event polled for under `token` is for the listener or one of its clients.
+    pub fn handles(&self, token: Token) -> bool {
+        token == self.subscriber.token() || self.connections.contains_key(&token)
+    }
+
+    /// Accepts every waiting client if `token` is the listener's, or moves the client's
+    /// connection on otherwise. Returns the requests that are ready to be answered.
+    pub fn handle(&mut self,
+                  poll: &Poll,
+                  token: Token,
+                  now: Instant,
+                  broadcaster: &mut Option<EventBroadcaster>)
+                  -> Vec<Request> {
+        if token == self.subscriber.token() {
+            self.accept_all(poll, now, broadcaster)
+        } else {
+            self.advance(poll, token, now, broadcaster).into_iter().collect()
+        }
+    }
+
+    /// Starts tracking a client's connection, returning the token it is polled for under.
+    pub fn add(&mut self, poll: &Poll, stream: UnixStream, now: Instant) -> io::Result<Token> {
+        let token = Token(self.next_token);
+        self.next_token += 1;
+        try!(poll.register(&stream, token, Ready::readable() | Ready::writable(), PollOpt::edge()));
+        self.connections.insert(token,
+                                Connection {
+                                    stream: stream,
+                                    decoder: FrameDecoder::new(self.framing.max_request_length),
+                                    output: Vec::new(),
+                                    answering: false,
+                                    subscription: None,
+                                    closing: false,
+                                    unframed: false,
+                                    since: now,
+                                });
+        Ok(token)
+    }
+
+    /// Queues the answer to a client's request to be written, and returns the client's next
+    /// request if it has already sent one.
+    pub fn reply(&mut self,
+                 poll: &Poll,
+                 token: Token,
+                 reply: Reply,
+                 now: Instant,
+                 broadcaster: &mut Option<EventBroadcaster>)
+                 -> Option<Request> {
+        let (response, subscription) = reply;
+        match self.connections.get_mut(&token) {
+            Some(connection) => {
+                connection.answering = false;
+                connection.queue(&response);
+                if connection.unframed {
+                    connection.closing = true;
+                } else {
+                    connection.subscription = subscription;
+                }
+                connection.since = now;
+            }
+            None => return None,
+        }
+        self.advance(poll, token, now, broadcaster)
+    }
+
+    /// How long until the next client is due to be timed out, if any is.
+    pub fn next_timeout(&self, now: Instant) -> Option<Duration> {
+        self.connections
+            .values()
+            .filter_map(|connection| connection.deadline(&self.framing))
+            .min()
+            .map(|deadline| if deadline > now {
+                deadline - now
+            } else {
+                Duration::from_secs(0)
+            })
+    }
+
+    /// Drops the clients that have kept their connections waiting for too long. A client cut
+    /// off part way through a request is told why, if it will take the answer straight away.
+    pub fn expire(&mut self, poll: &Poll, now: Instant) {
+        let expired: Vec<Token> = self.connections
+            .iter()
+            .filter(|&(_, connection)| {
+                connection.deadline(&self.framing).map(|deadline| deadline <= now).unwrap_or(false)
+            })
+            .map(|(token, _)| *token)
+            .collect();
+
+        for token in expired {
+            if let Some(connection) = self.connections.get_mut(&token) {
+                if connection.decoder.is_partial() {
+                    let error = Error::InvalidCommandError(String::from("Could not read the request: \
+                                                                         timed out"));
+                    warn!("Refused request: {}", error);
+                    connection.refuse(self.subscriber, &error);
+                    connection.flush(now);
+                } else if !connection.output.is_empty() {
+                    warn!("Dropping a client that is not reading its responses");
+                } else {
+                    info!("Closing idle session");
+                }
+            }
+            self.close(poll, token);
+        }
+    }
+
+    fn accept_all(&mut self,
+                  poll: &Poll,
+                  now: Instant,
+                  broadcaster: &mut Option<EventBroadcaster>)
+                  -> Vec<Request> {
+        let mut requests = Vec::new();
+        // Only a new connection wakes the listener again, so every waiting client is accepted.
+        loop {
+            let stream = match self.subscriber.accept() {
+                Ok(Some(stream)) => stream,
+                Ok(None) => return requests,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return requests,
+                Err(e) => {
+                    warn!("Could not accept a connection: {}", e);
+                    return requests;
+                }
+            };
+
+            match self.add(poll, stream, now) {
+                Ok(token) => {
+                    // The client may have sent its request already.
+                    requests.extend(self.advance(poll, token, now, broadcaster));
+                }
+                Err(e) => warn!("Could not watch a client's connection: {}", e),
+            }
+        }
+    }
+
+    fn advance(&mut self,
+               poll: &Poll,
+               token: Token,
+               now: Instant,
+               broadcaster: &mut Option<EventBroadcaster>)
+               -> Option<Request> {
+        let progress = match self.connections.get_mut(&token) {
+            Some(connection) => connection.advance(self.subscriber, now),
+            None => return None,
+        };
+
+        match progress {
+            Progress::Waiting => None,
+            Progress::Request(request) => Some((token, request)),
+            Progress::Subscribed(format) => {
+                self.hand_over(poll, token, format, broadcaster);
+                None
+            }
+            Progress::Closed => {
+                self.close(poll, token);
+                None
+            }
+        }
+    }
+
+    /// Passes a subscribed client's connection on to be sent events from then on. Anything
+    /// else the client sends is ignored.
+    fn hand_over(&mut self,
+                 poll: &Poll,
+                 token: Token,
+                 format: EventFormat,
+                 broadcaster: &mut Option<EventBroadcaster>) {
+        let connection = match self.connections.remove(&token) {
+            Some(connection) => connection,
+            None => return,
+        };
+        if let Err(e) = poll.deregister(&connection.stream) {
+            warn!("Could not stop watching a client's connection: {}", e);
+        }
+
+        match *broadcaster {
+            Some(ref mut broadcaster) => {
+                broadcaster.subscribe(poll, EventStream::new(connection.stream, format))
+            }
+            None => warn!("Dropping subscriber, since there are no events to send it"),
+        }
+    }
+
+    /// Stops watching a client's connection and closes it.
+    fn close(&mut self, poll: &Poll, token: Token) {
+        if let Some(connection) = self.connections.remove(&token) {
+            if let Err(e) = poll.deregister(&connection.stream) {
+                warn!("Could not stop watching a client's connection: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use daemon::chrono;
+    use daemon::io::mio;
+
+    /// Answers every request by echoing it, and never has clients to accept.
+    struct EchoSubscriber {
+        io: UnixStream,
+        framing: FramingConfig,
+    }
+
+    impl EchoSubscriber {
+        fn new(framing: FramingConfig) -> EchoSubscriber {
+            EchoSubscriber {
+                io: UnixStream::pair().unwrap().0,
+                framing: framing,
+            }
+        }
+    }
+
+    impl RequestSubscriber for EchoSubscriber {
+        fn accept(&self) -> io::Result<Option<UnixStream>> {
+            Ok(None)
+        }
+
+        fn respond(&self, request: Vec<u8>) -> Reply {
+            (String::from_utf8(request).unwrap(), None)
+        }
+
+        fn refuse(&self, error: &Error) -> String {
+            format!("ERROR {}: {}", error.code(), error)
+        }
+
+        fn framing(&self) -> &FramingConfig {
+            &self.framing
+        }
+
+        fn token(&self) -> mio::Token {
+            mio::Token(0)
+        }
+
+        fn io(&self) -> &mio::Evented {
+            &self.io
+        }
+    }
+
+    fn read_message(stream: &mut UnixStream) -> Option<String> {
+        framing::read_frame(stream, &mut FrameDecoder::new(1024))
+            .unwrap()
+            .map(|message| String::from_utf8(message).unwrap())
+    }
+
+    #[test]
+    fn answers_a_clients_requests_one_at_a_time_in_order() {
+        let subscriber = EchoSubscriber::new(FramingConfig::default());
+        let mut clients = ClientConnections::new(&subscriber);
+        let poll = Poll::new().unwrap();
+        let (server_end, mut client_end) = UnixStream::pair().unwrap();
+        let now = Instant::now();
+        let token = clients.add(&poll, server_end, now).unwrap();
+
+        framing::write_frame(&mut client_end, b"STATUS").unwrap();
+        framing::write_frame(&mut client_end, b"LIST").unwrap();
+        let first = clients.handle(&poll, token, now, &mut None);
+        let second = clients.reply(&poll, token, (String::from("first"), None), now, &mut None);
+
+        assert!(first == vec![(token, b"STATUS".to_vec())]);
+        assert!(second == Some((token, b"LIST".to_vec())));
+        assert!(read_message(&mut client_end) == Some(String::from("first")));
+    }
+
+    #[test]
+    fn answers_a_request_that_is_not_length_prefixed_in_plain_text_then_closes() {
+        let subscriber = EchoSubscriber::new(FramingConfig::default());
+        let mut clients = ClientConnections::new(&subscriber);
+        let poll = Poll::new().unwrap();
+        let (server_end, mut client_end) = UnixStream::pair().unwrap();
+        let now = Instant::now();
+        let token = clients.add(&poll, server_end, now).unwrap();
+
+        client_end.write_all(b"STATUS").unwrap();
+        let requests = clients.handle(&poll, token, now, &mut None);
+        clients.reply(&poll, token, (String::from("No pomodoro in progress"), None), now, &mut None);
+        let mut response = String::new();
+        client_end.read_to_string(&mut response).unwrap();
+
+        assert!(requests == vec![(token, b"STATUS".to_vec())]);
+        assert!(response == "No pomodoro in progress");
+        assert!(!clients.handles(token));
+    }
+
+    #[test]
+    fn refuses_requests_over_the_limit_and_closes_the_connection() {
+        let subscriber = EchoSubscriber::new(FramingConfig {
+            max_request_length: 8,
+            ..FramingConfig::default()
+        });
+        let mut clients = ClientConnections::new(&subscriber);
+        let poll = Poll::new().unwrap();
+        let (server_end, mut client_end) = UnixStream::pair().unwrap();
+        let now = Instant::now();
+        let token = clients.add(&poll, server_end, now).unwrap();
+
+        framing::write_frame(&mut client_end, b"NOTE far too long").unwrap();
+
+        assert!(clients.handle(&poll, token, now, &mut None).is_empty());
+        assert!(read_message(&mut client_end) ==
+                Some(String::from("ERROR INVALID_COMMAND: Message of 17 bytes is over the limit of \
+                                   8 bytes")));
+        assert!(read_message(&mut client_end) == None);
+        assert!(!clients.handles(token));
+    }
+
+    #[test]
+    fn times_out_idle_clients_and_those_slow_to_finish_a_request() {
+        let subscriber = EchoSubscriber::new(FramingConfig {
+            request_timeout: chrono::Duration::seconds(5),
+            idle_timeout: chrono::Duration::seconds(60),
+            ..FramingConfig::default()
+        });
+        let mut clients = ClientConnections::new(&subscriber);
+        let poll = Poll::new().unwrap();
+        let (idle_end, mut idle_client) = UnixStream::pair().unwrap();
+        let (slow_end, mut slow_client) = UnixStream::pair().unwrap();
+        let now = Instant::now();
+        let idle = clients.add(&poll, idle_end, now).unwrap();
+        let slow = clients.add(&poll, slow_end, now).unwrap();
+
+        slow_client.write_all(&framing::encode(b"STATUS")[..3]).unwrap();
+        clients.handle(&poll, slow, now, &mut None);
+        assert!(clients.next_timeout(now) == Some(Duration::from_secs(5)));
+
+        clients.expire(&poll, now + Duration::from_secs(6));
+        assert!(clients.handles(idle) && !clients.handles(slow));
+        assert!(read_message(&mut slow_client) ==
+                Some(String::from("ERROR INVALID_COMMAND: Could not read the request: timed out")));
+
+        clients.expire(&poll, now + Duration::from_secs(61));
+        assert!(!clients.handles(idle));
+        assert!(read_message(&mut idle_client) == None);
+    }
+}
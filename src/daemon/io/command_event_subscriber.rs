@@ -6,8 +6,12 @@ use daemon::chrono::offset::utc::UTC;
 use daemon::clock::Clock;
 use daemon::Command;
 use daemon::CommandProcessor;
-use daemon::io::{EventSubscriber, CanSend};
+use daemon::config::FramingConfig;
+use daemon::io::RequestSubscriber;
+use daemon::io::client_connections::Reply;
+use daemon::io::event_broadcaster::EventFormat;
 use daemon::pomodoros::Pomodoros;
+use daemon::protocol;
 use daemon::result::Error;
 use daemon::result::Result;
 
@@ -15,14 +19,13 @@ use self::mio_uds::UnixListener;
 use self::mio_uds::UnixStream;
 
 use std::fs;
-use std::io::{Read, Write};
-use std::iter::FromIterator;
-use std::net::Shutdown;
+use std::io;
 use std::path::Path;
 
 pub struct CommandEventSubscriber<C: Clock, P: Pomodoros> {
     io: UnixListener,
     command_processor: CommandProcessor<C, P>,
+    framing: FramingConfig,
     token: mio::Token,
 }
 
@@ -31,60 +34,79 @@ unsafe impl<C: Clock + Sync, P: Pomodoros + Sync> Sync for CommandEventSubscribe
 impl<C: Clock, P: Pomodoros> CommandEventSubscriber<C, P> {
     pub fn new(listener: UnixListener,
                command_processor: CommandProcessor<C, P>,
+               framing: FramingConfig,
                token: mio::Token)
         -> Result<CommandEventSubscriber<C, P>> {
             Ok(CommandEventSubscriber {
                 io: listener,
                 command_processor: command_processor,
+                framing: framing,
                 token: token,
             })
         }
 
-    fn process_stream(&self, stream: &mut UnixStream) -> Result<()> {
-        let mut buf: [u8; 1024] = [0; 1024];
-        try!(stream.read(&mut buf));
-        let codepoints = Vec::from_iter(buf.to_vec()
-            .into_iter()
-            .take_while(|codepoint| *codepoint != (0 as u8)));
-        let message = try!(String::from_utf8(codepoints));
-        let command = try!(Command::from_string(UTC::now(), message));
-
-        try!(self.command_processor
-            .handle_command(command)
-            .and_then(|response| {
-                let result = stream
-                    .write_all(response.as_bytes())
-                    .and_then(|_| { stream.shutdown(Shutdown::Both) })
-                    .map_err(|e| Error::from(e));
-
-                info!("Handled command");
-                result
-            }));
-
-        Ok(())
+    /// Answers a JSON request. Failures are part of the response rather than errors, so that
+    /// they are reported in JSON too.
+    fn respond_in_json(&self, message: &str) -> Reply {
+        let mut subscription = None;
+        let response = protocol::request_from_json(UTC::now(), message).and_then(|command| {
+            subscription = subscription_format(&command, EventFormat::Json);
+            self.command_processor.handle(command)
+        });
+        if let Err(ref e) = response {
+            warn!("Command failed: {}", e);
+            subscription = None;
+        }
+        (protocol::response_to_json(&response).to_string(), subscription)
+    }
+}
+
+/// How a client that sent `command` is to be sent events, if the command subscribes it to them.
+fn subscription_format(command: &Command, format: EventFormat) -> Option<EventFormat> {
+    if *command == Command::Subscribe {
+        Some(format)
+    } else {
+        None
     }
 }
 
-impl<'a, C: Clock, P: Pomodoros, S: CanSend<bool>> EventSubscriber<'a, S> for CommandEventSubscriber<C, P> {
-    fn handle(&self, stop_sender: S) -> () {
-        self
-            .io
-            .accept()
+/// Failed commands are answered rather than dropped, with the kind of failure first so that a
+/// client can tell e.g. "nothing to stop" from "the database is down".
+fn error_response(error: &Error) -> String {
+    format!("ERROR {}: {}", error.code(), error)
+}
+
+impl<C: Clock, P: Pomodoros> RequestSubscriber for CommandEventSubscriber<C, P> {
+    fn accept(&self) -> io::Result<Option<UnixStream>> {
+        self.io.accept().map(|accepted| accepted.map(|(stream, _)| stream))
+    }
+
+    /// Answers a request, along with how to send events to the client if it subscribed.
+    fn respond(&self, request: Vec<u8>) -> Reply {
+        String::from_utf8(request)
             .map_err(|e| Error::from(e))
-            .and_then(|acceptor| {
-                match acceptor {
-                    Some((mut stream, _)) => {
-                        self.process_stream(&mut stream)
-                            .map_err(|e| Error::from(e))
-                    }
-                    None => {
-                        warn!("Expected connection but got none");
-                        Ok(())
-                    }
-                }
+            .and_then(|message| if protocol::is_json_request(&message) {
+                Ok(self.respond_in_json(&message))
+            } else {
+                Command::from_string(UTC::now(), message).and_then(|command| {
+                    let subscription = subscription_format(&command, EventFormat::Text);
+                    self.command_processor
+                        .handle_command(command)
+                        .map(|response| (response, subscription))
+                })
+            })
+            .unwrap_or_else(|e| {
+                warn!("Command failed: {}", e);
+                (error_response(&e), None)
             })
-            .or_else(|_| stop_sender.send(true))
-            .unwrap();
+    }
+
+    fn refuse(&self, error: &Error) -> String {
+        error_response(error)
+    }
+
+    fn framing(&self) -> &FramingConfig {
+        &self.framing
     }
 
     fn token(&self) -> mio::Token {
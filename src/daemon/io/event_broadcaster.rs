@@ -0,0 +1,268 @@
+use daemon::io::framing;
+use daemon::pomodoro_event::PomodoroEvent;
+use daemon::protocol;
+
+use super::mio::{channel, Poll, PollOpt, Ready, Token};
+use super::mio_uds::UnixStream;
+
+use std::collections::HashMap;
+use std::io;
+use std::io::{Read, Write};
+
+/// Subscribers' connections are given tokens from here up, well clear of those given to the
+/// other subscriptions of the event poller.
+const FIRST_STREAM_TOKEN: usize = 1024;
+
+/// How many bytes of events a subscriber may leave unread beyond what its socket holds before
+/// it is given up on.
+const MAX_PENDING_EVENT_BYTES: usize = 64 * 1024;
+
+/// How a subscriber is sent events, the same way it subscribed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventFormat {
+    Text,
+    Json,
+}
+
+/// The connection of a client that subscribed to pomodoro events, which is sent each event as a
+/// framed message.
+pub struct EventStream {
+    stream: UnixStream,
+    format: EventFormat,
+    /// Framed events yet to be written, in the order they were published.
+    output: Vec<u8>,
+}
+
+impl EventStream {
+    pub fn new(stream: UnixStream, format: EventFormat) -> EventStream {
+        EventStream {
+            stream: stream,
+            format: format,
+            output: Vec::new(),
+        }
+    }
+
+    /// Queues an event and writes as much as the client will take without waiting on it. The
+    /// rest is written as the client makes room for it. Fails if the connection has failed, or
+    /// if the client has fallen too far behind.
+    fn send(&mut self, event: &PomodoroEvent) -> io::Result<()> {
+        let message = match self.format {
+            EventFormat::Text => event.to_string(),
+            EventFormat::Json => protocol::event_to_json(event).to_string(),
+        };
+        self.output.extend(framing::encode(message.as_bytes()));
+        try!(self.flush());
+
+        if self.output.len() > MAX_PENDING_EVENT_BYTES {
+            return Err(io::Error::new(io::ErrorKind::Other, "subscriber is not reading its events"));
+        }
+        Ok(())
+    }
+
+    /// Writes as much of the queued events as the client will take.
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.output.is_empty() {
+            match self.stream.write(&self.output) {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::WriteZero, "subscriber took no bytes")),
+                Ok(written) => self.output = self.output.split_off(written),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads and drops whatever the client sends, since subscribers have nothing more to ask,
+    /// and tells whether the client has hung up.
+    fn has_closed(&mut self) -> bool {
+        let mut buf: [u8; 1024] = [0; 1024];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return true,
+                Ok(_) => continue,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return false,
+                Err(_) => return true,
+            }
+        }
+    }
+}
+
+/// Keeps the connections of subscribed clients and sends each of them the events published by
+/// the command processor and the ticker. Everything it does is done on the polling thread, so
+/// that events reach every subscriber in the order they were published.
+pub struct EventBroadcaster {
+    events: channel::Receiver<PomodoroEvent>,
+    streams: HashMap<Token, EventStream>,
+    next_stream_token: usize,
+    events_token: Token,
+}
+
+impl EventBroadcaster {
+    /// Broadcasts the events sent on `events`.
+    pub fn new(events: channel::Receiver<PomodoroEvent>, events_token: Token) -> EventBroadcaster {
+        EventBroadcaster {
+            events: events,
+            streams: HashMap::new(),
+            next_stream_token: FIRST_STREAM_TOKEN,
+            events_token: events_token,
+        }
+    }
+
+    pub fn register(&self, poll: &Poll) -> io::Result<()> {
+        poll.register(&self.events, self.events_token, Ready::readable(), PollOpt::edge())
+    }
+
+    /// Whether an event polled for under `token` is the broadcaster's to handle.
+    pub fn handles(&self, token: Token) -> bool {
+        token == self.events_token || self.streams.contains_key(&token)
+    }
+
+    /// Problems with a subscriber's connection are logged and cost only that subscriber.
+    pub fn handle(&mut self, poll: &Poll, token: Token) {
+        if token == self.events_token {
+            self.send_events(poll);
+        } else {
+            let open = match self.streams.get_mut(&token) {
+                Some(stream) => {
+                    if stream.has_closed() {
+                        info!("Subscriber hung up");
+                        false
+                    } else if let Err(e) = stream.flush() {
+                        warn!("Dropping subscriber that could not be sent its events: {}", e);
+                        false
+                    } else {
+                        true
+                    }
+                }
+                None => true,
+            };
+            if !open {
+                self.remove_stream(poll, token);
+            }
+        }
+    }
+
+    /// Sends a client every event published from now on.
+    pub fn subscribe(&mut self, poll: &Poll, stream: EventStream) {
+        let token = Token(self.next_stream_token);
+        self.next_stream_token += 1;
+        // Watched to notice the subscriber hanging up, and to write the events it could not
+        // take straight away once it makes room for them.
+        match poll.register(&stream.stream,
+                            token,
+                            Ready::readable() | Ready::writable(),
+                            PollOpt::edge()) {
+            Ok(()) => {
+                self.streams.insert(token, stream);
+                info!("Client subscribed to events");
+            }
+            Err(e) => warn!("Could not watch a subscriber's connection: {}", e),
+        }
+    }
+
+    /// Sends every event published so far to every subscriber, giving up on any that has failed
+    /// or fallen too far behind.
+    fn send_events(&mut self, poll: &Poll) {
+        while let Ok(event) = self.events.try_recv() {
+            let failed: Vec<Token> = self.streams
+                .iter_mut()
+                .filter_map(|(token, stream)| match stream.send(&event) {
+                    Ok(()) => None,
+                    Err(e) => {
+                        warn!("Dropping subscriber that could not be sent an event: {}", e);
+                        Some(*token)
+                    }
+                })
+                .collect();
+            for token in failed {
+                self.remove_stream(poll, token);
+            }
+        }
+    }
+
+    /// Stops watching a subscriber's connection and closes it.
+    fn remove_stream(&mut self, poll: &Poll, token: Token) {
+        if let Some(stream) = self.streams.remove(&token) {
+            if let Err(e) = poll.deregister(&stream.stream) {
+                warn!("Could not stop watching a subscriber's connection: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use daemon::chrono::Duration;
+    use daemon::chrono::datetime::DateTime;
+    use daemon::chrono::offset::utc::UTC;
+    use daemon::io::framing::FrameDecoder;
+    use daemon::pomodoro::{Pomodoro, PomodoroStatus};
+
+    fn started_event() -> PomodoroEvent {
+        let pomodoro = Pomodoro {
+            id: 3,
+            work_start_time: "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap(),
+            work_end_time: None,
+            break_start_time: None,
+            break_end_time: None,
+            work_length: Duration::seconds(1500),
+            break_length: Duration::seconds(300),
+            tags: vec![],
+            pauses: vec![],
+            cycle_position: 1,
+            extensions: vec![],
+            interruptions: vec![],
+            notes: None,
+            status: PomodoroStatus::InProgress,
+        };
+        PomodoroEvent::between(None, &pomodoro).unwrap()
+    }
+
+    #[test]
+    fn keeps_what_a_slow_subscriber_cannot_take_until_it_makes_room() {
+        let (stream, mut client) = UnixStream::pair().unwrap();
+        let mut subscriber = EventStream::new(stream, EventFormat::Text);
+        let event = started_event();
+        let mut sent = 0;
+        while subscriber.output.is_empty() {
+            subscriber.send(&event).unwrap();
+            sent += 1;
+        }
+
+        let mut decoder = FrameDecoder::new(1024);
+        let mut buf: [u8; 1024] = [0; 1024];
+        let mut received = 0;
+        while received < sent {
+            match client.read(&mut buf) {
+                Ok(read) => decoder.push(&buf[..read]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => subscriber.flush().unwrap(),
+                Err(e) => panic!("{}", e),
+            }
+            while let Some(frame) = decoder.next_frame().unwrap() {
+                assert!(frame == event.to_string().into_bytes());
+                received += 1;
+            }
+        }
+
+        assert!(subscriber.output.is_empty());
+        assert!(!decoder.is_partial());
+    }
+
+    #[test]
+    fn gives_up_on_a_subscriber_that_stops_reading() {
+        let (stream, _client) = UnixStream::pair().unwrap();
+        let mut subscriber = EventStream::new(stream, EventFormat::Json);
+        let event = started_event();
+
+        let mut result = Ok(());
+        while result.is_ok() {
+            result = subscriber.send(&event);
+        }
+
+        assert!(subscriber.output.len() > MAX_PENDING_EVENT_BYTES);
+    }
+}
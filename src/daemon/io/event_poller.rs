@@ -1,17 +1,25 @@
 use daemon::crossbeam;
-use daemon::io::EventSubscriber;
+use daemon::io::{ClientConnections, EventBroadcaster, EventSubscriber, RequestSubscriber,
+                 TimedEventSubscriber};
+use daemon::io::client_connections::{Reply, Request};
 use daemon::result::Error;
 use daemon::result::Result;
 
 use std::collections::HashMap;
 use std::io;
+use std::time::{Duration, Instant};
 
 use super::mio::{channel, Events, Poll, PollOpt, Ready, Token};
+use super::mio::timer::Timer;
 
 pub struct EventPoller<'a> {
     poll: Poll,
     events: Events,
     subscriptions: HashMap<Token, &'a (EventSubscriber<'a, channel::Sender<bool>> + Sync)>,
+    timer: Timer<Token>,
+    timed_subscriptions: HashMap<Token, &'a (TimedEventSubscriber + Sync)>,
+    broadcaster: Option<EventBroadcaster>,
+    clients: Option<ClientConnections<'a>>,
 }
 
 impl<'a> EventPoller<'a> {
@@ -21,6 +29,10 @@ impl<'a> EventPoller<'a> {
             poll: poll,
             events: Events::with_capacity(1024),
             subscriptions: HashMap::new(),
+            timer: Timer::default(),
+            timed_subscriptions: HashMap::new(),
+            broadcaster: None,
+            clients: None,
         })
     }
 
@@ -33,36 +45,149 @@ impl<'a> EventPoller<'a> {
                            PollOpt::edge())
     }
 
+    /// Wakes `subscriber` as soon as polling starts, and after that whenever it asks to be.
+    pub fn schedule(&mut self, subscriber: &'a (TimedEventSubscriber + Sync)) -> Result<()> {
+        self.timed_subscriptions.insert(subscriber.token(), subscriber);
+        self.timer
+            .set_timeout(Duration::from_millis(0), subscriber.token())
+            .map(|_| ())
+            .map_err(|e| Error::from(format!("Could not schedule timer: {}", e)))
+    }
+
+    /// Accepts the clients of `subscriber` and reads and writes their connections on the polling
+    /// thread, handing each whole request to the subscriber to answer on a thread of its own.
+    pub fn listen_for_requests(&mut self, subscriber: &'a (RequestSubscriber + Sync)) -> io::Result<()> {
+        let clients = ClientConnections::new(subscriber);
+        try!(clients.register(&self.poll));
+        self.clients = Some(clients);
+        Ok(())
+    }
+
+    /// Tracks the connections of clients that subscribe to pomodoro events, and sends them the
+    /// events as they are published.
+    pub fn broadcast(&mut self, broadcaster: EventBroadcaster) -> io::Result<()> {
+        try!(broadcaster.register(&self.poll));
+        self.broadcaster = Some(broadcaster);
+        Ok(())
+    }
+
     /// Repeatedly poll for and handle incoming Events.
     /// Will return Ok if the dameon terminated gracefully after SIGTERM.
     /// Otherwise, will return Err with an Error indicating what happened.
     pub fn start_polling(&mut self) -> Result<()> {
         let (stop_sender, stop_receiver) = channel::channel::<bool>();
+        let (reply_sender, reply_receiver) = channel::channel::<(Token, Reply)>();
         let stop_token = Token(2);
+        let timer_token = Token(3);
+        let (rearm_sender, rearm_receiver) = channel::channel::<(Token, Duration)>();
+        let rearm_token = Token(6);
+        let reply_token = Token(7);
 
         try!(self.poll.register(&stop_receiver, stop_token, Ready::readable(), PollOpt::edge()));
+        try!(self.poll.register(&self.timer, timer_token, Ready::readable(), PollOpt::edge()));
+        try!(self.poll.register(&rearm_receiver, rearm_token, Ready::readable(), PollOpt::edge()));
+        try!(self.poll.register(&reply_receiver, reply_token, Ready::readable(), PollOpt::edge()));
 
         crossbeam::scope(|scope| {
             'outer: loop {
-                try!(self.poll.poll(&mut self.events, None)
+                // Wake up in time to drop the next client due to be timed out.
+                let timeout = self.clients
+                    .as_ref()
+                    .and_then(|clients| clients.next_timeout(Instant::now()));
+                try!(self.poll.poll(&mut self.events, timeout)
                      .map_err(|e| Error::from(e)));
 
+                let mut requests: Vec<Request> = Vec::new();
+
                 for event in self.events.iter() {
                     if event.token() == stop_token {
                         info!("Received stop message");
                         break 'outer;
                     }
 
+                    // Timed subscribers can take as long as the storage does, so they are run
+                    // away from the polling thread. The polling thread owns the timer, so it
+                    // reschedules each of them once it is done.
+                    if event.token() == timer_token {
+                        while let Some(token) = self.timer.poll() {
+                            if let Some(subscriber) = self.timed_subscriptions.get(&token) {
+                                let subscriber = *subscriber;
+                                let rearm_sender = rearm_sender.clone();
+                                scope.spawn(move || {
+                                    let interval = subscriber.handle_timeout();
+                                    if let Err(e) = rearm_sender.send((token, interval)) {
+                                        warn!("Could not reschedule a timed subscriber: {}", e);
+                                    }
+                                });
+                            }
+                        }
+                        continue;
+                    }
+
+                    if event.token() == rearm_token {
+                        while let Ok((token, interval)) = rearm_receiver.try_recv() {
+                            try!(self.timer
+                                .set_timeout(interval, token)
+                                .map_err(|e| Error::from(format!("Could not schedule timer: {}", e))));
+                        }
+                        continue;
+                    }
+
+                    // Subscribers' connections are written to from the polling thread, so that
+                    // they are sent events in the order they were published.
+                    if let Some(ref mut broadcaster) = self.broadcaster {
+                        if broadcaster.handles(event.token()) {
+                            broadcaster.handle(&self.poll, event.token());
+                            continue;
+                        }
+                    }
+
+                    if let Some(ref mut clients) = self.clients {
+                        if clients.handles(event.token()) {
+                            requests.extend(clients.handle(&self.poll,
+                                                           event.token(),
+                                                           Instant::now(),
+                                                           &mut self.broadcaster));
+                            continue;
+                        }
+
+                        if event.token() == reply_token {
+                            while let Ok((token, reply)) = reply_receiver.try_recv() {
+                                requests.extend(clients.reply(&self.poll,
+                                                              token,
+                                                              reply,
+                                                              Instant::now(),
+                                                              &mut self.broadcaster));
+                            }
+                            continue;
+                        }
+                    }
+
                     let stop_sender = stop_sender.clone();
 
                     match self.subscriptions.get(&event.token()) {
                         Some(subscriber) => { scope.spawn(move || subscriber.handle(stop_sender)); },
-                        None => {
-                            warn!("Received event from unknown source");
-                            break 'outer;
-                        }
+                        // Most likely a connection closed earlier in this batch of events.
+                        None => warn!("Received event from unknown source"),
                     };
                 }
+
+                if let Some(ref mut clients) = self.clients {
+                    clients.expire(&self.poll, Instant::now());
+
+                    // Answering a request can take as long as the storage does, so it is done
+                    // away from the polling thread.
+                    for (token, request) in requests {
+                        let subscriber = clients.subscriber();
+                        let reply_sender = reply_sender.clone();
+                        scope.spawn(move || {
+                            let reply = subscriber.respond(request);
+                            if let Err(e) = reply_sender.send((token, reply)) {
+                                warn!("Could not pass on the answer to a request: {}", e);
+                            }
+                        });
+                    }
+                }
             };
 
             info!("Exiting event loop soon");
@@ -1,9 +1,15 @@
 use daemon::io::mio;
+use daemon::io::mio_uds::UnixStream;
 
+use daemon::config::FramingConfig;
+use daemon::io::client_connections::Reply;
+use daemon::pomodoro_event::PomodoroEvent;
 use daemon::result::Error;
 use daemon::result::Result;
 
 use std::convert::From;
+use std::io;
+use std::time::Duration;
 
 pub trait CanSend<T> {
     fn send(&self, t: T) -> Result<()>;
@@ -15,8 +21,36 @@ impl CanSend<bool> for mio::channel::Sender<bool> where Error: From<mio::channel
     }
 }
 
+impl CanSend<PomodoroEvent> for mio::channel::Sender<PomodoroEvent> {
+    fn send(&self, event: PomodoroEvent) -> Result<()> {
+        self.send(event).map_err(|e| Error::from(format!("Could not send event: {}", e)))
+    }
+}
+
 pub trait EventSubscriber<'a, S: CanSend<bool>> {
     fn handle(&self, s: S) -> ();
     fn token(&self) -> mio::Token;
     fn io(&self) -> &mio::Evented;
 }
+
+/// A subscriber woken by the event poller's timer rather than by IO.
+pub trait TimedEventSubscriber {
+    /// Handles the timer firing, on a thread of its own, and returns how long to wait before it
+    /// fires again. The timer does not fire again for the subscriber until it has returned.
+    fn handle_timeout(&self) -> Duration;
+    fn token(&self) -> mio::Token;
+}
+
+/// A subscriber to a listening socket whose clients send framed requests. The event poller
+/// accepts the clients and reads and writes their connections without blocking, so that all
+/// the subscriber has to do is answer whole requests, which it does on threads of their own.
+pub trait RequestSubscriber {
+    /// Accepts the next waiting client, if there is one.
+    fn accept(&self) -> io::Result<Option<UnixStream>>;
+    fn respond(&self, request: Vec<u8>) -> Reply;
+    /// Answers a request that could not be read, e.g. because it was over the size limit.
+    fn refuse(&self, error: &Error) -> String;
+    fn framing(&self) -> &FramingConfig;
+    fn token(&self) -> mio::Token;
+    fn io(&self) -> &mio::Evented;
+}
@@ -0,0 +1,229 @@
+use daemon::result::Error;
+use daemon::result::Result;
+
+use std::io::{Read, Write};
+use std::io;
+use std::mem;
+
+/// Number of bytes ahead of each message on the command socket giving its length, as a
+/// big-endian unsigned integer.
+pub const LENGTH_PREFIX_SIZE: usize = 4;
+
+/// Frames a message for the command socket: its length, then its bytes.
+pub fn encode(message: &[u8]) -> Vec<u8> {
+    let length = message.len() as u32;
+    let mut frame = vec![(length >> 24) as u8, (length >> 16) as u8, (length >> 8) as u8, length as u8];
+    frame.extend_from_slice(message);
+    frame
+}
+
+pub fn write_frame<W: Write>(writer: &mut W, message: &[u8]) -> io::Result<()> {
+    writer.write_all(&encode(message)).and_then(|_| writer.flush())
+}
+
+/// Collects bytes as they are read from a stream until they make up whole messages, however the
+/// reads happen to split them. Clients from before messages were framed send plain text
+/// instead, which is read a line at a time.
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+    max_length: usize,
+}
+
+impl FrameDecoder {
+    /// Messages longer than `max_length` bytes are refused.
+    pub fn new(max_length: usize) -> FrameDecoder {
+        FrameDecoder {
+            buffer: Vec::new(),
+            max_length: max_length,
+        }
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Whether part of a message has been read but not the rest of it.
+    pub fn is_partial(&self) -> bool {
+        !self.buffer.is_empty()
+    }
+
+    /// Whether what has been read starts with plain text rather than a length prefix. A prefix
+    /// only starts with a printable character if it gives a length far over the limit, so the
+    /// first byte tells the two apart.
+    pub fn is_unframed(&self) -> bool {
+        match self.buffer.first() {
+            Some(&byte) => byte >= b' ' && byte <= b'~' && (byte as usize) << 24 > self.max_length,
+            None => false,
+        }
+    }
+
+    /// Takes the next whole message out of what has been read, if there is one. A message is
+    /// refused as soon as its length is known to be over the limit, before any more of it is
+    /// buffered.
+    pub fn next_frame(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.is_unframed() {
+            return self.next_line();
+        }
+        if self.buffer.len() < LENGTH_PREFIX_SIZE {
+            return Ok(None);
+        }
+
+        let length = self.buffer[..LENGTH_PREFIX_SIZE]
+            .iter()
+            .fold(0 as usize, |length, byte| (length << 8) | *byte as usize);
+        if length > self.max_length {
+            return Err(Error::InvalidCommandError(format!("Message of {} bytes is over the limit of {} \
+                                                           bytes",
+                                                          length,
+                                                          self.max_length)));
+        }
+        if self.buffer.len() < LENGTH_PREFIX_SIZE + length {
+            return Ok(None);
+        }
+
+        let rest = self.buffer.split_off(LENGTH_PREFIX_SIZE + length);
+        let frame = self.buffer.split_off(LENGTH_PREFIX_SIZE);
+        self.buffer = rest;
+        Ok(Some(frame))
+    }
+
+    /// Takes what is left of a plain text message once the client has sent all it is going to,
+    /// since the last one need not end in a newline.
+    pub fn finish_unframed(&mut self) -> Option<Vec<u8>> {
+        if !self.is_unframed() {
+            return None;
+        }
+        let line = mem::replace(&mut self.buffer, Vec::new());
+        Some(trim_line_ending(line))
+    }
+
+    fn next_line(&mut self) -> Result<Option<Vec<u8>>> {
+        let end = self.buffer.iter().position(|byte| *byte == b'\n');
+        if end.unwrap_or(self.buffer.len()) > self.max_length {
+            return Err(Error::InvalidCommandError(format!("Request that is not length-prefixed is \
+                                                           over the limit of {} bytes",
+                                                          self.max_length)));
+        }
+
+        Ok(end.map(|end| {
+            let rest = self.buffer.split_off(end + 1);
+            trim_line_ending(mem::replace(&mut self.buffer, rest))
+        }))
+    }
+}
+
+fn trim_line_ending(mut line: Vec<u8>) -> Vec<u8> {
+    if line.last() == Some(&b'\n') {
+        line.pop();
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    line
+}
+
+/// Reads from `reader` until `decoder` has a whole message, blocking as long as the reader
+/// does. Gives None if the stream ends cleanly between messages; ending part way through one is
+/// an error.
+pub fn read_frame<R: Read>(reader: &mut R, decoder: &mut FrameDecoder) -> Result<Option<Vec<u8>>> {
+    read_frame_with(reader, decoder, |_, _| Ok(()))
+}
+
+/// Reads a message like `read_frame`, calling `before_read` ahead of each read with whether
+/// part of the message has arrived already, e.g. to wait longer for a message to start than for
+/// the rest of it.
+pub fn read_frame_with<R, F>(reader: &mut R, decoder: &mut FrameDecoder, mut before_read: F)
+                             -> Result<Option<Vec<u8>>>
+    where R: Read,
+          F: FnMut(&mut R, bool) -> io::Result<()>
+{
+    let mut buf: [u8; 1024] = [0; 1024];
+    loop {
+        if let Some(frame) = try!(decoder.next_frame()) {
+            return Ok(Some(frame));
+        }
+
+        try!(before_read(reader, decoder.is_partial()));
+        let read = match reader.read(&mut buf) {
+            Ok(read) => read,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(Error::from(e)),
+        };
+        if read == 0 {
+            if decoder.is_partial() {
+                return Err(Error::from(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                                      "Stream ended part way through a message")));
+            }
+            return Ok(None);
+        }
+        decoder.push(&buf[..read]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::Cursor;
+
+    /// Hands out what it holds a few bytes at a time, as a socket might.
+    struct TrickleReader {
+        bytes: Cursor<Vec<u8>>,
+    }
+
+    impl Read for TrickleReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let length = if buf.len() < 3 { buf.len() } else { 3 };
+            self.bytes.read(&mut buf[..length])
+        }
+    }
+
+    #[test]
+    fn reads_whole_messages_across_several_reads() {
+        let mut bytes = encode(b"NOTE a note longer than a single read");
+        bytes.extend(encode(b"STATUS"));
+        let mut reader = TrickleReader { bytes: Cursor::new(bytes) };
+        let mut decoder = FrameDecoder::new(1024);
+
+        let first = read_frame(&mut reader, &mut decoder).unwrap();
+        let second = read_frame(&mut reader, &mut decoder).unwrap();
+        let end = read_frame(&mut reader, &mut decoder).unwrap();
+
+        assert!(first == Some(b"NOTE a note longer than a single read".to_vec()));
+        assert!(second == Some(b"STATUS".to_vec()));
+        assert!(end == None);
+    }
+
+    #[test]
+    fn refuses_messages_over_the_limit_from_their_length_alone() {
+        let mut decoder = FrameDecoder::new(8);
+        decoder.push(&encode(b"NOTE far too long")[..LENGTH_PREFIX_SIZE]);
+
+        let error = decoder.next_frame().unwrap_err();
+
+        assert!(error.code() == "INVALID_COMMAND");
+        assert!(error.to_string() == "Message of 17 bytes is over the limit of 8 bytes");
+    }
+
+    #[test]
+    fn reads_requests_that_are_not_length_prefixed_as_lines_of_text() {
+        let mut decoder = FrameDecoder::new(1024);
+        decoder.push(b"STATUS\r\nLIST\nSTO");
+
+        assert!(decoder.is_unframed());
+        assert!(decoder.next_frame().unwrap() == Some(b"STATUS".to_vec()));
+        assert!(decoder.next_frame().unwrap() == Some(b"LIST".to_vec()));
+        assert!(decoder.next_frame().unwrap() == None);
+        decoder.push(b"P");
+        assert!(decoder.finish_unframed() == Some(b"STOP".to_vec()));
+        assert!(!decoder.is_partial());
+    }
+
+    #[test]
+    fn fails_when_the_stream_ends_part_way_through_a_message() {
+        let frame = encode(b"STATUS");
+        let mut reader = Cursor::new(frame[..frame.len() - 1].to_vec());
+
+        assert!(read_frame(&mut reader, &mut FrameDecoder::new(1024)).is_err());
+    }
+}
@@ -1,13 +1,22 @@
 pub extern crate mio;
 pub extern crate mio_uds;
 
+pub mod client_connections;
+pub mod event_broadcaster;
 pub mod event_subscriber;
 pub mod command_event_subscriber;
 pub mod event_poller;
+pub mod framing;
 pub mod signal_event_subscriber;
+pub mod timer_event_subscriber;
 
+pub use self::client_connections::ClientConnections;
+pub use self::event_broadcaster::EventBroadcaster;
 pub use self::event_subscriber::EventSubscriber;
+pub use self::event_subscriber::RequestSubscriber;
 pub use self::event_subscriber::CanSend;
+pub use self::event_subscriber::TimedEventSubscriber;
 pub use self::command_event_subscriber::CommandEventSubscriber;
 pub use self::event_poller::EventPoller;
 pub use self::signal_event_subscriber::SignalEventSubscriber;
+pub use self::timer_event_subscriber::TimerEventSubscriber;
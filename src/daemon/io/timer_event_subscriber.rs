@@ -0,0 +1,51 @@
+use daemon::chrono::Duration;
+
+use daemon::clock::Clock;
+use daemon::PomodoroTicker;
+use daemon::io::TimedEventSubscriber;
+use daemon::pomodoros::Pomodoros;
+
+use super::mio;
+
+use std::cmp;
+use std::time;
+
+/// Commands can move the current pomodoro's deadline at any time, so the ticker looks again at
+/// least this often even when nothing is due.
+const MAX_TICK_INTERVAL_MILLIS: i64 = 1000;
+
+pub struct TimerEventSubscriber<C: Clock, P: Pomodoros> {
+    ticker: PomodoroTicker<C, P>,
+    token: mio::Token,
+}
+
+unsafe impl<C: Clock + Sync, P: Pomodoros + Sync> Sync for TimerEventSubscriber<C, P> { }
+
+impl<C: Clock, P: Pomodoros> TimerEventSubscriber<C, P> {
+    pub fn new(ticker: PomodoroTicker<C, P>, token: mio::Token) -> TimerEventSubscriber<C, P> {
+        TimerEventSubscriber {
+            ticker: ticker,
+            token: token,
+        }
+    }
+}
+
+impl<C: Clock, P: Pomodoros> TimedEventSubscriber for TimerEventSubscriber<C, P> {
+    fn handle_timeout(&self) -> time::Duration {
+        let max_interval = Duration::milliseconds(MAX_TICK_INTERVAL_MILLIS);
+        let interval = match self.ticker.tick() {
+            Ok(Some(due_in)) => cmp::min(due_in, max_interval),
+            Ok(None) => max_interval,
+            Err(e) => {
+                warn!("Could not move the current pomodoro on: {}", e);
+                max_interval
+            }
+        };
+
+        interval.to_std().unwrap_or(time::Duration::from_millis(0))
+    }
+
+    fn token(&self) -> mio::Token {
+        self.token
+    }
+}
@@ -0,0 +1,623 @@
+use daemon::chrono::Duration;
+use daemon::chrono::datetime::DateTime;
+use daemon::chrono::offset::utc::UTC;
+
+use daemon::in_memory_pomodoros::InMemoryPomodoros;
+use daemon::pomodoro::Pomodoro;
+use daemon::pomodoro::PomodoroStatus;
+use daemon::pomodoro_json;
+use daemon::pomodoros::{PomodoroQuery, Pomodoros};
+
+use daemon::serde_json;
+use daemon::serde_json::{Map, Value};
+
+use daemon::result::Error;
+use daemon::result::Result;
+
+use std::cmp;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::io;
+use std::mem;
+use std::option::Option;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// Number of superseded journal entries tolerated before the journal is rewritten.
+const COMPACTION_THRESHOLD: usize = 100;
+
+struct JournalState {
+    file: fs::File,
+    next_id: i32,
+    pomodoros: Vec<Pomodoro>,
+    entries: usize,
+    /// Whether a failed append may have left part of an entry at the end of the file, which
+    /// would run into the next entry unless the journal is rewritten first.
+    torn: bool,
+}
+
+/// Stores pomodoros as an append-only journal of JSON lines, one per create, update or delete,
+/// or one per transaction holding all of its changes so that it is never half-applied.
+/// The current state is rebuilt by replaying the journal when it is opened, and the journal is
+/// compacted down to one entry per pomodoro whenever too many entries have been superseded.
+/// Ids are never given out twice: a compacted journal starts with the next id to give out if
+/// the pomodoro with the highest id has been deleted.
+pub struct JournalPomodoros {
+    path: PathBuf,
+    state: Mutex<JournalState>,
+}
+
+impl JournalPomodoros {
+    pub fn open(path: &Path) -> Result<JournalPomodoros> {
+        if let Some(parent) = path.parent() {
+            try!(fs::create_dir_all(parent));
+        }
+
+        let (pomodoros, next_id, entries) = try!(JournalPomodoros::replay(path));
+        let file = try!(JournalPomodoros::open_for_append(path));
+        let journal = JournalPomodoros {
+            path: path.to_path_buf(),
+            state: Mutex::new(JournalState {
+                file: file,
+                next_id: next_id,
+                pomodoros: pomodoros,
+                entries: entries,
+                torn: false,
+            }),
+        };
+
+        {
+            let mut state = try!(journal.lock_state());
+            if state.entries > state.pomodoros.len() {
+                try!(journal.compact(&mut state));
+            }
+        }
+
+        Ok(journal)
+    }
+
+    /// Rebuilds the records from the journal at `path`, along with the next id to give out. A
+    /// journal that does not exist yet holds no records, but one that cannot be read is an error
+    /// rather than an empty history.
+    fn replay(path: &Path) -> Result<(Vec<Pomodoro>, i32, usize)> {
+        let mut pomodoros: Vec<Pomodoro> = Vec::new();
+        let mut next_id = 1;
+        let mut entries = 0;
+
+        let file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok((pomodoros, next_id, entries))
+            }
+            Err(e) => return Err(Error::from(e)),
+        };
+
+        let lines: Vec<String> = try!(BufReader::new(file).lines().collect());
+        let line_count = lines.len();
+
+        for (number, line) in lines.into_iter().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let entry = match serde_json::from_str::<Value>(&line) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    // A torn final line is what a crash in the middle of an append leaves behind.
+                    // Counting it as an entry forces a compaction, which drops it from the file.
+                    if number + 1 == line_count {
+                        warn!("Ignoring incomplete final journal entry: {}", e);
+                        entries += 1;
+                        break;
+                    }
+                    return Err(Error::from(e));
+                }
+            };
+
+            // The next id is kept apart from the entries that compaction counts.
+            if let Some("next_id") = entry.get("op").and_then(|op| op.as_str()) {
+                next_id = cmp::max(next_id, try!(JournalPomodoros::next_id_of(&entry)));
+                continue;
+            }
+            try!(JournalPomodoros::apply(&mut pomodoros, &mut next_id, &entry));
+            entries += 1;
+        }
+
+        Ok((pomodoros, next_id, entries))
+    }
+
+    fn apply(pomodoros: &mut Vec<Pomodoro>, next_id: &mut i32, entry: &Value) -> Result<()> {
+        if let Some("batch") = entry.get("op").and_then(|op| op.as_str()) {
+            let batch = try!(entry.get("entries")
+                .and_then(|entries| entries.as_array())
+                .ok_or(Error::from(format!("Journal batch has no entries: {}", entry))));
+            for batched_entry in batch.iter() {
+                try!(JournalPomodoros::apply(pomodoros, next_id, batched_entry));
+            }
+            if entry.get("next_id").is_some() {
+                *next_id = cmp::max(*next_id, try!(JournalPomodoros::next_id_of(entry)));
+            }
+            return Ok(());
+        }
+
+        let pomodoro = try!(entry.get("pomodoro")
+            .ok_or(Error::from(format!("Journal entry has no pomodoro: {}", entry)))
+            .and_then(pomodoro_json::from_json));
+        *next_id = cmp::max(*next_id, pomodoro.id + 1);
+
+        match entry.get("op").and_then(|op| op.as_str()) {
+            Some("create") => pomodoros.push(pomodoro),
+            Some("update") => {
+                for stored in pomodoros.iter_mut().filter(|stored| stored.id == pomodoro.id) {
+                    *stored = pomodoro.clone();
+                }
+            }
+            Some("delete") => pomodoros.retain(|stored| stored.id != pomodoro.id),
+            _ => return Err(Error::from(format!("Unknown journal entry: {}", entry))),
+        }
+        Ok(())
+    }
+
+    fn next_id_of(entry: &Value) -> Result<i32> {
+        entry.get("next_id")
+            .and_then(|next_id| next_id.as_i64())
+            .map(|next_id| next_id as i32)
+            .ok_or(Error::from(format!("Journal entry has no next id: {}", entry)))
+    }
+
+    fn open_for_append(path: &Path) -> Result<fs::File> {
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| Error::from(e))
+    }
+
+    /// Writes an entry to the end of the journal. If it cannot be written in full, the journal
+    /// is cut back to where it was, so that the next entry does not start part way through a
+    /// line.
+    fn append(&self, state: &mut JournalState, entry: Value) -> Result<()> {
+        if state.torn {
+            try!(self.compact(state));
+        }
+
+        let length = try!(state.file.metadata()).len();
+        if let Err(e) = writeln!(state.file, "{}", entry).and_then(|_| state.file.sync_data()) {
+            if let Err(truncate_error) = state.file.set_len(length) {
+                error!("Could not remove a partly written journal entry: {}", truncate_error);
+                state.torn = true;
+            }
+            return Err(Error::from(e));
+        }
+        state.entries += 1;
+
+        if state.entries > state.pomodoros.len() + COMPACTION_THRESHOLD {
+            try!(self.compact(state));
+        }
+        Ok(())
+    }
+
+    /// Rewrites the journal with a single create entry per pomodoro, after the next id to give
+    /// out if the pomodoros left do not account for it. The new journal is written beside the
+    /// old one and renamed over it, so a crash leaves one of the two intact.
+    fn compact(&self, state: &mut JournalState) -> Result<()> {
+        let compacted_path = self.path.with_extension("compacting");
+        {
+            let mut compacted = try!(fs::File::create(&compacted_path));
+            let highest_id = state.pomodoros.iter().map(|pomodoro| pomodoro.id).max().unwrap_or(0);
+            if state.next_id > highest_id + 1 {
+                let mut entry = Map::new();
+                entry.insert(String::from("op"), Value::from("next_id"));
+                entry.insert(String::from("next_id"), Value::from(state.next_id));
+                try!(writeln!(compacted, "{}", Value::Object(entry)));
+            }
+            for pomodoro in state.pomodoros.iter() {
+                try!(writeln!(compacted, "{}", journal_entry("create", pomodoro)));
+            }
+            try!(compacted.sync_all());
+        }
+        try!(fs::rename(&compacted_path, &self.path));
+
+        state.file = try!(JournalPomodoros::open_for_append(&self.path));
+        state.entries = state.pomodoros.len();
+        state.torn = false;
+        info!("Compacted journal {} to {} entries", self.path.display(), state.entries);
+        Ok(())
+    }
+
+    fn lock_state(&self) -> Result<MutexGuard<JournalState>> {
+        self.state
+            .lock()
+            .map_err(|_| Error::from(String::from("Journal lock was poisoned")))
+    }
+}
+
+impl Pomodoros for JournalPomodoros {
+    fn create(&self,
+              start_time: DateTime<UTC>,
+              work_duration: Duration,
+              break_duration: Duration,
+              tags: Vec<String>)
+              -> Result<()> {
+        let mut state = try!(self.lock_state());
+        let pomodoro = Pomodoro {
+            id: state.next_id,
+            work_start_time: start_time,
+            work_end_time: None,
+            break_start_time: None,
+            break_end_time: None,
+            work_length: work_duration,
+            break_length: break_duration,
+            tags: tags,
+            pauses: vec![],
+            cycle_position: 1,
+            extensions: vec![],
+            interruptions: vec![],
+            notes: None,
+            status: PomodoroStatus::InProgress,
+        };
+
+        try!(self.append(&mut state, journal_entry("create", &pomodoro)));
+        state.next_id += 1;
+        state.pomodoros.push(pomodoro);
+        Ok(())
+    }
+
+    fn last(&self, count: usize) -> Result<Vec<Pomodoro>> {
+        self.query(PomodoroQuery::most_recent(count))
+    }
+
+    fn query(&self, query: PomodoroQuery) -> Result<Vec<Pomodoro>> {
+        let state = try!(self.lock_state());
+        Ok(query.apply(&state.pomodoros))
+    }
+
+    fn most_recent(&self) -> Result<Option<Pomodoro>> {
+        self.last(1).map(|mut pomodoros| pomodoros.pop())
+    }
+
+    fn find(&self, id: i32) -> Result<Option<Pomodoro>> {
+        let state = try!(self.lock_state());
+        Ok(state.pomodoros.iter().find(|pomodoro| pomodoro.id == id).cloned())
+    }
+
+    fn update(&self, id: i32, pomodoro: Pomodoro) -> Result<()> {
+        let mut state = try!(self.lock_state());
+        if !state.pomodoros.iter().any(|stored| stored.id == id) {
+            return Ok(());
+        }
+
+        let updated = Pomodoro { id: id, ..pomodoro };
+        try!(self.append(&mut state, journal_entry("update", &updated)));
+        for stored in state.pomodoros.iter_mut().filter(|stored| stored.id == id) {
+            *stored = updated.clone();
+        }
+        Ok(())
+    }
+
+    fn insert(&self, pomodoro: Pomodoro) -> Result<i32> {
+        let mut state = try!(self.lock_state());
+        let inserted = Pomodoro { id: state.next_id, ..pomodoro };
+
+        try!(self.append(&mut state, journal_entry("create", &inserted)));
+        state.next_id += 1;
+        state.pomodoros.push(inserted.clone());
+        Ok(inserted.id)
+    }
+
+    fn delete(&self, id: i32) -> Result<()> {
+        let mut state = try!(self.lock_state());
+        let deleted = match state.pomodoros.iter().find(|stored| stored.id == id) {
+            Some(stored) => stored.clone(),
+            None => return Ok(()),
+        };
+
+        try!(self.append(&mut state, journal_entry("delete", &deleted)));
+        state.pomodoros.retain(|stored| stored.id != id);
+        Ok(())
+    }
+
+    fn restore(&self, pomodoro: Pomodoro) -> Result<()> {
+        let mut state = try!(self.lock_state());
+        let op = if state.pomodoros.iter().any(|stored| stored.id == pomodoro.id) {
+            "update"
+        } else {
+            "create"
+        };
+
+        try!(self.append(&mut state, journal_entry(op, &pomodoro)));
+        state.pomodoros.retain(|stored| stored.id != pomodoro.id);
+        state.next_id = cmp::max(state.next_id, pomodoro.id + 1);
+        state.pomodoros.push(pomodoro);
+        Ok(())
+    }
+
+    /// Runs `work` against an in-memory copy of the records, then journals everything it
+    /// changed as a single batch entry. A crash while the batch is written leaves a torn final
+    /// line, which is dropped on the next open along with the rest of the transaction.
+    fn transaction(&self, work: &mut FnMut(&Pomodoros) -> Result<()>) -> Result<()> {
+        let mut state = try!(self.lock_state());
+        let staged = InMemoryPomodoros::with_pomodoros(state.next_id, state.pomodoros.clone());
+
+        try!(work(&staged));
+        let (next_id, pomodoros) = try!(staged.into_pomodoros());
+
+        let mut changes: Vec<Value> = pomodoros.iter()
+            .filter_map(|pomodoro| {
+                match state.pomodoros.iter().find(|stored| stored.id == pomodoro.id) {
+                    None => Some(journal_entry("create", pomodoro)),
+                    Some(stored) if stored != pomodoro => Some(journal_entry("update", pomodoro)),
+                    Some(_) => None,
+                }
+            })
+            .collect();
+        changes.extend(state.pomodoros
+            .iter()
+            .filter(|stored| !pomodoros.iter().any(|pomodoro| pomodoro.id == stored.id))
+            .map(|stored| journal_entry("delete", stored)));
+
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        let mut batch = Map::new();
+        batch.insert(String::from("op"), Value::from("batch"));
+        batch.insert(String::from("entries"), Value::Array(changes));
+        batch.insert(String::from("next_id"), Value::from(next_id));
+
+        // Compaction after the append rewrites the journal from state.pomodoros, so it has to
+        // hold the committed records already.
+        let previous = mem::replace(&mut state.pomodoros, pomodoros);
+        if let Err(e) = self.append(&mut state, Value::Object(batch)) {
+            state.pomodoros = previous;
+            return Err(e);
+        }
+        state.next_id = next_id;
+        Ok(())
+    }
+}
+
+fn journal_entry(op: &str, pomodoro: &Pomodoro) -> Value {
+    let mut entry = Map::new();
+    entry.insert(String::from("op"), Value::from(op));
+    entry.insert(String::from("pomodoro"), pomodoro_json::to_json(pomodoro));
+    Value::Object(entry)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::env;
+    use std::io::Read;
+    use std::os;
+    use std::process;
+
+    fn journal_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("solanum-journal-{}-{}.jsonl", name, process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn journal_lines(path: &Path) -> usize {
+        let mut contents = String::new();
+        fs::File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+        contents.lines().count()
+    }
+
+    #[test]
+    fn rebuilds_state_by_replaying_the_journal() {
+        let path = journal_path("replay");
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let expected = {
+            let journal = JournalPomodoros::open(&path).unwrap();
+            journal.create(start_time, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+            let mut pomodoro = journal.most_recent().unwrap().unwrap();
+            pomodoro.work_end_time = Some(start_time + Duration::seconds(1));
+            pomodoro.status = PomodoroStatus::Aborted;
+            journal.update(pomodoro.id, pomodoro.clone()).unwrap();
+            pomodoro
+        };
+
+        let journal = JournalPomodoros::open(&path).unwrap();
+
+        assert!(journal.most_recent().unwrap() == Some(expected));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn continues_allocating_ids_after_a_replay() {
+        let path = journal_path("ids");
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        JournalPomodoros::open(&path)
+            .unwrap()
+            .create(start_time, Duration::seconds(5), Duration::seconds(5), vec![])
+            .unwrap();
+
+        let journal = JournalPomodoros::open(&path).unwrap();
+        journal.create(start_time + Duration::seconds(10), Duration::seconds(5), Duration::seconds(5), vec![])
+            .unwrap();
+
+        assert!(journal.most_recent().unwrap().unwrap().id == 2);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn does_not_give_out_the_id_of_a_deleted_pomodoro_again() {
+        let path = journal_path("deleted-ids");
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        {
+            let journal = JournalPomodoros::open(&path).unwrap();
+            for offset in 0..2 {
+                journal.create(start_time + Duration::seconds(offset * 10),
+                             Duration::seconds(5),
+                             Duration::seconds(5),
+                             vec![])
+                    .unwrap();
+            }
+            journal.delete(2).unwrap();
+        }
+        // Opening the journal again compacts the deletion away.
+        JournalPomodoros::open(&path).unwrap();
+        assert!(journal_lines(&path) == 2);
+
+        let journal = JournalPomodoros::open(&path).unwrap();
+        journal.create(start_time + Duration::seconds(20), Duration::seconds(5), Duration::seconds(5), vec![])
+            .unwrap();
+
+        assert!(journal.most_recent().unwrap().unwrap().id == 3);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn replays_inserted_records() {
+        let path = journal_path("insert");
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let expected = {
+            let journal = JournalPomodoros::open(&path).unwrap();
+            journal.create(start_time, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+            let mut logged = journal.most_recent().unwrap().unwrap();
+            logged.work_start_time = start_time - Duration::seconds(10);
+            logged.work_end_time = Some(start_time - Duration::seconds(5));
+            logged.status = PomodoroStatus::BreakSkipped;
+            let id = journal.insert(logged.clone()).unwrap();
+            Pomodoro { id: id, ..logged }
+        };
+
+        let journal = JournalPomodoros::open(&path).unwrap();
+
+        assert!(expected.id == 2);
+        assert!(journal.find(2).unwrap() == Some(expected));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn replays_deletions() {
+        let path = journal_path("delete");
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        {
+            let journal = JournalPomodoros::open(&path).unwrap();
+            for offset in 0..3 {
+                journal.create(start_time + Duration::seconds(offset * 10),
+                             Duration::seconds(5),
+                             Duration::seconds(5),
+                             vec![])
+                    .unwrap();
+            }
+            journal.delete(1).unwrap();
+            journal.transaction(&mut |pomodoros: &Pomodoros| pomodoros.delete(2)).unwrap();
+        }
+
+        let journal = JournalPomodoros::open(&path).unwrap();
+
+        let ids: Vec<i32> = journal.last(5).unwrap().into_iter().map(|p| p.id).collect();
+        assert!(ids == vec![3]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn compacts_superseded_entries() {
+        let path = journal_path("compaction");
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let journal = JournalPomodoros::open(&path).unwrap();
+        journal.create(start_time, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+        let pomodoro = journal.most_recent().unwrap().unwrap();
+
+        for _ in 0..COMPACTION_THRESHOLD {
+            journal.update(pomodoro.id, pomodoro.clone()).unwrap();
+        }
+
+        assert!(journal_lines(&path) == COMPACTION_THRESHOLD + 1);
+        journal.update(pomodoro.id, pomodoro.clone()).unwrap();
+        assert!(journal_lines(&path) == 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn replays_a_transaction_as_a_whole() {
+        let path = journal_path("transaction");
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let expected = {
+            let journal = JournalPomodoros::open(&path).unwrap();
+            journal.create(start_time, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+            let mut aborted = journal.most_recent().unwrap().unwrap();
+            aborted.work_end_time = Some(start_time + Duration::seconds(1));
+            aborted.status = PomodoroStatus::Aborted;
+            journal.transaction(&mut |pomodoros: &Pomodoros| {
+                    try!(pomodoros.update(aborted.id, aborted.clone()));
+                    pomodoros.create(start_time + Duration::seconds(1),
+                                     Duration::seconds(5),
+                                     Duration::seconds(5),
+                                     vec![])
+                })
+                .unwrap();
+            journal.last(5).unwrap()
+        };
+
+        let journal = JournalPomodoros::open(&path).unwrap();
+
+        assert!(journal_lines(&path) == 2);
+        assert!(journal.last(5).unwrap() == expected);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn journals_nothing_for_a_failed_transaction() {
+        let path = journal_path("failed-transaction");
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let journal = JournalPomodoros::open(&path).unwrap();
+
+        let result = journal.transaction(&mut |pomodoros: &Pomodoros| {
+            try!(pomodoros.create(start_time, Duration::seconds(5), Duration::seconds(5), vec![]));
+            Err(Error::from(String::from("interrupted")))
+        });
+
+        assert!(result.is_err());
+        assert!(journal.most_recent().unwrap() == None);
+        assert!(journal_lines(&path) == 0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rewrites_the_journal_before_appending_after_a_partly_written_entry() {
+        let path = journal_path("partly-written");
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let journal = JournalPomodoros::open(&path).unwrap();
+        journal.create(start_time, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+        write!(JournalPomodoros::open_for_append(&path).unwrap(), "{{\"op\":\"upd").unwrap();
+        journal.lock_state().unwrap().torn = true;
+
+        journal.create(start_time + Duration::seconds(10), Duration::seconds(5), Duration::seconds(5), vec![])
+            .unwrap();
+
+        assert!(JournalPomodoros::open(&path).unwrap().last(5).unwrap().len() == 2);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn fails_to_replay_a_journal_that_cannot_be_read() {
+        let path = journal_path("unreadable");
+        os::unix::fs::symlink(&path, &path).unwrap();
+
+        let result = JournalPomodoros::replay(&path);
+
+        assert!(result.is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn ignores_a_torn_final_entry() {
+        let path = journal_path("torn");
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        JournalPomodoros::open(&path)
+            .unwrap()
+            .create(start_time, Duration::seconds(5), Duration::seconds(5), vec![])
+            .unwrap();
+        write!(JournalPomodoros::open_for_append(&path).unwrap(), "{{\"op\":\"upd").unwrap();
+
+        let journal = JournalPomodoros::open(&path).unwrap();
+
+        assert!(journal.last(5).unwrap().len() == 1);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,252 @@
+use daemon::postgres;
+
+use daemon::result::Error;
+use daemon::result::Result;
+
+pub struct Migration {
+    pub version: i32,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+/// Migrations for the PostgreSQL schema, applied in order of version. The version of the most
+/// recently applied migration is recorded in the schema_migrations table.
+///
+/// Never edit a migration that has been released; add a new one instead.
+pub const POSTGRES_MIGRATIONS: &'static [Migration] = &[
+    Migration {
+        version: 1,
+        description: "create pomodoros table",
+        sql: "CREATE TABLE IF NOT EXISTS pomodoros (
+                  id SERIAL PRIMARY KEY,
+                  work_start_time TIMESTAMP WITH TIME ZONE NOT NULL,
+                  work_end_time TIMESTAMP WITH TIME ZONE,
+                  break_start_time TIMESTAMP WITH TIME ZONE,
+                  break_end_time TIMESTAMP WITH TIME ZONE,
+                  work_length BIGINT NOT NULL,
+                  break_length BIGINT NOT NULL,
+                  tags TEXT NOT NULL DEFAULT '',
+                  status TEXT NOT NULL
+              );",
+    },
+    Migration {
+        version: 2,
+        description: "store tags as a list",
+        sql: "ALTER TABLE pomodoros ALTER COLUMN tags DROP DEFAULT;
+              ALTER TABLE pomodoros ALTER COLUMN tags TYPE TEXT[] USING
+                  CASE WHEN tags = '' THEN '{}'::TEXT[] ELSE string_to_array(tags, ',') END;
+              ALTER TABLE pomodoros ALTER COLUMN tags SET DEFAULT '{}';",
+    },
+    Migration {
+        version: 3,
+        description: "add notes column",
+        sql: "ALTER TABLE pomodoros ADD COLUMN notes TEXT;",
+    },
+    Migration {
+        version: 4,
+        description: "add pause intervals",
+        sql: "CREATE TABLE pomodoro_pauses (
+                  pomodoro_id INTEGER NOT NULL REFERENCES pomodoros (id) ON DELETE CASCADE,
+                  start_time TIMESTAMP WITH TIME ZONE NOT NULL,
+                  end_time TIMESTAMP WITH TIME ZONE
+              );
+              CREATE INDEX pomodoro_pauses_pomodoro_id_idx ON pomodoro_pauses (pomodoro_id);",
+    },
+    Migration {
+        version: 5,
+        description: "index pomodoros by work_start_time",
+        sql: "CREATE INDEX pomodoros_work_start_time_idx ON pomodoros (work_start_time);",
+    },
+    Migration {
+        version: 6,
+        description: "order the work_start_time index by id as well",
+        sql: "DROP INDEX IF EXISTS pomodoros_work_start_time_idx;
+              CREATE INDEX pomodoros_work_start_time_id_idx ON pomodoros (work_start_time, id);",
+    },
+    Migration {
+        version: 7,
+        description: "add cycle_position column",
+        sql: "ALTER TABLE pomodoros ADD COLUMN cycle_position INTEGER NOT NULL DEFAULT 1;",
+    },
+    Migration {
+        version: 8,
+        description: "add extensions",
+        sql: "CREATE TABLE pomodoro_extensions (
+                  pomodoro_id INTEGER NOT NULL REFERENCES pomodoros (id) ON DELETE CASCADE,
+                  time TIMESTAMP WITH TIME ZONE NOT NULL,
+                  period TEXT NOT NULL,
+                  length BIGINT NOT NULL
+              );
+              CREATE INDEX pomodoro_extensions_pomodoro_id_idx ON pomodoro_extensions (pomodoro_id);",
+    },
+    Migration {
+        version: 9,
+        description: "add interruptions",
+        sql: "CREATE TABLE pomodoro_interruptions (
+                  pomodoro_id INTEGER NOT NULL REFERENCES pomodoros (id) ON DELETE CASCADE,
+                  time TIMESTAMP WITH TIME ZONE NOT NULL,
+                  kind TEXT NOT NULL,
+                  note TEXT
+              );
+              CREATE INDEX pomodoro_interruptions_pomodoro_id_idx
+                  ON pomodoro_interruptions (pomodoro_id);",
+    },
+];
+
+/// Migrations for the SQLite schema. The version of the most recently applied migration is kept
+/// in the file's user_version, and pending migrations are applied whenever the file is opened.
+///
+/// Files created before migrations were tracked have a user_version of 0 and already hold the
+/// first migration's tables, which is why it only creates what is missing.
+pub const SQLITE_MIGRATIONS: &'static [Migration] = &[
+    Migration {
+        version: 1,
+        description: "create pomodoros, tags and pauses tables",
+        sql: "CREATE TABLE IF NOT EXISTS pomodoros (
+                  id INTEGER PRIMARY KEY AUTOINCREMENT,
+                  work_start_time INTEGER NOT NULL,
+                  work_end_time INTEGER,
+                  break_start_time INTEGER,
+                  break_end_time INTEGER,
+                  work_length INTEGER NOT NULL,
+                  break_length INTEGER NOT NULL,
+                  status TEXT NOT NULL
+              );
+              CREATE INDEX IF NOT EXISTS pomodoros_work_start_time_idx ON pomodoros (work_start_time);
+              CREATE TABLE IF NOT EXISTS pomodoro_tags (
+                  pomodoro_id INTEGER NOT NULL REFERENCES pomodoros (id) ON DELETE CASCADE,
+                  position INTEGER NOT NULL,
+                  tag TEXT NOT NULL,
+                  PRIMARY KEY (pomodoro_id, position)
+              );
+              CREATE TABLE IF NOT EXISTS pomodoro_pauses (
+                  pomodoro_id INTEGER NOT NULL REFERENCES pomodoros (id) ON DELETE CASCADE,
+                  start_time INTEGER NOT NULL,
+                  end_time INTEGER
+              );
+              CREATE INDEX IF NOT EXISTS pomodoro_pauses_pomodoro_id_idx
+                  ON pomodoro_pauses (pomodoro_id);",
+    },
+    Migration {
+        version: 2,
+        description: "add cycle_position column",
+        sql: "ALTER TABLE pomodoros ADD COLUMN cycle_position INTEGER NOT NULL DEFAULT 1;",
+    },
+    Migration {
+        version: 3,
+        description: "add extensions",
+        sql: "CREATE TABLE pomodoro_extensions (
+                  pomodoro_id INTEGER NOT NULL REFERENCES pomodoros (id) ON DELETE CASCADE,
+                  time INTEGER NOT NULL,
+                  period TEXT NOT NULL,
+                  length INTEGER NOT NULL
+              );
+              CREATE INDEX pomodoro_extensions_pomodoro_id_idx ON pomodoro_extensions (pomodoro_id);",
+    },
+    Migration {
+        version: 4,
+        description: "add interruptions",
+        sql: "CREATE TABLE pomodoro_interruptions (
+                  pomodoro_id INTEGER NOT NULL REFERENCES pomodoros (id) ON DELETE CASCADE,
+                  time INTEGER NOT NULL,
+                  kind TEXT NOT NULL,
+                  note TEXT
+              );
+              CREATE INDEX pomodoro_interruptions_pomodoro_id_idx
+                  ON pomodoro_interruptions (pomodoro_id);",
+    },
+    Migration {
+        version: 5,
+        description: "add notes column",
+        sql: "ALTER TABLE pomodoros ADD COLUMN notes TEXT;",
+    },
+];
+
+const CREATE_SCHEMA_MIGRATIONS: &'static str = "CREATE TABLE IF NOT EXISTS schema_migrations (
+        version INTEGER PRIMARY KEY,
+        description TEXT NOT NULL,
+        applied_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+    );";
+
+pub fn pending_after(version: i32, migrations: &'static [Migration]) -> Vec<&'static Migration> {
+    migrations.iter().filter(|migration| migration.version > version).collect()
+}
+
+/// Reads the version of the schema without changing it, so that checking for pending
+/// migrations is safe on a database that is not to be migrated. A database without a
+/// schema_migrations table has had no migrations applied.
+pub fn current_version(conn: &postgres::Connection) -> Result<i32> {
+    let rows = try!(conn.query("SELECT to_regclass('schema_migrations') IS NOT NULL", &[]));
+    let tracked: bool = rows.get(0).get(0);
+    if !tracked {
+        return Ok(0);
+    }
+
+    let rows = try!(conn.query("SELECT COALESCE(MAX(version), 0) FROM schema_migrations", &[]));
+    Ok(rows.get(0).get(0))
+}
+
+pub fn pending(conn: &postgres::Connection) -> Result<Vec<&'static Migration>> {
+    current_version(conn).map(|version| pending_after(version, POSTGRES_MIGRATIONS))
+}
+
+/// Applies every pending migration, each in its own transaction, and returns those applied.
+/// The schema_migrations table is locked while a migration runs so that two daemons migrating
+/// the same database cannot apply a migration twice.
+pub fn migrate(conn: &postgres::Connection) -> Result<Vec<&'static Migration>> {
+    let mut applied = Vec::new();
+
+    try!(conn.batch_execute(CREATE_SCHEMA_MIGRATIONS));
+    for migration in try!(pending(conn)) {
+        let transaction = try!(conn.transaction());
+        try!(transaction.batch_execute("LOCK TABLE schema_migrations IN EXCLUSIVE MODE"));
+
+        let rows = try!(transaction.query("SELECT 1 FROM schema_migrations WHERE version = $1",
+                                          &[&migration.version]));
+        if !rows.is_empty() {
+            continue;
+        }
+
+        try!(transaction.batch_execute(migration.sql).map_err(|e| {
+            Error::from(format!("Migration {} ({}) failed: {}",
+                                migration.version,
+                                migration.description,
+                                e))
+        }));
+        try!(transaction.execute("INSERT INTO schema_migrations (version, description) VALUES ($1, $2)",
+                                 &[&migration.version, &migration.description]));
+        try!(transaction.commit());
+
+        info!("Applied migration {}: {}", migration.version, migration.description);
+        applied.push(migration);
+    }
+
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn migrations_are_numbered_consecutively_from_one() {
+        for (index, migration) in POSTGRES_MIGRATIONS.iter().enumerate() {
+            assert!(migration.version == index as i32 + 1);
+        }
+    }
+
+    #[test]
+    fn sqlite_migrations_are_numbered_consecutively_from_one() {
+        for (index, migration) in SQLITE_MIGRATIONS.iter().enumerate() {
+            assert!(migration.version == index as i32 + 1);
+        }
+    }
+
+    #[test]
+    fn only_migrations_newer_than_the_current_version_are_pending() {
+        let pending = pending_after(3, POSTGRES_MIGRATIONS);
+
+        assert!(pending.iter().map(|migration| migration.version).collect::<Vec<i32>>() ==
+                (4..POSTGRES_MIGRATIONS.len() as i32 + 1).collect::<Vec<i32>>());
+    }
+}
@@ -1,26 +1,47 @@
 extern crate chrono;
 extern crate crossbeam;
+extern crate openssl;
 extern crate postgres;
+extern crate regex;
+extern crate rusqlite;
+extern crate serde_json;
 
 #[cfg(test)] extern crate mockers_derive;
 
 pub mod clock;
 pub mod command;
 pub mod command_processor;
+pub mod config;
+pub mod connection_pool;
 pub mod daemon_container;
+pub mod in_memory_pomodoros;
 pub mod io;
+pub mod journal_pomodoros;
+pub mod migrations;
 pub mod pomodoro;
+pub mod pomodoro_event;
+pub mod pomodoro_json;
 pub mod pomodoros;
 pub mod pomodoro_query_mapper;
+pub mod pomodoro_ticker;
 pub mod pomodoro_transitioner;
+pub mod protocol;
+pub mod response;
 pub mod result;
+pub mod sqlite_pomodoro_query_mapper;
 pub mod system_clock;
 
 pub use self::daemon_container::DaemonContainer;
 
 pub use self::command::Command;
 pub use self::command_processor::CommandProcessor;
+pub use self::config::Config;
+pub use self::response::Response;
 
+pub use self::in_memory_pomodoros::InMemoryPomodoros;
+pub use self::journal_pomodoros::JournalPomodoros;
 pub use self::pomodoro_query_mapper::PomodoroQueryMapper;
+pub use self::sqlite_pomodoro_query_mapper::SqlitePomodoroQueryMapper;
 
+pub use self::pomodoro_ticker::PomodoroTicker;
 pub use self::pomodoro_transitioner::PomodoroTransitioner;
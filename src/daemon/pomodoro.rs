@@ -2,6 +2,7 @@ use daemon::chrono::Duration;
 use daemon::chrono::datetime::DateTime;
 use daemon::chrono::offset::utc::UTC;
 
+use std::cmp;
 use std::fmt;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -13,27 +14,227 @@ pub struct Pomodoro {
     pub break_end_time: Option<DateTime<UTC>>,
     pub work_length: Duration,
     pub break_length: Duration,
-    pub tags: String,
+    pub tags: Vec<String>,
+    pub pauses: Vec<Pause>,
+    /// Position of this pomodoro in its cycle, counting from 1. The pomodoro that ends a cycle
+    /// is followed by a long break.
+    pub cycle_position: i32,
+    pub extensions: Vec<Extension>,
+    pub interruptions: Vec<Interruption>,
+    /// What was written down about the pomodoro, one note per line.
+    pub notes: Option<String>,
     pub status: PomodoroStatus,
 }
 
+/// An interval during which a pomodoro was paused. A pause that has not been resumed yet has
+/// no end time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pause {
+    pub start_time: DateTime<UTC>,
+    pub end_time: Option<DateTime<UTC>>,
+}
+
+/// Time added to the work period or break of a pomodoro, kept so that its lengths can be
+/// accounted for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Extension {
+    pub time: DateTime<UTC>,
+    pub period: Period,
+    pub length: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Period {
+    Work,
+    Break,
+}
+
+/// Something that broke concentration during the work period, whether it came from the person
+/// working or from someone else.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Interruption {
+    pub time: DateTime<UTC>,
+    pub kind: InterruptionKind,
+    pub note: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InterruptionKind {
+    Internal,
+    External,
+}
+
+/// A correction to one field of a recorded pomodoro. Times that can be missing are cleared with
+/// None.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PomodoroEdit {
+    WorkStartTime(DateTime<UTC>),
+    WorkEndTime(Option<DateTime<UTC>>),
+    BreakStartTime(Option<DateTime<UTC>>),
+    BreakEndTime(Option<DateTime<UTC>>),
+    WorkLength(Duration),
+    BreakLength(Duration),
+    Tags(Vec<String>),
+    Status(PomodoroStatus),
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum PomodoroStatus {
     InProgress,
+    Paused,
     Aborted,
     BreakPending,
     Break,
+    BreakPaused,
     Completed,
+    /// The work period was finished but its break was never taken.
+    BreakSkipped,
+}
+
+impl Pomodoro {
+    /// Time spent paused during the work period, counting a pause still open at `now`.
+    pub fn work_paused_time(&self, now: DateTime<UTC>) -> Duration {
+        self.paused_time(now, |pause| {
+            self.break_start_time.map(|start| pause.start_time < start).unwrap_or(true)
+        })
+    }
+
+    /// Time spent paused during the break, counting a pause still open at `now`.
+    pub fn break_paused_time(&self, now: DateTime<UTC>) -> Duration {
+        match self.break_start_time {
+            Some(start) => self.paused_time(now, |pause| pause.start_time >= start),
+            None => Duration::zero(),
+        }
+    }
+
+    /// When the work period ends, given the pauses taken up to `now`.
+    pub fn work_deadline(&self, now: DateTime<UTC>) -> DateTime<UTC> {
+        self.work_start_time + self.work_length + self.work_paused_time(now)
+    }
+
+    /// When the break ends, given the pauses taken up to `now`, if it has started.
+    pub fn break_deadline(&self, now: DateTime<UTC>) -> Option<DateTime<UTC>> {
+        self.break_start_time.map(|start| start + self.break_length + self.break_paused_time(now))
+    }
+
+    /// When the running work period or break ends, given the pauses taken up to `now`. Nothing
+    /// is due while a pomodoro is paused, waiting for its break or finished.
+    pub fn next_deadline(&self, now: DateTime<UTC>) -> Option<DateTime<UTC>> {
+        match self.status {
+            PomodoroStatus::InProgress => Some(self.work_deadline(now)),
+            PomodoroStatus::Break => self.break_deadline(now),
+            _ => None,
+        }
+    }
+
+    /// When the pomodoro was over, if it is: the end of its break if one was taken, otherwise the
+    /// end of its work period.
+    pub fn end_time(&self) -> Option<DateTime<UTC>> {
+        match self.status {
+            PomodoroStatus::Completed => self.break_end_time.or(self.work_end_time),
+            PomodoroStatus::Aborted | PomodoroStatus::BreakSkipped => self.work_end_time,
+            _ => None,
+        }
+    }
+
+    /// Total time added to `period` by extensions.
+    pub fn extended_time(&self, period: Period) -> Duration {
+        self.extensions
+            .iter()
+            .filter(|extension| extension.period == period)
+            .fold(Duration::zero(), |total, extension| total + extension.length)
+    }
+
+    /// Number of interruptions of the given kind.
+    pub fn interruption_count(&self, kind: InterruptionKind) -> usize {
+        self.interruptions.iter().filter(|interruption| interruption.kind == kind).count()
+    }
+
+    /// Adds a note on a line of its own after any notes already written.
+    pub fn add_note(&mut self, note: &str) {
+        self.notes = Some(match self.notes {
+            Some(ref notes) => format!("{}\n{}", notes, note),
+            None => String::from(note),
+        });
+    }
+
+    fn paused_time<F: Fn(&Pause) -> bool>(&self, now: DateTime<UTC>, during: F) -> Duration {
+        self.pauses
+            .iter()
+            .filter(|pause| during(pause))
+            .fold(Duration::zero(), |total, pause| {
+                let end_time = pause.end_time.unwrap_or(cmp::max(now, pause.start_time));
+                total + (end_time - pause.start_time)
+            })
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Period::Work => write!(f, "Work"),
+            Period::Break => write!(f, "Break"),
+        }
+    }
+}
+
+impl From<String> for Period {
+    fn from(string: String) -> Period {
+        match string.as_str() {
+            "Break" => Period::Break,
+            _ => Period::Work,
+        }
+    }
+}
+
+impl fmt::Display for InterruptionKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InterruptionKind::Internal => write!(f, "internal"),
+            InterruptionKind::External => write!(f, "external"),
+        }
+    }
+}
+
+impl From<String> for InterruptionKind {
+    fn from(string: String) -> InterruptionKind {
+        match string.as_str() {
+            "external" => InterruptionKind::External,
+            _ => InterruptionKind::Internal,
+        }
+    }
+}
+
+impl fmt::Display for PomodoroEdit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn time(time: &Option<DateTime<UTC>>) -> String {
+            time.map(|time| time.to_rfc3339()).unwrap_or(String::from("none"))
+        }
+
+        match *self {
+            PomodoroEdit::WorkStartTime(start) => write!(f, "work_start_time={}", start.to_rfc3339()),
+            PomodoroEdit::WorkEndTime(ref end) => write!(f, "work_end_time={}", time(end)),
+            PomodoroEdit::BreakStartTime(ref start) => write!(f, "break_start_time={}", time(start)),
+            PomodoroEdit::BreakEndTime(ref end) => write!(f, "break_end_time={}", time(end)),
+            PomodoroEdit::WorkLength(length) => write!(f, "work_length={}", length.num_seconds()),
+            PomodoroEdit::BreakLength(length) => write!(f, "break_length={}", length.num_seconds()),
+            PomodoroEdit::Tags(ref tags) => write!(f, "tags={}", tags.join(",")),
+            PomodoroEdit::Status(ref status) => write!(f, "status={}", status),
+        }
+    }
 }
 
 impl fmt::Display for PomodoroStatus {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             PomodoroStatus::InProgress => write!(f, "InProgress"),
+            PomodoroStatus::Paused => write!(f, "Paused"),
             PomodoroStatus::Aborted => write!(f, "Aborted"),
             PomodoroStatus::BreakPending => write!(f, "BreakPending"),
             PomodoroStatus::Break => write!(f, "Break"),
+            PomodoroStatus::BreakPaused => write!(f, "BreakPaused"),
             PomodoroStatus::Completed => write!(f, "Completed"),
+            PomodoroStatus::BreakSkipped => write!(f, "BreakSkipped"),
         }
     }
 }
@@ -42,9 +243,12 @@ impl From<String> for PomodoroStatus {
     fn from(string: String) -> PomodoroStatus {
         match string.as_str() {
             "InProgress" => PomodoroStatus::InProgress,
+            "Paused" => PomodoroStatus::Paused,
             "BreakPending" => PomodoroStatus::BreakPending,
             "Break" => PomodoroStatus::Break,
+            "BreakPaused" => PomodoroStatus::BreakPaused,
             "Completed" => PomodoroStatus::Completed,
+            "BreakSkipped" => PomodoroStatus::BreakSkipped,
             _ => PomodoroStatus::Aborted,
         }
     }
@@ -0,0 +1,209 @@
+use daemon::io::CanSend;
+use daemon::pomodoro::Pomodoro;
+use daemon::pomodoro::PomodoroStatus;
+
+use std::fmt;
+use std::sync::Mutex;
+
+/// The steps in a pomodoro's life that subscribed clients are told about.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PomodoroEventKind {
+    Started,
+    BreakPending,
+    BreakStarted,
+    /// The pomodoro was closed after its work was done, whether or not its break was taken.
+    Completed,
+    Aborted,
+    /// The pomodoro went back to an earlier step, as when its work is extended after running
+    /// out, or when a change that finished it is undone or edited away.
+    Reopened,
+}
+
+impl fmt::Display for PomodoroEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PomodoroEventKind::Started => write!(f, "started"),
+            PomodoroEventKind::BreakPending => write!(f, "break_pending"),
+            PomodoroEventKind::BreakStarted => write!(f, "break_started"),
+            PomodoroEventKind::Completed => write!(f, "completed"),
+            PomodoroEventKind::Aborted => write!(f, "aborted"),
+            PomodoroEventKind::Reopened => write!(f, "reopened"),
+        }
+    }
+}
+
+/// A pomodoro as it stands just after reaching a step of its life.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PomodoroEvent {
+    pub kind: PomodoroEventKind,
+    pub pomodoro: Pomodoro,
+}
+
+impl PomodoroEvent {
+    /// Works out which step a change took a pomodoro to, given how it was before the change, or
+    /// None if the change created it. Changes that keep it at the same step, such as pausing and
+    /// resuming, are not events, and neither are records created already finished, as by LOG.
+    /// A pomodoro only starts when it is created; going back to work or to a break from a later
+    /// step reopens it.
+    pub fn between(before: Option<&Pomodoro>, after: &Pomodoro) -> Option<PomodoroEvent> {
+        let before_status = match before {
+            Some(pomodoro) => pomodoro.status.clone(),
+            None if after.status == PomodoroStatus::InProgress => {
+                return Some(PomodoroEvent {
+                    kind: PomodoroEventKind::Started,
+                    pomodoro: after.clone(),
+                })
+            }
+            None => return None,
+        };
+        if before_status == after.status {
+            return None;
+        }
+
+        let kind = match (before_status, after.status.clone()) {
+            (PomodoroStatus::Paused, PomodoroStatus::InProgress) => return None,
+            (PomodoroStatus::BreakPaused, PomodoroStatus::Break) => return None,
+            (_, PomodoroStatus::Paused) => return None,
+            (_, PomodoroStatus::BreakPaused) => return None,
+            (PomodoroStatus::InProgress, PomodoroStatus::BreakPending) |
+            (PomodoroStatus::Paused, PomodoroStatus::BreakPending) => PomodoroEventKind::BreakPending,
+            (PomodoroStatus::InProgress, PomodoroStatus::Break) |
+            (PomodoroStatus::Paused, PomodoroStatus::Break) |
+            (PomodoroStatus::BreakPending, PomodoroStatus::Break) => PomodoroEventKind::BreakStarted,
+            (_, PomodoroStatus::Completed) => PomodoroEventKind::Completed,
+            (_, PomodoroStatus::BreakSkipped) => PomodoroEventKind::Completed,
+            (_, PomodoroStatus::Aborted) => PomodoroEventKind::Aborted,
+            (_, PomodoroStatus::InProgress) |
+            (_, PomodoroStatus::BreakPending) |
+            (_, PomodoroStatus::Break) => PomodoroEventKind::Reopened,
+        };
+
+        Some(PomodoroEvent {
+            kind: kind,
+            pomodoro: after.clone(),
+        })
+    }
+}
+
+/// Sent as is to clients that subscribed with a text command.
+impl fmt::Display for PomodoroEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "{} #{} [{}]",
+               self.kind,
+               self.pomodoro.id,
+               self.pomodoro.work_start_time.format("%F %H:%M:%S"))
+    }
+}
+
+/// Hands events on to be broadcast to subscribed clients, if there is anywhere to hand them.
+pub struct EventPublisher {
+    sender: Option<Mutex<Box<CanSend<PomodoroEvent> + Send>>>,
+}
+
+impl EventPublisher {
+    pub fn new<S: CanSend<PomodoroEvent> + Send + 'static>(sender: S) -> EventPublisher {
+        EventPublisher { sender: Some(Mutex::new(Box::new(sender))) }
+    }
+
+    /// Drops every event, for when no client can subscribe.
+    pub fn none() -> EventPublisher {
+        EventPublisher { sender: None }
+    }
+
+    /// Events that cannot be handed on are logged rather than failing the change that caused
+    /// them, which has already been made by then.
+    pub fn publish(&self, events: Vec<PomodoroEvent>) {
+        let sender = match self.sender {
+            Some(ref sender) => sender,
+            None => return,
+        };
+
+        match sender.lock() {
+            Ok(sender) => {
+                for event in events {
+                    if let Err(e) = sender.send(event) {
+                        warn!("Could not publish event: {}", e);
+                    }
+                }
+            }
+            Err(_) => warn!("Event publisher lock was poisoned"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use daemon::chrono::Duration;
+    use daemon::chrono::datetime::DateTime;
+    use daemon::chrono::offset::utc::UTC;
+
+    fn pomodoro(status: PomodoroStatus) -> Pomodoro {
+        Pomodoro {
+            id: 3,
+            work_start_time: "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap(),
+            work_end_time: None,
+            break_start_time: None,
+            break_end_time: None,
+            work_length: Duration::seconds(1500),
+            break_length: Duration::seconds(300),
+            tags: vec![],
+            pauses: vec![],
+            cycle_position: 1,
+            extensions: vec![],
+            interruptions: vec![],
+            notes: None,
+            status: status,
+        }
+    }
+
+    fn kind_between(before: Option<PomodoroStatus>, after: PomodoroStatus) -> Option<PomodoroEventKind> {
+        let before = before.map(pomodoro);
+        PomodoroEvent::between(before.as_ref(), &pomodoro(after)).map(|event| event.kind)
+    }
+
+    #[test]
+    fn names_the_step_a_pomodoro_was_moved_to() {
+        assert!(kind_between(None, PomodoroStatus::InProgress) == Some(PomodoroEventKind::Started));
+        assert!(kind_between(Some(PomodoroStatus::InProgress), PomodoroStatus::BreakPending) ==
+                Some(PomodoroEventKind::BreakPending));
+        assert!(kind_between(Some(PomodoroStatus::BreakPending), PomodoroStatus::Break) ==
+                Some(PomodoroEventKind::BreakStarted));
+        assert!(kind_between(Some(PomodoroStatus::Break), PomodoroStatus::Completed) ==
+                Some(PomodoroEventKind::Completed));
+        assert!(kind_between(Some(PomodoroStatus::BreakPending), PomodoroStatus::BreakSkipped) ==
+                Some(PomodoroEventKind::Completed));
+        assert!(kind_between(Some(PomodoroStatus::Paused), PomodoroStatus::Aborted) ==
+                Some(PomodoroEventKind::Aborted));
+    }
+
+    #[test]
+    fn starts_only_pomodoros_that_were_just_created() {
+        assert!(kind_between(Some(PomodoroStatus::BreakPending), PomodoroStatus::InProgress) ==
+                Some(PomodoroEventKind::Reopened));
+        assert!(kind_between(Some(PomodoroStatus::Aborted), PomodoroStatus::InProgress) ==
+                Some(PomodoroEventKind::Reopened));
+        assert!(kind_between(Some(PomodoroStatus::Completed), PomodoroStatus::Break) ==
+                Some(PomodoroEventKind::Reopened));
+        assert!(kind_between(Some(PomodoroStatus::BreakSkipped), PomodoroStatus::BreakPending) ==
+                Some(PomodoroEventKind::Reopened));
+    }
+
+    #[test]
+    fn ignores_pauses_notes_and_logged_pomodoros() {
+        assert!(kind_between(Some(PomodoroStatus::InProgress), PomodoroStatus::Paused) == None);
+        assert!(kind_between(Some(PomodoroStatus::Paused), PomodoroStatus::InProgress) == None);
+        assert!(kind_between(Some(PomodoroStatus::BreakPaused), PomodoroStatus::Break) == None);
+        assert!(kind_between(Some(PomodoroStatus::InProgress), PomodoroStatus::InProgress) == None);
+        assert!(kind_between(None, PomodoroStatus::Completed) == None);
+    }
+
+    #[test]
+    fn shows_events_to_text_subscribers_on_one_line() {
+        let event = PomodoroEvent::between(None, &pomodoro(PomodoroStatus::InProgress)).unwrap();
+
+        assert!(event.to_string() == "started #3 [2000-01-01 00:00:00]");
+    }
+}
@@ -0,0 +1,280 @@
+use daemon::chrono::Duration;
+use daemon::chrono::datetime::DateTime;
+use daemon::chrono::offset::utc::UTC;
+
+use daemon::pomodoro::Extension;
+use daemon::pomodoro::Interruption;
+use daemon::pomodoro::InterruptionKind;
+use daemon::pomodoro::Pause;
+use daemon::pomodoro::Period;
+use daemon::pomodoro::Pomodoro;
+use daemon::pomodoro::PomodoroStatus;
+
+use daemon::serde_json::{Map, Value};
+
+use daemon::result::Error;
+use daemon::result::Result;
+
+use std::option::Option;
+
+/// Converts a pomodoro into a JSON object. Times are RFC 3339 strings and lengths are seconds.
+pub fn to_json(pomodoro: &Pomodoro) -> Value {
+    let mut object = Map::new();
+    object.insert(String::from("id"), Value::from(pomodoro.id));
+    object.insert(String::from("work_start_time"), time_to_json(Some(pomodoro.work_start_time)));
+    object.insert(String::from("work_end_time"), time_to_json(pomodoro.work_end_time));
+    object.insert(String::from("break_start_time"), time_to_json(pomodoro.break_start_time));
+    object.insert(String::from("break_end_time"), time_to_json(pomodoro.break_end_time));
+    object.insert(String::from("work_length"), Value::from(pomodoro.work_length.num_seconds()));
+    object.insert(String::from("break_length"), Value::from(pomodoro.break_length.num_seconds()));
+    object.insert(String::from("tags"), Value::from(pomodoro.tags.clone()));
+    object.insert(String::from("pauses"),
+                  Value::Array(pomodoro.pauses.iter().map(pause_to_json).collect()));
+    object.insert(String::from("cycle_position"), Value::from(pomodoro.cycle_position));
+    object.insert(String::from("extensions"),
+                  Value::Array(pomodoro.extensions.iter().map(extension_to_json).collect()));
+    object.insert(String::from("interruptions"),
+                  Value::Array(pomodoro.interruptions.iter().map(interruption_to_json).collect()));
+    object.insert(String::from("notes"),
+                  pomodoro.notes.clone().map(Value::from).unwrap_or(Value::Null));
+    object.insert(String::from("status"), Value::from(pomodoro.status.to_string()));
+    Value::Object(object)
+}
+
+pub fn from_json(value: &Value) -> Result<Pomodoro> {
+    Ok(Pomodoro {
+        id: try!(integer_field(value, "id")) as i32,
+        work_start_time: try!(try!(time_field(value, "work_start_time"))
+            .ok_or(Error::from(String::from("Pomodoro is missing work_start_time")))),
+        work_end_time: try!(time_field(value, "work_end_time")),
+        break_start_time: try!(time_field(value, "break_start_time")),
+        break_end_time: try!(time_field(value, "break_end_time")),
+        work_length: Duration::seconds(try!(integer_field(value, "work_length"))),
+        break_length: Duration::seconds(try!(integer_field(value, "break_length"))),
+        tags: try!(tags_field(value, "tags")),
+        pauses: try!(pauses_field(value, "pauses")),
+        cycle_position: try!(cycle_position_field(value, "cycle_position")),
+        extensions: try!(extensions_field(value, "extensions")),
+        interruptions: try!(interruptions_field(value, "interruptions")),
+        notes: try!(optional_string_field(value, "notes")),
+        status: PomodoroStatus::from(String::from(try!(string_field(value, "status")))),
+    })
+}
+
+fn time_to_json(time: Option<DateTime<UTC>>) -> Value {
+    time.map(|time| Value::from(time.to_rfc3339())).unwrap_or(Value::Null)
+}
+
+fn pause_to_json(pause: &Pause) -> Value {
+    let mut object = Map::new();
+    object.insert(String::from("start_time"), time_to_json(Some(pause.start_time)));
+    object.insert(String::from("end_time"), time_to_json(pause.end_time));
+    Value::Object(object)
+}
+
+fn extension_to_json(extension: &Extension) -> Value {
+    let mut object = Map::new();
+    object.insert(String::from("time"), time_to_json(Some(extension.time)));
+    object.insert(String::from("period"), Value::from(extension.period.to_string()));
+    object.insert(String::from("length"), Value::from(extension.length.num_seconds()));
+    Value::Object(object)
+}
+
+fn interruption_to_json(interruption: &Interruption) -> Value {
+    let mut object = Map::new();
+    object.insert(String::from("time"), time_to_json(Some(interruption.time)));
+    object.insert(String::from("kind"), Value::from(interruption.kind.to_string()));
+    object.insert(String::from("note"),
+                  interruption.note.clone().map(Value::from).unwrap_or(Value::Null));
+    Value::Object(object)
+}
+
+fn integer_field(value: &Value, field: &str) -> Result<i64> {
+    value.get(field)
+        .and_then(|field| field.as_i64())
+        .ok_or(Error::from(format!("Expected integer field {} in {}", field, value)))
+}
+
+fn string_field<'a>(value: &'a Value, field: &str) -> Result<&'a str> {
+    value.get(field)
+        .and_then(|field| field.as_str())
+        .ok_or(Error::from(format!("Expected string field {} in {}", field, value)))
+}
+
+/// Reads a list of tags. Journals written before tags were stored as a list hold a
+/// comma-separated string instead, which is split into its tags.
+fn tags_field(value: &Value, field: &str) -> Result<Vec<String>> {
+    match value.get(field) {
+        None => Ok(vec![]),
+        Some(&Value::String(ref tags_csv)) => {
+            Ok(tags_csv.split(",").filter(|tag| !tag.is_empty()).map(String::from).collect())
+        }
+        Some(&Value::Array(ref tags)) => {
+            tags.iter()
+                .map(|tag| {
+                    tag.as_str()
+                        .map(String::from)
+                        .ok_or(Error::from(format!("Expected string tag in field {}, got {}", field, tag)))
+                })
+                .collect()
+        }
+        Some(other) => Err(Error::from(format!("Expected tags in field {}, got {}", field, other))),
+    }
+}
+
+/// Reads a list of pauses. Journals written before pomodoros could be paused have none.
+fn pauses_field(value: &Value, field: &str) -> Result<Vec<Pause>> {
+    match value.get(field) {
+        None => Ok(vec![]),
+        Some(&Value::Array(ref pauses)) => {
+            pauses.iter()
+                .map(|pause| -> Result<Pause> {
+                    Ok(Pause {
+                        start_time: try!(try!(time_field(pause, "start_time"))
+                            .ok_or(Error::from(format!("Pause is missing start_time: {}", pause)))),
+                        end_time: try!(time_field(pause, "end_time")),
+                    })
+                })
+                .collect()
+        }
+        Some(other) => Err(Error::from(format!("Expected pauses in field {}, got {}", field, other))),
+    }
+}
+
+/// Reads a list of extensions. Journals written before pomodoros could be extended have none.
+fn extensions_field(value: &Value, field: &str) -> Result<Vec<Extension>> {
+    match value.get(field) {
+        None => Ok(vec![]),
+        Some(&Value::Array(ref extensions)) => {
+            extensions.iter()
+                .map(|extension| -> Result<Extension> {
+                    Ok(Extension {
+                        time: try!(try!(time_field(extension, "time"))
+                            .ok_or(Error::from(format!("Extension is missing time: {}", extension)))),
+                        period: Period::from(String::from(try!(string_field(extension, "period")))),
+                        length: Duration::seconds(try!(integer_field(extension, "length"))),
+                    })
+                })
+                .collect()
+        }
+        Some(other) => Err(Error::from(format!("Expected extensions in field {}, got {}", field, other))),
+    }
+}
+
+/// Reads a list of interruptions. Journals written before interruptions were tracked have none.
+fn interruptions_field(value: &Value, field: &str) -> Result<Vec<Interruption>> {
+    match value.get(field) {
+        None => Ok(vec![]),
+        Some(&Value::Array(ref interruptions)) => {
+            interruptions.iter()
+                .map(|interruption| -> Result<Interruption> {
+                    Ok(Interruption {
+                        time: try!(try!(time_field(interruption, "time"))
+                            .ok_or(Error::from(format!("Interruption is missing time: {}", interruption)))),
+                        kind: InterruptionKind::from(String::from(try!(string_field(interruption, "kind")))),
+                        note: interruption.get("note").and_then(|note| note.as_str()).map(String::from),
+                    })
+                })
+                .collect()
+        }
+        Some(other) => Err(Error::from(format!("Expected interruptions in field {}, got {}", field, other))),
+    }
+}
+
+/// Reads a position in a cycle. Journals written before cycles were tracked have none, so each of
+/// their pomodoros starts a cycle of its own.
+fn cycle_position_field(value: &Value, field: &str) -> Result<i32> {
+    match value.get(field) {
+        None => Ok(1),
+        Some(position) => {
+            position.as_i64()
+                .map(|position| position as i32)
+                .ok_or(Error::from(format!("Expected cycle position in field {}, got {}", field, position)))
+        }
+    }
+}
+
+fn optional_string_field(value: &Value, field: &str) -> Result<Option<String>> {
+    match value.get(field) {
+        None => Ok(None),
+        Some(&Value::Null) => Ok(None),
+        Some(&Value::String(ref string)) => Ok(Some(string.clone())),
+        Some(other) => Err(Error::from(format!("Expected string in field {}, got {}", field, other))),
+    }
+}
+
+fn time_field(value: &Value, field: &str) -> Result<Option<DateTime<UTC>>> {
+    match value.get(field) {
+        None => Ok(None),
+        Some(&Value::Null) => Ok(None),
+        Some(&Value::String(ref time)) => {
+            time.parse::<DateTime<UTC>>()
+                .map(Some)
+                .map_err(|e| Error::from(format!("Invalid time in field {}: {}", field, e)))
+        }
+        Some(other) => Err(Error::from(format!("Expected time in field {}, got {}", field, other))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trips_a_pomodoro() {
+        let pomodoro = Pomodoro {
+            id: 7,
+            work_start_time: "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap(),
+            work_end_time: Some("2000-01-01T00:00:05+00:00".parse::<DateTime<UTC>>().unwrap()),
+            break_start_time: None,
+            break_end_time: None,
+            work_length: Duration::seconds(5),
+            break_length: Duration::seconds(5),
+            tags: vec![String::from("foo"), String::from("bar")],
+            pauses: vec![Pause {
+                             start_time: "2000-01-01T00:00:01+00:00".parse::<DateTime<UTC>>().unwrap(),
+                             end_time: Some("2000-01-01T00:00:02+00:00".parse::<DateTime<UTC>>().unwrap()),
+                         }],
+            cycle_position: 3,
+            extensions: vec![Extension {
+                                 time: "2000-01-01T00:00:04+00:00".parse::<DateTime<UTC>>().unwrap(),
+                                 period: Period::Work,
+                                 length: Duration::seconds(60),
+                             }],
+            interruptions: vec![Interruption {
+                                    time: "2000-01-01T00:00:03+00:00".parse::<DateTime<UTC>>().unwrap(),
+                                    kind: InterruptionKind::External,
+                                    note: Some(String::from("phone")),
+                                }],
+            notes: Some(String::from("wrote the parser\nfixed a test")),
+            status: PomodoroStatus::BreakPending,
+        };
+
+        assert!(from_json(&to_json(&pomodoro)).unwrap() == pomodoro);
+    }
+
+    #[test]
+    fn reads_tags_stored_as_a_comma_separated_string() {
+        let mut json = to_json(&Pomodoro {
+            id: 7,
+            work_start_time: "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap(),
+            work_end_time: None,
+            break_start_time: None,
+            break_end_time: None,
+            work_length: Duration::seconds(5),
+            break_length: Duration::seconds(5),
+            tags: vec![],
+            pauses: vec![],
+            cycle_position: 1,
+            extensions: vec![],
+            interruptions: vec![],
+            notes: None,
+            status: PomodoroStatus::InProgress,
+        });
+        json.as_object_mut().unwrap().insert(String::from("tags"), Value::from("foo,bar"));
+
+        let pomodoro = from_json(&json).unwrap();
+
+        assert!(pomodoro.tags == vec![String::from("foo"), String::from("bar")]);
+    }
+}
@@ -3,64 +3,101 @@ use daemon::chrono::Duration;
 use daemon::chrono::datetime::DateTime;
 use daemon::chrono::offset::utc::UTC;
 
+use daemon::config::DatabaseConfig;
+use daemon::config::SslMode;
+use daemon::connection_pool::{ConnectionPool, PooledConnection};
+use daemon::migrations;
+use daemon::migrations::Migration;
+use daemon::pomodoro::Extension;
+use daemon::pomodoro::Interruption;
+use daemon::pomodoro::InterruptionKind;
+use daemon::pomodoro::Pause;
+use daemon::pomodoro::Period;
 use daemon::pomodoro::Pomodoro;
-use daemon::pomodoros::Pomodoros;
+use daemon::pomodoros::{PomodoroQuery, Pomodoros};
 use daemon::pomodoro::PomodoroStatus;
 
+use daemon::openssl::ssl::{SslContext, SslMethod};
 use daemon::postgres;
+use daemon::postgres::IntoConnectParams;
+use daemon::postgres::types::ToSql;
 
 use daemon::result::Error;
 use daemon::result::Result;
 
-use std::iter::FromIterator;
+use std::collections::HashMap;
 use std::option::Option;
 use std::error::Error as StdError;
+use std::time;
 
-pub struct PomodoroQueryMapper {}
+pub struct PomodoroQueryMapper {
+    pool: ConnectionPool<postgres::Connection>,
+}
+
+/// The view of the database given to the work of a transaction.
+struct PostgresTransaction<'a> {
+    conn: &'a postgres::GenericConnection,
+}
 
 impl PomodoroQueryMapper {
-    pub fn new() -> PomodoroQueryMapper {
-        PomodoroQueryMapper {}
+    /// Builds a query mapper for the configured database and connects once, so that bad
+    /// connection settings are reported immediately rather than on the first command.
+    /// Connections are kept open in a pool of at most `pool_size` connections.
+    pub fn new(config: &DatabaseConfig) -> Result<PomodoroQueryMapper> {
+        let mut connect_params = try!(config.url
+            .as_str()
+            .into_connect_params()
+            .map_err(|e| Error::ConfigError(format!("Invalid database_url {}: {}", config.url, e))));
+
+        if config.user.is_some() || config.password.is_some() {
+            let url_user = connect_params.user.take();
+            connect_params.user = Some(postgres::UserInfo {
+                user: config.user
+                    .clone()
+                    .or(url_user.as_ref().map(|info| info.user.clone()))
+                    .unwrap_or(String::from("postgres")),
+                password: config.password
+                    .clone()
+                    .or(url_user.and_then(|info| info.password)),
+            });
+        }
+
+        let ssl_context = match config.ssl_mode {
+            SslMode::Disable => None,
+            SslMode::Prefer | SslMode::Require => {
+                Some(try!(SslContext::new(SslMethod::Sslv23)
+                    .map_err(|e| Error::ConfigError(format!("Could not set up SSL: {}", e)))))
+            }
+        };
+
+        let ssl_mode = config.ssl_mode.clone();
+        let schema = config.schema.clone();
+        let pool = ConnectionPool::new(config.pool_size,
+                                       time::Duration::from_secs(5),
+                                       Box::new(move || {
+                                           PomodoroQueryMapper::connect(&connect_params,
+                                                                        &ssl_mode,
+                                                                        &ssl_context,
+                                                                        &schema)
+                                       }),
+                                       Box::new(|conn: &postgres::Connection| {
+                                           conn.batch_execute("SELECT 1").is_ok()
+                                       }));
+
+        let mapper = PomodoroQueryMapper { pool: pool };
+        try!(mapper.acquire_connection());
+
+        Ok(mapper)
     }
 
     pub fn create_pomodoro(&self,
                            start_time: DateTime<UTC>,
                            work_duration: Duration,
-                           break_duration: Duration)
+                           break_duration: Duration,
+                           tags: Vec<String>)
                            -> Result<()> {
         let conn = try!(self.acquire_connection());
-        let work_length = work_duration.num_seconds();
-        let break_length = break_duration.num_seconds();
-        let result = conn.execute("INSERT INTO pomodoros(
-                work_start_time,
-                \
-                                   work_end_time,
-                break_start_time,
-                \
-                                   break_end_time,
-                work_length,
-                \
-                                   break_length,
-                tags,
-                status
-            \
-                                   ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
-                                  &[&start_time,
-                                    &None as &Option<DateTime<UTC>>,
-                                    &None as &Option<DateTime<UTC>>,
-                                    &None as &Option<DateTime<UTC>>,
-                                    &work_length as &i64,
-                                    &break_length as &i64,
-                                    &String::from(""),
-                                    &PomodoroStatus::InProgress.to_string()]);
-
-        match result {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                error!("{}", e.description());
-                Err(Error::from(e))
-            }
-        }
+        insert_pomodoro(&*conn, start_time, work_duration, break_duration, tags)
     }
 
     pub fn get_most_recent_pomodoro(&self) -> Result<Option<Pomodoro>> {
@@ -73,87 +110,84 @@ impl PomodoroQueryMapper {
                 }
             },
             Err(e) => {
-                //error!("{}", e.description());
-                Err(Error::from(e))
+                error!("{}", e);
+                Err(e)
             }
         }
     }
 
     pub fn list_most_recent_pomodoros(&self, limit: usize) -> Result<Vec<Pomodoro>> {
+        self.query_pomodoros(&PomodoroQuery::most_recent(limit))
+    }
+
+    pub fn query_pomodoros(&self, query: &PomodoroQuery) -> Result<Vec<Pomodoro>> {
         let conn = try!(self.acquire_connection());
+        select_pomodoros(&*conn, query)
+    }
 
-        let most_recent_results: postgres::rows::Rows = try!((&conn)
-            .query("SELECT id, work_start_time, work_end_time, break_start_time, \
-                    break_end_time, work_length, break_length, status, tags FROM pomodoros \
-                    ORDER BY work_start_time DESC",
-                   &[])
-            .or_else(|err| {
-                error!("{}", err.description());
-                Err(err)
-            }));
-
-        Ok(Vec::from_iter(most_recent_results.iter().take(limit).map(|pomodoro| {
-            let id: i32 = pomodoro.get(0);
-            let work_start_time: DateTime<UTC> = pomodoro.get(1);
-            let work_end_time: Option<DateTime<UTC>> = pomodoro.get(2);
-            let break_start_time: Option<DateTime<UTC>> = pomodoro.get(3);
-            let break_end_time: Option<DateTime<UTC>> = pomodoro.get(4);
-            let work_length: i64 = pomodoro.get(5);
-            let break_length: i64 = pomodoro.get(6);
-            let status: String = pomodoro.get(7);
-            let tags: String = pomodoro.get(8);
-
-            Pomodoro {
-                id: id,
-                work_start_time: work_start_time,
-                work_end_time: work_end_time,
-                break_start_time: break_start_time,
-                break_end_time: break_end_time,
-                work_length: Duration::seconds(work_length),
-                break_length: Duration::seconds(break_length),
-                status: PomodoroStatus::from(status),
-                tags: tags,
-            }
-        })))
+    pub fn find_pomodoro(&self, id: i32) -> Result<Option<Pomodoro>> {
+        let conn = try!(self.acquire_connection());
+        select_pomodoro(&*conn, id)
     }
 
+    /// Updates the pomodoro along with its pauses in one transaction.
     pub fn update_pomodoro(&self, id: i32, pomodoro: Pomodoro) -> Result<()> {
+        self.transaction(&mut |pomodoros: &Pomodoros| pomodoros.update(id, pomodoro.clone()))
+    }
+
+    /// Inserts a complete pomodoro along with its pauses in one transaction.
+    pub fn insert_pomodoro(&self, pomodoro: Pomodoro) -> Result<i32> {
+        let mut id = 0;
+        try!(self.transaction(&mut |pomodoros: &Pomodoros| {
+            id = try!(pomodoros.insert(pomodoro.clone()));
+            Ok(())
+        }));
+        Ok(id)
+    }
+
+    /// Deletes the pomodoro, whose pauses and other details go with it through ON DELETE CASCADE.
+    pub fn delete_pomodoro(&self, id: i32) -> Result<()> {
+        let conn = try!(self.acquire_connection());
+        delete_pomodoro_row(&*conn, id)
+    }
+
+    /// Restores the pomodoro along with its pauses in one transaction.
+    pub fn restore_pomodoro(&self, pomodoro: Pomodoro) -> Result<()> {
+        self.transaction(&mut |pomodoros: &Pomodoros| pomodoros.restore(pomodoro.clone()))
+    }
+
+    pub fn pending_migrations(&self) -> Result<Vec<&'static Migration>> {
         let conn = try!(self.acquire_connection());
+        migrations::pending(&conn)
+    }
 
-        (&conn)
-            .execute("UPDATE pomodoros SET
-                    work_start_time = $2,
-                    \
-                      work_end_time = $3,
-                    break_start_time = $4,
-                    \
-                      break_end_time = $5,
-                    work_length = $6,
-                    \
-                      break_length = $7,
-                    tags = $8,
-                    \
-                      status = $9
-                WHERE id = $1",
-                     &[&id,
-                       &pomodoro.work_start_time,
-                       &pomodoro.work_end_time,
-                       &pomodoro.break_start_time,
-                       &pomodoro.break_end_time,
-                       &pomodoro.work_length.num_seconds() as &i64,
-                       &pomodoro.break_length.num_seconds() as &i64,
-                       &String::from(""),
-                       &pomodoro.status.to_string()])
-            .or_else(|err| {
-                error!("{}", err.description());
-                Err(Error::from(err))
-            })
-            .map(|_| ())
-    }
-
-    fn acquire_connection(&self) -> Result<postgres::Connection> {
-        postgres::Connection::connect("postgres://postgres@localhost:5432/solanum_test", postgres::SslMode::None)
-            .map_err(|e| Error::from(e))
+    pub fn migrate(&self) -> Result<Vec<&'static Migration>> {
+        let conn = try!(self.acquire_connection());
+        migrations::migrate(&conn)
+    }
+
+    fn acquire_connection(&self) -> Result<PooledConnection<postgres::Connection>> {
+        self.pool.get()
+    }
+
+    fn connect(connect_params: &postgres::ConnectParams,
+               ssl_mode: &SslMode,
+               ssl_context: &Option<SslContext>,
+               schema: &Option<String>)
+               -> Result<postgres::Connection> {
+        let ssl_mode = match (ssl_mode, ssl_context) {
+            (&SslMode::Require, &Some(ref context)) => postgres::SslMode::Require(context),
+            (&SslMode::Prefer, &Some(ref context)) => postgres::SslMode::Prefer(context),
+            _ => postgres::SslMode::None,
+        };
+
+        let conn = try!(postgres::Connection::connect(connect_params.clone(), ssl_mode)
+            .map_err(|e| Error::from(e)));
+
+        if let Some(ref schema) = *schema {
+            try!(conn.batch_execute(&format!("SET search_path TO \"{}\"", schema)));
+        }
+        Ok(conn)
     }
 }
 
@@ -161,20 +195,425 @@ impl Pomodoros for PomodoroQueryMapper {
     fn create(&self,
               start_time: DateTime<UTC>,
               work_duration: Duration,
-              break_duration: Duration)
+              break_duration: Duration,
+              tags: Vec<String>)
               -> Result<()> {
-        self.create_pomodoro(start_time, work_duration, break_duration)
+        self.create_pomodoro(start_time, work_duration, break_duration, tags)
     }
 
     fn last(&self, count: usize) -> Result<Vec<Pomodoro>> {
         self.list_most_recent_pomodoros(count)
     }
 
-    fn most_recent(&self) -> Option<Pomodoro> {
-        self.get_most_recent_pomodoro().ok().and_then(|pomodoro| pomodoro)
+    fn query(&self, query: PomodoroQuery) -> Result<Vec<Pomodoro>> {
+        self.query_pomodoros(&query)
+    }
+
+    fn most_recent(&self) -> Result<Option<Pomodoro>> {
+        self.get_most_recent_pomodoro()
+    }
+
+    fn find(&self, id: i32) -> Result<Option<Pomodoro>> {
+        self.find_pomodoro(id)
     }
 
     fn update(&self, id: i32, pomodoro: Pomodoro) -> Result<()> {
         self.update_pomodoro(id, pomodoro)
     }
+
+    fn insert(&self, pomodoro: Pomodoro) -> Result<i32> {
+        self.insert_pomodoro(pomodoro)
+    }
+
+    fn delete(&self, id: i32) -> Result<()> {
+        self.delete_pomodoro(id)
+    }
+
+    fn restore(&self, pomodoro: Pomodoro) -> Result<()> {
+        self.restore_pomodoro(pomodoro)
+    }
+
+    /// Runs `work` in a database transaction on one pooled connection. The pomodoros table is
+    /// locked against other writers for the duration, so concurrent transactions cannot both
+    /// act on the same most recent pomodoro.
+    fn transaction(&self, work: &mut FnMut(&Pomodoros) -> Result<()>) -> Result<()> {
+        let conn = try!(self.acquire_connection());
+        let transaction = try!(conn.transaction());
+        try!(transaction.batch_execute("LOCK TABLE pomodoros IN SHARE ROW EXCLUSIVE MODE"));
+
+        try!(work(&PostgresTransaction { conn: &transaction }));
+        transaction.commit().map_err(|e| Error::from(e))
+    }
+}
+
+impl<'a> Pomodoros for PostgresTransaction<'a> {
+    fn create(&self,
+              start_time: DateTime<UTC>,
+              work_duration: Duration,
+              break_duration: Duration,
+              tags: Vec<String>)
+              -> Result<()> {
+        insert_pomodoro(self.conn, start_time, work_duration, break_duration, tags)
+    }
+
+    fn last(&self, count: usize) -> Result<Vec<Pomodoro>> {
+        select_pomodoros(self.conn, &PomodoroQuery::most_recent(count))
+    }
+
+    fn query(&self, query: PomodoroQuery) -> Result<Vec<Pomodoro>> {
+        select_pomodoros(self.conn, &query)
+    }
+
+    fn most_recent(&self) -> Result<Option<Pomodoro>> {
+        self.last(1).map(|mut pomodoros| pomodoros.pop())
+    }
+
+    fn find(&self, id: i32) -> Result<Option<Pomodoro>> {
+        select_pomodoro(self.conn, id)
+    }
+
+    fn update(&self, id: i32, pomodoro: Pomodoro) -> Result<()> {
+        update_pomodoro_row(self.conn, id, pomodoro)
+    }
+
+    fn insert(&self, pomodoro: Pomodoro) -> Result<i32> {
+        insert_pomodoro_record(self.conn, pomodoro)
+    }
+
+    fn delete(&self, id: i32) -> Result<()> {
+        delete_pomodoro_row(self.conn, id)
+    }
+
+    fn restore(&self, pomodoro: Pomodoro) -> Result<()> {
+        restore_pomodoro_row(self.conn, pomodoro)
+    }
+
+    fn transaction(&self, work: &mut FnMut(&Pomodoros) -> Result<()>) -> Result<()> {
+        work(self)
+    }
+}
+
+fn insert_pomodoro(conn: &postgres::GenericConnection,
+                   start_time: DateTime<UTC>,
+                   work_duration: Duration,
+                   break_duration: Duration,
+                   tags: Vec<String>)
+                   -> Result<()> {
+    let work_length = work_duration.num_seconds();
+    let break_length = break_duration.num_seconds();
+    let result = conn.execute("INSERT INTO pomodoros(
+            work_start_time,
+            \
+                               work_end_time,
+            break_start_time,
+            \
+                               break_end_time,
+            work_length,
+            \
+                               break_length,
+            tags,
+            status
+        \
+                               ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                              &[&start_time,
+                                &None as &Option<DateTime<UTC>>,
+                                &None as &Option<DateTime<UTC>>,
+                                &None as &Option<DateTime<UTC>>,
+                                &work_length as &i64,
+                                &break_length as &i64,
+                                &tags,
+                                &PomodoroStatus::InProgress.to_string()]);
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            error!("{}", e.description());
+            Err(Error::from(e))
+        }
+    }
+}
+
+/// Inserts a complete record by creating a row for it and then filling the row in.
+fn insert_pomodoro_record(conn: &postgres::GenericConnection, pomodoro: Pomodoro) -> Result<i32> {
+    let rows = try!(conn.query("INSERT INTO pomodoros(work_start_time, work_length, break_length, status) \
+                                VALUES ($1, $2, $3, $4) RETURNING id",
+                               &[&pomodoro.work_start_time,
+                                 &pomodoro.work_length.num_seconds(),
+                                 &pomodoro.break_length.num_seconds(),
+                                 &pomodoro.status.to_string()]));
+    let id: i32 = try!(rows.iter()
+        .next()
+        .map(|row| row.get(0))
+        .ok_or(Error::from(String::from("Inserting a pomodoro did not return its id"))));
+
+    try!(update_pomodoro_row(conn, id, pomodoro));
+    Ok(id)
+}
+
+/// Runs the query entirely in the database. Each combination of filters has its own cached
+/// prepared statement, so the index on (work_start_time, id) serves both the range and the
+/// ordering and only the requested page of rows is read.
+fn select_pomodoros(conn: &postgres::GenericConnection, query: &PomodoroQuery) -> Result<Vec<Pomodoro>> {
+    let limit = query.limit as i64;
+    let offset = query.offset as i64;
+    let mut params: Vec<&ToSql> = vec![&limit as &ToSql, &offset];
+    let mut conditions: Vec<String> = Vec::new();
+
+    if let Some(ref since) = query.since {
+        params.push(since);
+        conditions.push(format!("work_start_time >= ${}", params.len()));
+    }
+    if let Some(ref until) = query.until {
+        params.push(until);
+        conditions.push(format!("work_start_time < ${}", params.len()));
+    }
+
+    select_where(conn, conditions, params)
+}
+
+fn select_pomodoro(conn: &postgres::GenericConnection, id: i32) -> Result<Option<Pomodoro>> {
+    let limit = 1 as i64;
+    let offset = 0 as i64;
+
+    select_where(conn, vec![String::from("id = $3")], vec![&limit as &ToSql, &offset, &id])
+        .map(|mut pomodoros| pomodoros.pop())
+}
+
+/// Selects the pomodoros meeting every condition, along with their pauses, extensions and
+/// interruptions. The first two parameters are the limit and the offset. The details of the
+/// whole page are read with one query per table, however many pomodoros are on it.
+fn select_where(conn: &postgres::GenericConnection,
+                conditions: Vec<String>,
+                params: Vec<&ToSql>)
+                -> Result<Vec<Pomodoro>> {
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+    let sql = format!("SELECT id, work_start_time, work_end_time, break_start_time, \
+                       break_end_time, work_length, break_length, status, tags, cycle_position, notes \
+                       FROM pomodoros {} ORDER BY work_start_time DESC, id DESC LIMIT $1 OFFSET $2",
+                      where_clause);
+
+    let statement = try!(conn.prepare_cached(&sql));
+    let rows: postgres::rows::Rows = try!(statement.query(&params)
+        .or_else(|err| {
+            error!("{}", err.description());
+            Err(err)
+        }));
+
+    let mut pomodoros: Vec<Pomodoro> = rows.iter().map(pomodoro_from_row).collect();
+    if pomodoros.is_empty() {
+        return Ok(pomodoros);
+    }
+
+    let ids: Vec<i32> = pomodoros.iter().map(|pomodoro| pomodoro.id).collect();
+    let mut pauses = try!(load_pauses(conn, &ids));
+    let mut extensions = try!(load_extensions(conn, &ids));
+    let mut interruptions = try!(load_interruptions(conn, &ids));
+    for pomodoro in pomodoros.iter_mut() {
+        pomodoro.pauses = pauses.remove(&pomodoro.id).unwrap_or(vec![]);
+        pomodoro.extensions = extensions.remove(&pomodoro.id).unwrap_or(vec![]);
+        pomodoro.interruptions = interruptions.remove(&pomodoro.id).unwrap_or(vec![]);
+    }
+    Ok(pomodoros)
+}
+
+fn update_pomodoro_row(conn: &postgres::GenericConnection, id: i32, pomodoro: Pomodoro) -> Result<()> {
+    let updated = try!(conn.execute("UPDATE pomodoros SET
+                work_start_time = $2,
+                \
+                  work_end_time = $3,
+                break_start_time = $4,
+                \
+                  break_end_time = $5,
+                work_length = $6,
+                \
+                  break_length = $7,
+                tags = $8,
+                \
+                  status = $9,
+                cycle_position = $10,
+                notes = $11
+            WHERE id = $1",
+                 &[&id,
+                   &pomodoro.work_start_time,
+                   &pomodoro.work_end_time,
+                   &pomodoro.break_start_time,
+                   &pomodoro.break_end_time,
+                   &pomodoro.work_length.num_seconds() as &i64,
+                   &pomodoro.break_length.num_seconds() as &i64,
+                   &pomodoro.tags,
+                   &pomodoro.status.to_string(),
+                   &pomodoro.cycle_position,
+                   &pomodoro.notes])
+        .or_else(|err| {
+            error!("{}", err.description());
+            Err(Error::from(err))
+        }));
+
+    if updated == 0 {
+        return Ok(());
+    }
+    try!(replace_pauses(conn, id, &pomodoro.pauses));
+    try!(replace_extensions(conn, id, &pomodoro.extensions));
+    replace_interruptions(conn, id, &pomodoro.interruptions)
+}
+
+/// Recreates the row of the pomodoro if it has been deleted, then fills the row in. Its id was
+/// drawn from the sequence when it was first created, so the sequence needs no adjusting.
+fn restore_pomodoro_row(conn: &postgres::GenericConnection, pomodoro: Pomodoro) -> Result<()> {
+    try!(conn.execute("INSERT INTO pomodoros(id, work_start_time, work_length, break_length, status) \
+                       VALUES ($1, $2, $3, $4, $5) ON CONFLICT (id) DO NOTHING",
+                      &[&pomodoro.id,
+                        &pomodoro.work_start_time,
+                        &pomodoro.work_length.num_seconds(),
+                        &pomodoro.break_length.num_seconds(),
+                        &pomodoro.status.to_string()]));
+    let id = pomodoro.id;
+    update_pomodoro_row(conn, id, pomodoro)
+}
+
+fn delete_pomodoro_row(conn: &postgres::GenericConnection, id: i32) -> Result<()> {
+    try!(conn.execute("DELETE FROM pomodoros WHERE id = $1", &[&id]));
+    Ok(())
+}
+
+/// Loads the pauses of each of the pomodoros, keyed by the id of the pomodoro they belong to.
+fn load_pauses(conn: &postgres::GenericConnection, ids: &Vec<i32>) -> Result<HashMap<i32, Vec<Pause>>> {
+    let statement = try!(conn.prepare_cached("SELECT pomodoro_id, start_time, end_time FROM \
+                                              pomodoro_pauses WHERE pomodoro_id = ANY($1) \
+                                              ORDER BY start_time"));
+    let rows = try!(statement.query(&[ids]));
+
+    let mut pauses: HashMap<i32, Vec<Pause>> = HashMap::new();
+    for row in rows.iter() {
+        pauses.entry(row.get(0)).or_insert(vec![]).push(Pause {
+            start_time: row.get(1),
+            end_time: row.get(2),
+        });
+    }
+    Ok(pauses)
+}
+
+fn replace_pauses(conn: &postgres::GenericConnection,
+                  pomodoro_id: i32,
+                  pauses: &Vec<Pause>)
+                  -> Result<()> {
+    try!(conn.execute("DELETE FROM pomodoro_pauses WHERE pomodoro_id = $1", &[&pomodoro_id]));
+    for pause in pauses.iter() {
+        try!(conn.execute("INSERT INTO pomodoro_pauses(pomodoro_id, start_time, end_time) \
+                           VALUES ($1, $2, $3)",
+                          &[&pomodoro_id, &pause.start_time, &pause.end_time]));
+    }
+    Ok(())
+}
+
+/// Loads the extensions of each of the pomodoros, keyed by the id of the pomodoro they belong
+/// to.
+fn load_extensions(conn: &postgres::GenericConnection,
+                   ids: &Vec<i32>)
+                   -> Result<HashMap<i32, Vec<Extension>>> {
+    let statement = try!(conn.prepare_cached("SELECT pomodoro_id, time, period, length FROM \
+                                              pomodoro_extensions WHERE pomodoro_id = ANY($1) \
+                                              ORDER BY time"));
+    let rows = try!(statement.query(&[ids]));
+
+    let mut extensions: HashMap<i32, Vec<Extension>> = HashMap::new();
+    for row in rows.iter() {
+        let period: String = row.get(2);
+        let length: i64 = row.get(3);
+        extensions.entry(row.get(0)).or_insert(vec![]).push(Extension {
+            time: row.get(1),
+            period: Period::from(period),
+            length: Duration::seconds(length),
+        });
+    }
+    Ok(extensions)
+}
+
+fn replace_extensions(conn: &postgres::GenericConnection,
+                      pomodoro_id: i32,
+                      extensions: &Vec<Extension>)
+                      -> Result<()> {
+    try!(conn.execute("DELETE FROM pomodoro_extensions WHERE pomodoro_id = $1", &[&pomodoro_id]));
+    for extension in extensions.iter() {
+        try!(conn.execute("INSERT INTO pomodoro_extensions(pomodoro_id, time, period, length) \
+                           VALUES ($1, $2, $3, $4)",
+                          &[&pomodoro_id,
+                            &extension.time,
+                            &extension.period.to_string(),
+                            &extension.length.num_seconds()]));
+    }
+    Ok(())
+}
+
+/// Loads the interruptions of each of the pomodoros, keyed by the id of the pomodoro they
+/// belong to.
+fn load_interruptions(conn: &postgres::GenericConnection,
+                      ids: &Vec<i32>)
+                      -> Result<HashMap<i32, Vec<Interruption>>> {
+    let statement = try!(conn.prepare_cached("SELECT pomodoro_id, time, kind, note FROM \
+                                              pomodoro_interruptions WHERE pomodoro_id = ANY($1) \
+                                              ORDER BY time"));
+    let rows = try!(statement.query(&[ids]));
+
+    let mut interruptions: HashMap<i32, Vec<Interruption>> = HashMap::new();
+    for row in rows.iter() {
+        let kind: String = row.get(2);
+        interruptions.entry(row.get(0)).or_insert(vec![]).push(Interruption {
+            time: row.get(1),
+            kind: InterruptionKind::from(kind),
+            note: row.get(3),
+        });
+    }
+    Ok(interruptions)
+}
+
+fn replace_interruptions(conn: &postgres::GenericConnection,
+                         pomodoro_id: i32,
+                         interruptions: &Vec<Interruption>)
+                         -> Result<()> {
+    try!(conn.execute("DELETE FROM pomodoro_interruptions WHERE pomodoro_id = $1", &[&pomodoro_id]));
+    for interruption in interruptions.iter() {
+        try!(conn.execute("INSERT INTO pomodoro_interruptions(pomodoro_id, time, kind, note) \
+                           VALUES ($1, $2, $3, $4)",
+                          &[&pomodoro_id,
+                            &interruption.time,
+                            &interruption.kind.to_string(),
+                            &interruption.note]));
+    }
+    Ok(())
+}
+
+fn pomodoro_from_row(row: postgres::rows::Row) -> Pomodoro {
+    let id: i32 = row.get(0);
+    let work_start_time: DateTime<UTC> = row.get(1);
+    let work_end_time: Option<DateTime<UTC>> = row.get(2);
+    let break_start_time: Option<DateTime<UTC>> = row.get(3);
+    let break_end_time: Option<DateTime<UTC>> = row.get(4);
+    let work_length: i64 = row.get(5);
+    let break_length: i64 = row.get(6);
+    let status: String = row.get(7);
+    let tags: Vec<String> = row.get(8);
+    let cycle_position: i32 = row.get(9);
+    let notes: Option<String> = row.get(10);
+
+    Pomodoro {
+        id: id,
+        work_start_time: work_start_time,
+        work_end_time: work_end_time,
+        break_start_time: break_start_time,
+        break_end_time: break_end_time,
+        work_length: Duration::seconds(work_length),
+        break_length: Duration::seconds(break_length),
+        status: PomodoroStatus::from(status),
+        tags: tags,
+        pauses: vec![],
+        cycle_position: cycle_position,
+        extensions: vec![],
+        interruptions: vec![],
+        notes: notes,
+    }
 }
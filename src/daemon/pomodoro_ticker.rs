@@ -0,0 +1,137 @@
+use daemon::chrono::Duration;
+
+use daemon::clock::Clock;
+use daemon::PomodoroTransitioner;
+use daemon::pomodoro_event::{EventPublisher, PomodoroEvent};
+use daemon::pomodoros::Pomodoros;
+use daemon::result::Result;
+
+/// Moves the current pomodoro on when its work period or break runs out, so that what is stored
+/// does not wait on the next command to catch up.
+pub struct PomodoroTicker<C: Clock, P: Pomodoros> {
+    clock: C,
+    pomodoros: P,
+    events: EventPublisher,
+}
+
+impl<C: Clock, P: Pomodoros> PomodoroTicker<C, P> {
+    pub fn new(clock: C, pomodoros: P) -> PomodoroTicker<C, P> {
+        PomodoroTicker::with_events(clock, pomodoros, EventPublisher::none())
+    }
+
+    /// Publishes each pomodoro it moves on to `events`.
+    pub fn with_events(clock: C, pomodoros: P, events: EventPublisher) -> PomodoroTicker<C, P> {
+        PomodoroTicker {
+            clock: clock,
+            pomodoros: pomodoros,
+            events: events,
+        }
+    }
+
+    /// Expires the current pomodoro if it is due, and returns how long until it is next due, if
+    /// ever.
+    pub fn tick(&self) -> Result<Option<Duration>> {
+        let now = self.clock.current_time();
+        let mut current = try!(self.pomodoros.most_recent());
+        let mut event = None;
+
+        if current.as_ref().and_then(|pomodoro| PomodoroTransitioner::expire(now, pomodoro)).is_some() {
+            // Expire it again within a transaction, so that a command handled in the meantime
+            // is not overwritten.
+            try!(self.pomodoros.transaction(&mut |pomodoros: &Pomodoros| {
+                current = try!(pomodoros.most_recent());
+                if let Some(expired) = current.as_ref()
+                    .and_then(|pomodoro| PomodoroTransitioner::expire(now, pomodoro)) {
+                    try!(pomodoros.update(expired.id, expired.clone()));
+                    event = PomodoroEvent::between(current.as_ref(), &expired);
+                    current = Some(expired);
+                }
+                Ok(())
+            }));
+        }
+        self.events.publish(event.into_iter().collect());
+
+        Ok(current.and_then(|pomodoro| pomodoro.next_deadline(now)).map(|deadline| deadline - now))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use daemon::chrono::datetime::DateTime;
+    use daemon::chrono::offset::utc::UTC;
+
+    use daemon::InMemoryPomodoros;
+    use daemon::io::mio::channel;
+    use daemon::pomodoro::PomodoroStatus;
+    use daemon::pomodoro_event::PomodoroEventKind;
+
+    struct ClockStub {
+        fake_time: DateTime<UTC>,
+    }
+
+    impl Clock for ClockStub {
+        fn current_time(&self) -> DateTime<UTC> {
+            self.fake_time
+        }
+    }
+
+    #[test]
+    fn waits_for_the_work_deadline() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(60), Duration::seconds(30), vec![]).unwrap();
+        let ticker = PomodoroTicker::new(ClockStub { fake_time: start_time + Duration::seconds(10) },
+                                         &pomodoros);
+
+        assert!(ticker.tick().unwrap() == Some(Duration::seconds(50)));
+        assert!(pomodoros.most_recent().unwrap().unwrap().status == PomodoroStatus::InProgress);
+    }
+
+    #[test]
+    fn ends_the_work_period_once_it_has_run_out() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(60), Duration::seconds(30), vec![]).unwrap();
+        let ticker = PomodoroTicker::new(ClockStub { fake_time: start_time + Duration::seconds(61) },
+                                         &pomodoros);
+
+        assert!(ticker.tick().unwrap() == None);
+
+        let pomodoro = pomodoros.most_recent().unwrap().unwrap();
+        assert!(pomodoro.status == PomodoroStatus::BreakPending);
+        assert!(pomodoro.work_end_time == Some(start_time + Duration::seconds(60)));
+    }
+
+    #[test]
+    fn publishes_the_pomodoros_it_moves_on() {
+        let pomodoros = InMemoryPomodoros::new();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        pomodoros.create(start_time, Duration::seconds(60), Duration::seconds(30), vec![]).unwrap();
+        let (sender, receiver) = channel::channel();
+        let ticker = PomodoroTicker::with_events(ClockStub { fake_time: start_time + Duration::seconds(61) },
+                                                 &pomodoros,
+                                                 EventPublisher::new(sender));
+
+        ticker.tick().unwrap();
+        ticker.tick().unwrap();
+
+        let event = receiver.try_recv().unwrap();
+        assert!(event.kind == PomodoroEventKind::BreakPending);
+        assert!(event.pomodoro == pomodoros.most_recent().unwrap().unwrap());
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn has_nothing_to_wait_for_without_pomodoros() {
+        let ticker = PomodoroTicker::new(ClockStub {
+                                             fake_time: "2000-01-01T00:00:00+00:00"
+                                                 .parse::<DateTime<UTC>>()
+                                                 .unwrap(),
+                                         },
+                                         InMemoryPomodoros::new());
+
+        assert!(ticker.tick().unwrap() == None);
+    }
+}
@@ -1,9 +1,20 @@
 use daemon::chrono::datetime::DateTime;
 use daemon::chrono::offset::utc::UTC;
 
+use daemon::chrono::Duration;
+
+use daemon::pomodoro::Extension;
+use daemon::pomodoro::Interruption;
+use daemon::pomodoro::InterruptionKind;
+use daemon::pomodoro::Pause;
+use daemon::pomodoro::Period;
 use daemon::pomodoro::Pomodoro;
+use daemon::pomodoro::PomodoroEdit;
 use daemon::pomodoro::PomodoroStatus;
 
+use daemon::result::Error;
+use daemon::result::Result;
+
 pub struct PomodoroTransitioner {}
 
 impl PomodoroTransitioner {
@@ -13,14 +24,23 @@ impl PomodoroTransitioner {
         match (*pomodoro).status {
             PomodoroStatus::Aborted => new_pomodoro,
             PomodoroStatus::Completed => new_pomodoro,
+            PomodoroStatus::BreakSkipped => new_pomodoro,
             PomodoroStatus::BreakPending => {
                 PomodoroTransitioner::start_break(current_time, new_pomodoro)
             }
             PomodoroStatus::Break => {
                 PomodoroTransitioner::complete_pomodoro(current_time, new_pomodoro)
             }
+            PomodoroStatus::BreakPaused => {
+                let resumed = PomodoroTransitioner::end_pause(current_time, new_pomodoro);
+                PomodoroTransitioner::complete_pomodoro(current_time, resumed)
+            }
+            PomodoroStatus::Paused => {
+                let resumed = PomodoroTransitioner::end_pause(current_time, new_pomodoro);
+                PomodoroTransitioner::abort_pomodoro(current_time, resumed)
+            }
             PomodoroStatus::InProgress => {
-                if current_time >= pomodoro.work_start_time + pomodoro.work_length {
+                if current_time >= pomodoro.work_deadline(current_time) {
                     PomodoroTransitioner::finish_working(current_time, new_pomodoro)
                 } else {
                     PomodoroTransitioner::abort_pomodoro(current_time, new_pomodoro)
@@ -29,6 +49,242 @@ impl PomodoroTransitioner {
         }
     }
 
+    /// Moves a pomodoro on if its work period or break has run out by `current_time`, recording
+    /// the time it ran out rather than `current_time`. Returns None if nothing has run out.
+    pub fn expire(current_time: DateTime<UTC>, pomodoro: &Pomodoro) -> Option<Pomodoro> {
+        let deadline = match pomodoro.next_deadline(current_time) {
+            Some(deadline) if deadline <= current_time => deadline,
+            _ => return None,
+        };
+
+        match (*pomodoro).status {
+            PomodoroStatus::InProgress => {
+                Some(PomodoroTransitioner::finish_working(current_time, pomodoro.clone()))
+            }
+            PomodoroStatus::Break => {
+                Some(PomodoroTransitioner::complete_pomodoro(deadline, pomodoro.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Starts the break of a pomodoro whose work period is over, whether or not that has been
+    /// recorded yet.
+    pub fn begin_break(current_time: DateTime<UTC>, pomodoro: &Pomodoro) -> Result<Pomodoro> {
+        let finished = try!(PomodoroTransitioner::finished_working(current_time,
+                                                                   pomodoro,
+                                                                   "start the break of"));
+        Ok(PomodoroTransitioner::start_break(current_time, finished))
+    }
+
+    /// Closes a pomodoro whose work period is over without taking its break.
+    pub fn skip_break(current_time: DateTime<UTC>, pomodoro: &Pomodoro) -> Result<Pomodoro> {
+        let mut skipped = try!(PomodoroTransitioner::finished_working(current_time,
+                                                                      pomodoro,
+                                                                      "skip the break of"));
+        skipped.status = PomodoroStatus::BreakSkipped;
+        Ok(skipped)
+    }
+
+    fn finished_working(current_time: DateTime<UTC>,
+                        pomodoro: &Pomodoro,
+                        action: &str)
+                        -> Result<Pomodoro> {
+        match (*pomodoro).status {
+            PomodoroStatus::BreakPending => Ok(pomodoro.clone()),
+            PomodoroStatus::InProgress if current_time >= pomodoro.work_deadline(current_time) => {
+                Ok(PomodoroTransitioner::finish_working(current_time, pomodoro.clone()))
+            }
+            _ => {
+                Err(Error::InvalidStateError(format!("Cannot {} a pomodoro that is {}.",
+                                                     action,
+                                                     pomodoro.status)))
+            }
+        }
+    }
+
+    /// Adds `length` to the work period of a pomodoro that is being worked on or waiting for its
+    /// break, which takes it back to work, or to the break of one on a break.
+    pub fn extend(current_time: DateTime<UTC>,
+                  pomodoro: &Pomodoro,
+                  length: Duration)
+                  -> Result<Pomodoro> {
+        let mut new_pomodoro = pomodoro.clone();
+
+        let period = match (*pomodoro).status {
+            PomodoroStatus::InProgress | PomodoroStatus::Paused => Period::Work,
+            PomodoroStatus::BreakPending => {
+                new_pomodoro.work_end_time = None;
+                new_pomodoro.status = PomodoroStatus::InProgress;
+                Period::Work
+            }
+            PomodoroStatus::Break | PomodoroStatus::BreakPaused => Period::Break,
+            _ => {
+                return Err(Error::InvalidStateError(format!("Cannot extend a pomodoro that is {}.",
+                                                            pomodoro.status)))
+            }
+        };
+
+        match period {
+            Period::Work => new_pomodoro.work_length = new_pomodoro.work_length + length,
+            Period::Break => new_pomodoro.break_length = new_pomodoro.break_length + length,
+        }
+        new_pomodoro.extensions.push(Extension {
+            time: current_time,
+            period: period,
+            length: length,
+        });
+        Ok(new_pomodoro)
+    }
+
+    /// Records an interruption of a pomodoro that is being worked on.
+    pub fn interrupt(current_time: DateTime<UTC>,
+                     pomodoro: &Pomodoro,
+                     kind: InterruptionKind,
+                     note: Option<String>)
+                     -> Result<Pomodoro> {
+        match (*pomodoro).status {
+            PomodoroStatus::InProgress | PomodoroStatus::Paused => {}
+            _ => {
+                return Err(Error::InvalidStateError(format!("Cannot interrupt a pomodoro that is {}.",
+                                                            pomodoro.status)))
+            }
+        }
+
+        let mut new_pomodoro = pomodoro.clone();
+        new_pomodoro.interruptions.push(Interruption {
+            time: current_time,
+            kind: kind,
+            note: note,
+        });
+        Ok(new_pomodoro)
+    }
+
+    /// Corrects the fields of a recorded pomodoro, refusing corrections that would leave its
+    /// times out of order or its status at odds with them.
+    pub fn edit(pomodoro: &Pomodoro, edits: &Vec<PomodoroEdit>) -> Result<Pomodoro> {
+        let mut new_pomodoro = pomodoro.clone();
+        for edit in edits.iter() {
+            match edit.clone() {
+                PomodoroEdit::WorkStartTime(time) => new_pomodoro.work_start_time = time,
+                PomodoroEdit::WorkEndTime(time) => new_pomodoro.work_end_time = time,
+                PomodoroEdit::BreakStartTime(time) => new_pomodoro.break_start_time = time,
+                PomodoroEdit::BreakEndTime(time) => new_pomodoro.break_end_time = time,
+                PomodoroEdit::WorkLength(length) => new_pomodoro.work_length = length,
+                PomodoroEdit::BreakLength(length) => new_pomodoro.break_length = length,
+                PomodoroEdit::Tags(tags) => new_pomodoro.tags = tags,
+                PomodoroEdit::Status(status) => new_pomodoro.status = status,
+            }
+        }
+
+        try!(PomodoroTransitioner::check_invariants(&new_pomodoro));
+        Ok(new_pomodoro)
+    }
+
+    /// Checks what every transition keeps true: lengths are positive, each recorded time comes
+    /// no earlier than the one before it, and exactly the times the status implies are recorded.
+    fn check_invariants(pomodoro: &Pomodoro) -> Result<()> {
+        if pomodoro.work_length <= Duration::zero() || pomodoro.break_length < Duration::zero() {
+            return Err(Error::InvalidStateError(String::from("The work length must be positive and \
+                                                              the break length cannot be negative.")));
+        }
+
+        let times = [("work_start_time", Some(pomodoro.work_start_time)),
+                     ("work_end_time", pomodoro.work_end_time),
+                     ("break_start_time", pomodoro.break_start_time),
+                     ("break_end_time", pomodoro.break_end_time)];
+        let mut previous: Option<(&str, DateTime<UTC>)> = None;
+        for &(name, time) in times.iter() {
+            if let Some(time) = time {
+                if let Some((previous_name, previous_time)) = previous {
+                    if time < previous_time {
+                        return Err(Error::InvalidStateError(format!("The {} cannot be before the {}.",
+                                                                    name,
+                                                                    previous_name)));
+                    }
+                }
+                previous = Some((name, time));
+            }
+        }
+
+        let (work_ended, break_started, break_ended) = match pomodoro.status {
+            PomodoroStatus::InProgress | PomodoroStatus::Paused => (false, false, false),
+            PomodoroStatus::BreakPending |
+            PomodoroStatus::Aborted |
+            PomodoroStatus::BreakSkipped => (true, false, false),
+            PomodoroStatus::Break | PomodoroStatus::BreakPaused => (true, true, false),
+            PomodoroStatus::Completed => (true, true, true),
+        };
+        if pomodoro.work_end_time.is_some() != work_ended ||
+           pomodoro.break_start_time.is_some() != break_started ||
+           pomodoro.break_end_time.is_some() != break_ended {
+            let needs = |recorded: bool| if recorded { "needs" } else { "cannot have" };
+            return Err(Error::InvalidStateError(format!("A pomodoro that is {} {} a work_end_time, \
+                                                         {} a break_start_time and {} a \
+                                                         break_end_time.",
+                                                        pomodoro.status,
+                                                        needs(work_ended),
+                                                        needs(break_started),
+                                                        needs(break_ended))));
+        }
+
+        let paused = pomodoro.status == PomodoroStatus::Paused ||
+                     pomodoro.status == PomodoroStatus::BreakPaused;
+        if pomodoro.pauses.iter().any(|pause| pause.end_time.is_none()) != paused {
+            return Err(Error::InvalidStateError(format!("A pomodoro that is {} {} a pause that has \
+                                                         not been resumed.",
+                                                        pomodoro.status,
+                                                        if paused { "needs" } else { "cannot have" })));
+        }
+        Ok(())
+    }
+
+    /// Suspends a running work period or break. Time spent paused does not count towards the
+    /// work or break length.
+    pub fn pause(current_time: DateTime<UTC>, pomodoro: &Pomodoro) -> Result<Pomodoro> {
+        let mut new_pomodoro = pomodoro.clone();
+
+        match (*pomodoro).status {
+            PomodoroStatus::InProgress if current_time < pomodoro.work_deadline(current_time) => {
+                new_pomodoro.status = PomodoroStatus::Paused;
+            }
+            PomodoroStatus::Break => new_pomodoro.status = PomodoroStatus::BreakPaused,
+            _ => {
+                return Err(Error::InvalidStateError(format!("Cannot pause a pomodoro that is {}.",
+                                                            pomodoro.status)))
+            }
+        }
+
+        new_pomodoro.pauses.push(Pause {
+            start_time: current_time,
+            end_time: None,
+        });
+        Ok(new_pomodoro)
+    }
+
+    pub fn resume(current_time: DateTime<UTC>, pomodoro: &Pomodoro) -> Result<Pomodoro> {
+        let status = match (*pomodoro).status {
+            PomodoroStatus::Paused => PomodoroStatus::InProgress,
+            PomodoroStatus::BreakPaused => PomodoroStatus::Break,
+            _ => {
+                return Err(Error::InvalidStateError(format!("Cannot resume a pomodoro that is {}.",
+                                                            pomodoro.status)))
+            }
+        };
+
+        let mut new_pomodoro = PomodoroTransitioner::end_pause(current_time, pomodoro.clone());
+        new_pomodoro.status = status;
+        Ok(new_pomodoro)
+    }
+
+    fn end_pause(current_time: DateTime<UTC>, pomodoro: Pomodoro) -> Pomodoro {
+        let mut pomodoro = pomodoro;
+        for pause in pomodoro.pauses.iter_mut().filter(|pause| pause.end_time.is_none()) {
+            pause.end_time = Some(current_time);
+        }
+        pomodoro
+    }
+
     fn start_break(current_time: DateTime<UTC>, pomodoro: Pomodoro) -> Pomodoro {
         let mut pomodoro = pomodoro;
         pomodoro.break_start_time = Some(current_time);
@@ -43,9 +299,9 @@ impl PomodoroTransitioner {
         pomodoro
     }
 
-    fn finish_working(_: DateTime<UTC>, pomodoro: Pomodoro) -> Pomodoro {
+    fn finish_working(current_time: DateTime<UTC>, pomodoro: Pomodoro) -> Pomodoro {
         let mut pomodoro = pomodoro;
-        pomodoro.work_end_time = Some(pomodoro.work_start_time + pomodoro.work_length);
+        pomodoro.work_end_time = Some(pomodoro.work_deadline(current_time));
         pomodoro.status = PomodoroStatus::BreakPending;
         pomodoro
     }
@@ -74,7 +330,12 @@ mod test {
             break_end_time: None,
             work_length: Duration::seconds(5),
             break_length: Duration::seconds(5),
-            tags: String::from(""),
+            tags: vec![],
+            pauses: vec![],
+            cycle_position: 1,
+            extensions: vec![],
+            interruptions: vec![],
+            notes: None,
             status: PomodoroStatus::InProgress,
         };
         let transition_time = "2000-01-01T00:00:01+00:00".parse::<DateTime<UTC>>().unwrap();
@@ -95,7 +356,12 @@ mod test {
             break_end_time: None,
             work_length: Duration::seconds(5),
             break_length: Duration::seconds(5),
-            tags: String::from(""),
+            tags: vec![],
+            pauses: vec![],
+            cycle_position: 1,
+            extensions: vec![],
+            interruptions: vec![],
+            notes: None,
             status: PomodoroStatus::InProgress,
         };
         let transition_time = "2000-01-01T00:00:05+00:00".parse::<DateTime<UTC>>().unwrap();
@@ -116,7 +382,12 @@ mod test {
             break_end_time: None,
             work_length: Duration::seconds(5),
             break_length: Duration::seconds(5),
-            tags: String::from(""),
+            tags: vec![],
+            pauses: vec![],
+            cycle_position: 1,
+            extensions: vec![],
+            interruptions: vec![],
+            notes: None,
             status: PomodoroStatus::BreakPending,
         };
         let transition_time = "2000-01-01T00:00:10+00:00".parse::<DateTime<UTC>>().unwrap();
@@ -137,7 +408,12 @@ mod test {
             break_end_time: None,
             work_length: Duration::seconds(5),
             break_length: Duration::seconds(5),
-            tags: String::from(""),
+            tags: vec![],
+            pauses: vec![],
+            cycle_position: 1,
+            extensions: vec![],
+            interruptions: vec![],
+            notes: None,
             status: PomodoroStatus::Break,
         };
         let transition_time = "2000-01-01T00:00:15+00:00".parse::<DateTime<UTC>>().unwrap();
@@ -158,7 +434,12 @@ mod test {
             break_end_time: None,
             work_length: Duration::seconds(5),
             break_length: Duration::seconds(5),
-            tags: String::from(""),
+            tags: vec![],
+            pauses: vec![],
+            cycle_position: 1,
+            extensions: vec![],
+            interruptions: vec![],
+            notes: None,
             status: PomodoroStatus::Aborted,
         };
         let transition_time = "2000-01-01T00:00:10+00:00".parse::<DateTime<UTC>>().unwrap();
@@ -178,7 +459,12 @@ mod test {
             break_end_time: Some("2000-01-01T00:00:15+00:00".parse::<DateTime<UTC>>().unwrap()),
             work_length: Duration::seconds(5),
             break_length: Duration::seconds(5),
-            tags: String::from(""),
+            tags: vec![],
+            pauses: vec![],
+            cycle_position: 1,
+            extensions: vec![],
+            interruptions: vec![],
+            notes: None,
             status: PomodoroStatus::Completed,
         };
         let transition_time = "2000-01-01T00:00:30+00:00".parse::<DateTime<UTC>>().unwrap();
@@ -188,4 +474,287 @@ mod test {
         println!("{:?} {:?}", updated_pomodoro, pomodoro);
         assert!(updated_pomodoro == pomodoro);
     }
+
+    fn in_progress_pomodoro(pauses: Vec<Pause>, status: PomodoroStatus) -> Pomodoro {
+        Pomodoro {
+            id: 0,
+            work_start_time: "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap(),
+            work_end_time: None,
+            break_start_time: None,
+            break_end_time: None,
+            work_length: Duration::seconds(5),
+            break_length: Duration::seconds(5),
+            tags: vec![],
+            pauses: pauses,
+            cycle_position: 1,
+            extensions: vec![],
+            interruptions: vec![],
+            notes: None,
+            status: status,
+        }
+    }
+
+    #[test]
+    fn pauses_a_pomodoro_in_progress() {
+        let pomodoro = in_progress_pomodoro(vec![], PomodoroStatus::InProgress);
+        let pause_time = "2000-01-01T00:00:01+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        let paused_pomodoro = PomodoroTransitioner::pause(pause_time, &pomodoro).unwrap();
+
+        assert!(paused_pomodoro.status == PomodoroStatus::Paused);
+        assert!(paused_pomodoro.pauses == vec![Pause { start_time: pause_time, end_time: None }]);
+    }
+
+    #[test]
+    fn refuses_to_pause_a_pomodoro_that_is_not_running() {
+        let pomodoro = in_progress_pomodoro(vec![], PomodoroStatus::BreakPending);
+        let pause_time = "2000-01-01T00:00:06+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        assert!(PomodoroTransitioner::pause(pause_time, &pomodoro).is_err());
+    }
+
+    #[test]
+    fn resumes_a_paused_pomodoro() {
+        let pause_time = "2000-01-01T00:00:01+00:00".parse::<DateTime<UTC>>().unwrap();
+        let resume_time = "2000-01-01T00:00:03+00:00".parse::<DateTime<UTC>>().unwrap();
+        let pomodoro = in_progress_pomodoro(vec![Pause { start_time: pause_time, end_time: None }],
+                                            PomodoroStatus::Paused);
+
+        let resumed_pomodoro = PomodoroTransitioner::resume(resume_time, &pomodoro).unwrap();
+
+        assert!(resumed_pomodoro.status == PomodoroStatus::InProgress);
+        assert!(resumed_pomodoro.pauses ==
+                vec![Pause { start_time: pause_time, end_time: Some(resume_time) }]);
+    }
+
+    #[test]
+    fn does_not_count_time_spent_paused_towards_the_work_length() {
+        let pause = Pause {
+            start_time: "2000-01-01T00:00:01+00:00".parse::<DateTime<UTC>>().unwrap(),
+            end_time: Some("2000-01-01T00:00:04+00:00".parse::<DateTime<UTC>>().unwrap()),
+        };
+        let pomodoro = in_progress_pomodoro(vec![pause], PomodoroStatus::InProgress);
+        let before_deadline = "2000-01-01T00:00:07+00:00".parse::<DateTime<UTC>>().unwrap();
+        let after_deadline = "2000-01-01T00:00:09+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        let before_deadline = PomodoroTransitioner::transition(before_deadline, &pomodoro);
+        let after_deadline = PomodoroTransitioner::transition(after_deadline, &pomodoro);
+
+        assert!(before_deadline.status == PomodoroStatus::Aborted);
+        assert!(after_deadline.status == PomodoroStatus::BreakPending);
+        assert!(after_deadline.work_end_time ==
+                Some("2000-01-01T00:00:08+00:00".parse::<DateTime<UTC>>().unwrap()));
+    }
+
+    #[test]
+    fn expires_work_at_the_work_deadline_rather_than_when_noticed() {
+        let pomodoro = in_progress_pomodoro(vec![], PomodoroStatus::InProgress);
+        let noticed_time = "2000-01-01T00:00:07+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        let expired = PomodoroTransitioner::expire(noticed_time, &pomodoro).unwrap();
+
+        assert!(expired.status == PomodoroStatus::BreakPending);
+        assert!(expired.work_end_time ==
+                Some("2000-01-01T00:00:05+00:00".parse::<DateTime<UTC>>().unwrap()));
+    }
+
+    #[test]
+    fn expires_a_break_at_the_break_deadline() {
+        let mut pomodoro = in_progress_pomodoro(vec![], PomodoroStatus::Break);
+        pomodoro.work_end_time = Some("2000-01-01T00:00:05+00:00".parse::<DateTime<UTC>>().unwrap());
+        pomodoro.break_start_time = Some("2000-01-01T00:00:05+00:00".parse::<DateTime<UTC>>().unwrap());
+        let noticed_time = "2000-01-01T00:00:12+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        let expired = PomodoroTransitioner::expire(noticed_time, &pomodoro).unwrap();
+
+        assert!(expired.status == PomodoroStatus::Completed);
+        assert!(expired.break_end_time ==
+                Some("2000-01-01T00:00:10+00:00".parse::<DateTime<UTC>>().unwrap()));
+    }
+
+    #[test]
+    fn does_not_expire_a_pomodoro_before_its_deadline_or_while_paused() {
+        let running = in_progress_pomodoro(vec![], PomodoroStatus::InProgress);
+        let paused = in_progress_pomodoro(vec![Pause {
+                                                   start_time: "2000-01-01T00:00:01+00:00"
+                                                       .parse::<DateTime<UTC>>()
+                                                       .unwrap(),
+                                                   end_time: None,
+                                               }],
+                                          PomodoroStatus::Paused);
+        let before_deadline = "2000-01-01T00:00:04+00:00".parse::<DateTime<UTC>>().unwrap();
+        let after_deadline = "2000-01-01T00:00:30+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        assert!(PomodoroTransitioner::expire(before_deadline, &running).is_none());
+        assert!(PomodoroTransitioner::expire(after_deadline, &paused).is_none());
+    }
+
+    #[test]
+    fn starts_the_break_of_a_pomodoro_whose_work_has_run_out() {
+        let pomodoro = in_progress_pomodoro(vec![], PomodoroStatus::InProgress);
+        let break_time = "2000-01-01T00:00:07+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        let on_break = PomodoroTransitioner::begin_break(break_time, &pomodoro).unwrap();
+
+        assert!(on_break.status == PomodoroStatus::Break);
+        assert!(on_break.work_end_time ==
+                Some("2000-01-01T00:00:05+00:00".parse::<DateTime<UTC>>().unwrap()));
+        assert!(on_break.break_start_time == Some(break_time));
+    }
+
+    #[test]
+    fn records_a_skipped_break_without_break_times() {
+        let mut pomodoro = in_progress_pomodoro(vec![], PomodoroStatus::BreakPending);
+        pomodoro.work_end_time = Some("2000-01-01T00:00:05+00:00".parse::<DateTime<UTC>>().unwrap());
+        let skip_time = "2000-01-01T00:00:07+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        let skipped = PomodoroTransitioner::skip_break(skip_time, &pomodoro).unwrap();
+
+        assert!(skipped.status == PomodoroStatus::BreakSkipped);
+        assert!(skipped.break_start_time == None);
+        assert!(skipped.break_end_time == None);
+    }
+
+    #[test]
+    fn refuses_to_start_a_break_before_the_work_has_run_out() {
+        let pomodoro = in_progress_pomodoro(vec![], PomodoroStatus::InProgress);
+        let break_time = "2000-01-01T00:00:04+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        assert!(PomodoroTransitioner::begin_break(break_time, &pomodoro).is_err());
+        assert!(PomodoroTransitioner::skip_break(break_time, &pomodoro).is_err());
+    }
+
+    #[test]
+    fn extends_the_work_period_until_the_new_length_has_elapsed() {
+        let pomodoro = in_progress_pomodoro(vec![], PomodoroStatus::InProgress);
+        let extend_time = "2000-01-01T00:00:04+00:00".parse::<DateTime<UTC>>().unwrap();
+        let old_deadline = "2000-01-01T00:00:06+00:00".parse::<DateTime<UTC>>().unwrap();
+        let new_deadline = "2000-01-01T00:00:15+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        let extended = PomodoroTransitioner::extend(extend_time, &pomodoro, Duration::seconds(10))
+            .unwrap();
+
+        assert!(extended.work_length == Duration::seconds(15));
+        assert!(extended.extensions ==
+                vec![Extension {
+                         time: extend_time,
+                         period: Period::Work,
+                         length: Duration::seconds(10),
+                     }]);
+        assert!(PomodoroTransitioner::transition(old_deadline, &extended).status ==
+                PomodoroStatus::Aborted);
+        assert!(PomodoroTransitioner::transition(new_deadline, &extended).status ==
+                PomodoroStatus::BreakPending);
+    }
+
+    #[test]
+    fn takes_a_pomodoro_waiting_for_its_break_back_to_work_when_extended() {
+        let mut pomodoro = in_progress_pomodoro(vec![], PomodoroStatus::BreakPending);
+        pomodoro.work_end_time = Some("2000-01-01T00:00:05+00:00".parse::<DateTime<UTC>>().unwrap());
+        let extend_time = "2000-01-01T00:00:06+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        let extended = PomodoroTransitioner::extend(extend_time, &pomodoro, Duration::seconds(10))
+            .unwrap();
+
+        assert!(extended.status == PomodoroStatus::InProgress);
+        assert!(extended.work_end_time == None);
+        assert!(extended.work_deadline(extend_time) ==
+                "2000-01-01T00:00:15+00:00".parse::<DateTime<UTC>>().unwrap());
+    }
+
+    #[test]
+    fn extends_the_break_of_a_pomodoro_on_a_break() {
+        let mut pomodoro = in_progress_pomodoro(vec![], PomodoroStatus::Break);
+        pomodoro.work_end_time = Some("2000-01-01T00:00:05+00:00".parse::<DateTime<UTC>>().unwrap());
+        pomodoro.break_start_time = Some("2000-01-01T00:00:05+00:00".parse::<DateTime<UTC>>().unwrap());
+        let extend_time = "2000-01-01T00:00:06+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        let extended = PomodoroTransitioner::extend(extend_time, &pomodoro, Duration::seconds(10))
+            .unwrap();
+
+        assert!(extended.work_length == Duration::seconds(5));
+        assert!(extended.break_length == Duration::seconds(15));
+    }
+
+    #[test]
+    fn records_interruptions_of_a_pomodoro_in_progress() {
+        let pomodoro = in_progress_pomodoro(vec![], PomodoroStatus::InProgress);
+        let interruption_time = "2000-01-01T00:00:01+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        let interrupted = PomodoroTransitioner::interrupt(interruption_time,
+                                                          &pomodoro,
+                                                          InterruptionKind::External,
+                                                          Some(String::from("phone")))
+            .unwrap();
+
+        assert!(interrupted.interruptions ==
+                vec![Interruption {
+                         time: interruption_time,
+                         kind: InterruptionKind::External,
+                         note: Some(String::from("phone")),
+                     }]);
+    }
+
+    #[test]
+    fn refuses_to_interrupt_a_break() {
+        let pomodoro = in_progress_pomodoro(vec![], PomodoroStatus::Break);
+        let interruption_time = "2000-01-01T00:00:06+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        assert!(PomodoroTransitioner::interrupt(interruption_time,
+                                                &pomodoro,
+                                                InterruptionKind::Internal,
+                                                None)
+            .is_err());
+    }
+
+    #[test]
+    fn edits_a_pomodoro_left_running_by_mistake() {
+        let pomodoro = in_progress_pomodoro(vec![], PomodoroStatus::InProgress);
+        let end_time = "2000-01-01T00:00:03+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        let edited = PomodoroTransitioner::edit(&pomodoro,
+                                                &vec![PomodoroEdit::WorkEndTime(Some(end_time)),
+                                                      PomodoroEdit::Status(PomodoroStatus::Aborted),
+                                                      PomodoroEdit::Tags(vec![String::from("foo")])])
+            .unwrap();
+
+        assert!(edited.work_end_time == Some(end_time));
+        assert!(edited.status == PomodoroStatus::Aborted);
+        assert!(edited.tags == vec![String::from("foo")]);
+    }
+
+    #[test]
+    fn refuses_edits_that_put_times_out_of_order() {
+        let pomodoro = in_progress_pomodoro(vec![], PomodoroStatus::InProgress);
+        let end_time = "1999-12-31T23:59:59+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        assert!(PomodoroTransitioner::edit(&pomodoro,
+                                           &vec![PomodoroEdit::WorkEndTime(Some(end_time)),
+                                                 PomodoroEdit::Status(PomodoroStatus::Aborted)])
+            .is_err());
+        assert!(PomodoroTransitioner::edit(&pomodoro,
+                                           &vec![PomodoroEdit::WorkLength(Duration::seconds(0))])
+            .is_err());
+    }
+
+    #[test]
+    fn refuses_edits_that_leave_the_status_at_odds_with_the_times() {
+        let pomodoro = in_progress_pomodoro(vec![], PomodoroStatus::InProgress);
+        let paused = in_progress_pomodoro(vec![Pause {
+                                                   start_time: "2000-01-01T00:00:01+00:00"
+                                                       .parse::<DateTime<UTC>>()
+                                                       .unwrap(),
+                                                   end_time: None,
+                                               }],
+                                          PomodoroStatus::Paused);
+
+        assert!(PomodoroTransitioner::edit(&pomodoro,
+                                           &vec![PomodoroEdit::Status(PomodoroStatus::Completed)])
+            .is_err());
+        assert!(PomodoroTransitioner::edit(&pomodoro, &vec![PomodoroEdit::Status(PomodoroStatus::Paused)])
+            .is_err());
+        assert!(PomodoroTransitioner::edit(&paused,
+                                           &vec![PomodoroEdit::Status(PomodoroStatus::InProgress)])
+            .is_err());
+    }
 }
@@ -7,6 +7,41 @@ use daemon::result::Result;
 
 use std::vec::Vec;
 use std::option::Option;
+use std::sync::Arc;
+
+/// Selects pomodoros most recent first. Only pomodoros whose work started at or after `since`
+/// and before `until` match; the first `offset` matches are skipped and at most `limit` returned.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PomodoroQuery {
+    pub limit: usize,
+    pub offset: usize,
+    pub since: Option<DateTime<UTC>>,
+    pub until: Option<DateTime<UTC>>,
+}
+
+impl PomodoroQuery {
+    pub fn most_recent(limit: usize) -> PomodoroQuery {
+        PomodoroQuery {
+            limit: limit,
+            offset: 0,
+            since: None,
+            until: None,
+        }
+    }
+
+    pub fn matches(&self, pomodoro: &Pomodoro) -> bool {
+        self.since.map(|since| pomodoro.work_start_time >= since).unwrap_or(true) &&
+        self.until.map(|until| pomodoro.work_start_time < until).unwrap_or(true)
+    }
+
+    /// Runs the query over pomodoros held in memory, ordering them the way the SQL stores do.
+    pub fn apply(&self, pomodoros: &Vec<Pomodoro>) -> Vec<Pomodoro> {
+        let mut matching: Vec<Pomodoro> =
+            pomodoros.iter().filter(|pomodoro| self.matches(pomodoro)).cloned().collect();
+        matching.sort_by(|a, b| b.work_start_time.cmp(&a.work_start_time).then(b.id.cmp(&a.id)));
+        matching.into_iter().skip(self.offset).take(self.limit).collect()
+    }
+}
 
 #[cfg(test)] use super::mockers_derive::mocked;
 #[cfg_attr(test, mocked)]
@@ -14,9 +49,226 @@ pub trait Pomodoros {
     fn create(&self,
               start_time: DateTime<UTC>,
               start_duration: Duration,
-              break_duration: Duration)
+              break_duration: Duration,
+              tags: Vec<String>)
               -> Result<()>;
     fn last(&self, count: usize) -> Result<Vec<Pomodoro>>;
-    fn most_recent(&self) -> Option<Pomodoro>;
+    fn query(&self, query: PomodoroQuery) -> Result<Vec<Pomodoro>>;
+    fn most_recent(&self) -> Result<Option<Pomodoro>>;
+    fn find(&self, id: i32) -> Result<Option<Pomodoro>>;
     fn update(&self, id: i32, pomodoro: Pomodoro) -> Result<()>;
+    /// Stores a complete record, such as one for a pomodoro done away from the daemon, under a
+    /// newly allocated id, which is returned.
+    fn insert(&self, pomodoro: Pomodoro) -> Result<i32>;
+    /// Removes a pomodoro along with its pauses and other details. Unknown ids are ignored.
+    fn delete(&self, id: i32) -> Result<()>;
+    /// Stores a record under its own id, replacing any record with that id, so that a change
+    /// can be undone exactly.
+    fn restore(&self, pomodoro: Pomodoro) -> Result<()>;
+
+    /// Runs `work` as a single unit: either every change it makes through the given view is
+    /// kept, or, if it returns an error, none are. Other writers are held off until it finishes,
+    /// so what `work` reads cannot change underneath it. A transaction started from within
+    /// `work` joins the enclosing one.
+    fn transaction(&self, work: &mut FnMut(&Pomodoros) -> Result<()>) -> Result<()>;
+}
+
+impl<P: Pomodoros + ?Sized> Pomodoros for Box<P> {
+    fn create(&self,
+              start_time: DateTime<UTC>,
+              start_duration: Duration,
+              break_duration: Duration,
+              tags: Vec<String>)
+              -> Result<()> {
+        (**self).create(start_time, start_duration, break_duration, tags)
+    }
+
+    fn last(&self, count: usize) -> Result<Vec<Pomodoro>> {
+        (**self).last(count)
+    }
+
+    fn query(&self, query: PomodoroQuery) -> Result<Vec<Pomodoro>> {
+        (**self).query(query)
+    }
+
+    fn most_recent(&self) -> Result<Option<Pomodoro>> {
+        (**self).most_recent()
+    }
+
+    fn find(&self, id: i32) -> Result<Option<Pomodoro>> {
+        (**self).find(id)
+    }
+
+    fn update(&self, id: i32, pomodoro: Pomodoro) -> Result<()> {
+        (**self).update(id, pomodoro)
+    }
+
+    fn insert(&self, pomodoro: Pomodoro) -> Result<i32> {
+        (**self).insert(pomodoro)
+    }
+
+    fn delete(&self, id: i32) -> Result<()> {
+        (**self).delete(id)
+    }
+
+    fn restore(&self, pomodoro: Pomodoro) -> Result<()> {
+        (**self).restore(pomodoro)
+    }
+
+    fn transaction(&self, work: &mut FnMut(&Pomodoros) -> Result<()>) -> Result<()> {
+        (**self).transaction(work)
+    }
+}
+
+impl<'a, P: Pomodoros + ?Sized> Pomodoros for &'a P {
+    fn create(&self,
+              start_time: DateTime<UTC>,
+              start_duration: Duration,
+              break_duration: Duration,
+              tags: Vec<String>)
+              -> Result<()> {
+        (**self).create(start_time, start_duration, break_duration, tags)
+    }
+
+    fn last(&self, count: usize) -> Result<Vec<Pomodoro>> {
+        (**self).last(count)
+    }
+
+    fn query(&self, query: PomodoroQuery) -> Result<Vec<Pomodoro>> {
+        (**self).query(query)
+    }
+
+    fn most_recent(&self) -> Result<Option<Pomodoro>> {
+        (**self).most_recent()
+    }
+
+    fn find(&self, id: i32) -> Result<Option<Pomodoro>> {
+        (**self).find(id)
+    }
+
+    fn update(&self, id: i32, pomodoro: Pomodoro) -> Result<()> {
+        (**self).update(id, pomodoro)
+    }
+
+    fn insert(&self, pomodoro: Pomodoro) -> Result<i32> {
+        (**self).insert(pomodoro)
+    }
+
+    fn delete(&self, id: i32) -> Result<()> {
+        (**self).delete(id)
+    }
+
+    fn restore(&self, pomodoro: Pomodoro) -> Result<()> {
+        (**self).restore(pomodoro)
+    }
+
+    fn transaction(&self, work: &mut FnMut(&Pomodoros) -> Result<()>) -> Result<()> {
+        (**self).transaction(work)
+    }
+}
+
+impl<P: Pomodoros + ?Sized> Pomodoros for Arc<P> {
+    fn create(&self,
+              start_time: DateTime<UTC>,
+              start_duration: Duration,
+              break_duration: Duration,
+              tags: Vec<String>)
+              -> Result<()> {
+        (**self).create(start_time, start_duration, break_duration, tags)
+    }
+
+    fn last(&self, count: usize) -> Result<Vec<Pomodoro>> {
+        (**self).last(count)
+    }
+
+    fn query(&self, query: PomodoroQuery) -> Result<Vec<Pomodoro>> {
+        (**self).query(query)
+    }
+
+    fn most_recent(&self) -> Result<Option<Pomodoro>> {
+        (**self).most_recent()
+    }
+
+    fn find(&self, id: i32) -> Result<Option<Pomodoro>> {
+        (**self).find(id)
+    }
+
+    fn update(&self, id: i32, pomodoro: Pomodoro) -> Result<()> {
+        (**self).update(id, pomodoro)
+    }
+
+    fn insert(&self, pomodoro: Pomodoro) -> Result<i32> {
+        (**self).insert(pomodoro)
+    }
+
+    fn delete(&self, id: i32) -> Result<()> {
+        (**self).delete(id)
+    }
+
+    fn restore(&self, pomodoro: Pomodoro) -> Result<()> {
+        (**self).restore(pomodoro)
+    }
+
+    fn transaction(&self, work: &mut FnMut(&Pomodoros) -> Result<()>) -> Result<()> {
+        (**self).transaction(work)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use daemon::pomodoro::PomodoroStatus;
+
+    fn pomodoro_starting_at(id: i32, work_start_time: &str) -> Pomodoro {
+        Pomodoro {
+            id: id,
+            work_start_time: work_start_time.parse::<DateTime<UTC>>().unwrap(),
+            work_end_time: None,
+            break_start_time: None,
+            break_end_time: None,
+            work_length: Duration::seconds(5),
+            break_length: Duration::seconds(5),
+            tags: vec![],
+            pauses: vec![],
+            cycle_position: 1,
+            extensions: vec![],
+            interruptions: vec![],
+            notes: None,
+            status: PomodoroStatus::Completed,
+        }
+    }
+
+    #[test]
+    fn applies_the_time_range_before_the_offset_and_limit() {
+        let pomodoros = vec![pomodoro_starting_at(1, "2000-01-01T00:00:00+00:00"),
+                             pomodoro_starting_at(2, "2000-01-02T00:00:00+00:00"),
+                             pomodoro_starting_at(3, "2000-01-03T00:00:00+00:00"),
+                             pomodoro_starting_at(4, "2000-01-04T00:00:00+00:00"),
+                             pomodoro_starting_at(5, "2000-01-05T00:00:00+00:00")];
+        let query = PomodoroQuery {
+            limit: 2,
+            offset: 1,
+            since: Some("2000-01-02T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap()),
+            until: Some("2000-01-05T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap()),
+        };
+
+        let ids: Vec<i32> = query.apply(&pomodoros).into_iter().map(|p| p.id).collect();
+
+        assert!(ids == vec![3, 2]);
+    }
+
+    #[test]
+    fn breaks_ties_in_start_time_by_most_recently_created() {
+        let pomodoros = vec![pomodoro_starting_at(1, "2000-01-01T00:00:00+00:00"),
+                             pomodoro_starting_at(2, "2000-01-01T00:00:00+00:00")];
+
+        let ids: Vec<i32> = PomodoroQuery::most_recent(5)
+            .apply(&pomodoros)
+            .into_iter()
+            .map(|p| p.id)
+            .collect();
+
+        assert!(ids == vec![2, 1]);
+    }
 }
@@ -0,0 +1,369 @@
+use daemon::chrono::Duration;
+use daemon::chrono::datetime::DateTime;
+use daemon::chrono::offset::utc::UTC;
+
+use daemon::command;
+use daemon::command::Command;
+use daemon::pomodoro::InterruptionKind;
+use daemon::pomodoro::Period;
+use daemon::pomodoro::PomodoroEdit;
+use daemon::pomodoro_event::PomodoroEvent;
+use daemon::pomodoro_json;
+use daemon::response::Response;
+
+use daemon::serde_json;
+use daemon::serde_json::{Map, Value};
+
+use daemon::result::Error;
+use daemon::result::Result;
+
+use std::i32;
+use std::option::Option;
+
+/// Version of the JSON protocol spoken by this daemon. Requests have to name it and every
+/// response carries it, so that clients can tell when the protocol has changed under them.
+pub const PROTOCOL_VERSION: i64 = 1;
+
+/// Whether a message is a JSON request rather than a text command. No text command starts with
+/// a brace, so both can be spoken over the same socket.
+pub fn is_json_request(message: &str) -> bool {
+    message.trim_left().starts_with("{")
+}
+
+/// Reads a JSON request, an object naming its command and giving that command's arguments, e.g.
+/// `{"version": 1, "command": "start", "work_length": 1500, "tags": ["foo"]}`. Lengths are
+/// seconds and times are RFC 3339 strings; arguments the text commands let out can be left out.
+pub fn request_from_json(current_time: DateTime<UTC>, message: &str) -> Result<Command> {
+    let request: Value = try!(serde_json::from_str(message)
+        .map_err(|e| Error::InvalidCommandError(format!("Invalid request: {}", e))));
+    if !request.is_object() {
+        return Err(Error::InvalidCommandError(format!("Expected a request object, got {}", request)));
+    }
+
+    let version = try!(required(field(&request, "version", "an integer", Value::as_i64), "version"));
+    if version != PROTOCOL_VERSION {
+        return Err(Error::InvalidCommandError(format!("Unsupported protocol version: {}", version)));
+    }
+
+    let name = try!(required(field(&request, "command", "a string", Value::as_str), "command"));
+    match name {
+        "start" => {
+            let work_length = try!(seconds_field(&request, "work_length"));
+            let break_length = try!(seconds_field(&request, "break_length"));
+            Ok(Command::Start(current_time,
+                              work_length.unwrap_or(Duration::seconds(1500)),
+                              break_length.unwrap_or(Duration::seconds(300)),
+                              try!(tags_field(&request, "tags")).unwrap_or(vec![])))
+        }
+        "stop" => Ok(Command::Stop),
+        "list" => Ok(Command::List),
+        "status" => Ok(Command::Status),
+        "pause" => Ok(Command::Pause),
+        "resume" => Ok(Command::Resume),
+        "start_break" => Ok(Command::StartBreak),
+        "skip_break" => Ok(Command::SkipBreak),
+        "extend" => {
+            let length = try!(required(seconds_field(&request, "length"), "length"));
+            Ok(Command::Extend(length))
+        }
+        "interrupt" => {
+            let kind = try!(required(field(&request, "kind", "a string", Value::as_str), "kind"));
+            if kind != "internal" && kind != "external" {
+                return Err(Error::InvalidCommandError(format!("Invalid interruption kind: {}", kind)));
+            }
+            let note = try!(field(&request, "note", "a string", Value::as_str));
+            Ok(Command::Interrupt(InterruptionKind::from(String::from(kind)), note.map(String::from)))
+        }
+        "note" => {
+            let text = try!(required(field(&request, "text", "a string", Value::as_str), "text"));
+            if text.trim().is_empty() {
+                return Err(Error::InvalidCommandError(String::from("Note has no text")));
+            }
+            Ok(Command::Note(try!(id_field(&request, "id")), String::from(text.trim())))
+        }
+        "log" => {
+            let start_time = try!(required(time_field(&request, "start_time"), "start_time"));
+            let work_length = try!(required(seconds_field(&request, "work_length"), "work_length"));
+            let break_length = try!(required(seconds_field(&request, "break_length"), "break_length"));
+            let status = try!(field(&request, "status", "a string", Value::as_str));
+            Ok(Command::Log(start_time,
+                            work_length,
+                            break_length,
+                            try!(tags_field(&request, "tags")).unwrap_or(vec![]),
+                            try!(command::parse_final_status(status.unwrap_or("Completed")))))
+        }
+        "edit" => {
+            let id = try!(required(id_field(&request, "id"), "id"));
+            let edits = try!(required(field(&request, "edits", "an object", Value::as_object), "edits"));
+            if edits.is_empty() {
+                return Err(Error::InvalidCommandError(String::from("Nothing to edit")));
+            }
+            let edits = try!(edits.iter()
+                .map(|(field, value)| command::parse_edit(field, &edit_value(value)))
+                .collect::<Result<Vec<PomodoroEdit>>>());
+            Ok(Command::Edit(id, edits))
+        }
+        "delete" => Ok(Command::Delete(try!(required(id_field(&request, "id"), "id")))),
+        "undo" => Ok(Command::Undo),
+        "subscribe" => Ok(Command::Subscribe),
+        _ => Err(Error::InvalidCommandError(format!("Unknown command: {}", name))),
+    }
+}
+
+/// Writes the outcome of a request. Every response has the protocol version, a status of "ok"
+/// or "error", an error code, which is null on success, a human-readable message and a payload
+/// holding the records and times behind it.
+pub fn response_to_json(response: &Result<Response>) -> Value {
+    let mut object = Map::new();
+    object.insert(String::from("version"), Value::from(PROTOCOL_VERSION));
+    match *response {
+        Ok(ref response) => {
+            object.insert(String::from("status"), Value::from("ok"));
+            object.insert(String::from("code"), Value::Null);
+            object.insert(String::from("message"), Value::from(response.to_string()));
+            object.insert(String::from("payload"), payload_to_json(response));
+        }
+        Err(ref e) => {
+            object.insert(String::from("status"), Value::from("error"));
+            object.insert(String::from("code"), Value::from(e.code()));
+            object.insert(String::from("message"), Value::from(e.to_string()));
+            object.insert(String::from("payload"), Value::Null);
+        }
+    }
+    Value::Object(object)
+}
+
+/// Writes an event sent to a client that subscribed in JSON, e.g.
+/// `{"version": 1, "event": "break_pending", "pomodoro": {...}}`.
+pub fn event_to_json(event: &PomodoroEvent) -> Value {
+    let mut object = Map::new();
+    object.insert(String::from("version"), Value::from(PROTOCOL_VERSION));
+    object.insert(String::from("event"), Value::from(event.kind.to_string()));
+    object.insert(String::from("pomodoro"), pomodoro_json::to_json(&event.pomodoro));
+    Value::Object(object)
+}
+
+fn payload_to_json(response: &Response) -> Value {
+    let mut object = Map::new();
+    match *response {
+        Response::Done(_, ref pomodoro) => {
+            object.insert(String::from("pomodoro"),
+                          pomodoro.as_ref().map(pomodoro_json::to_json).unwrap_or(Value::Null));
+        }
+        Response::List(ref pomodoros, cycle_length) => {
+            object.insert(String::from("pomodoros"),
+                          Value::Array(pomodoros.iter().map(pomodoro_json::to_json).collect()));
+            object.insert(String::from("cycle_length"), Value::from(cycle_length));
+        }
+        Response::Status { ref pomodoro, work_remaining, break_remaining, cycle_length } => {
+            object.insert(String::from("pomodoro"), pomodoro_json::to_json(pomodoro));
+            object.insert(String::from("work_remaining"), Value::from(work_remaining.num_seconds()));
+            object.insert(String::from("break_remaining"), Value::from(break_remaining.num_seconds()));
+            object.insert(String::from("work_extended"),
+                          Value::from(pomodoro.extended_time(Period::Work).num_seconds()));
+            object.insert(String::from("break_extended"),
+                          Value::from(pomodoro.extended_time(Period::Break).num_seconds()));
+            object.insert(String::from("cycle_position"), Value::from(pomodoro.cycle_position));
+            object.insert(String::from("cycle_length"), Value::from(cycle_length));
+        }
+    }
+    Value::Object(object)
+}
+
+/// Reads an optional argument, which is missing if left out or null. `kind` describes what
+/// `read` accepts, for the error sent back when it accepts nothing.
+fn field<'a, T, F>(request: &'a Value, field: &str, kind: &str, read: F) -> Result<Option<T>>
+    where F: Fn(&'a Value) -> Option<T>
+{
+    match request.get(field) {
+        None | Some(&Value::Null) => Ok(None),
+        Some(value) => {
+            read(value)
+                .map(Some)
+                .ok_or(Error::InvalidCommandError(format!("Expected {} in field {}, got {}",
+                                                          kind,
+                                                          field,
+                                                          value)))
+        }
+    }
+}
+
+fn required<T>(value: Result<Option<T>>, field: &str) -> Result<T> {
+    value.and_then(|value| value.ok_or(Error::InvalidCommandError(format!("Missing field {}", field))))
+}
+
+/// Reads a pomodoro id, refusing numbers that no id could be rather than truncating them.
+fn id_field(request: &Value, field_name: &str) -> Result<Option<i32>> {
+    field(request, field_name, "a pomodoro id", |value| {
+        value.as_i64()
+            .and_then(|id| if id >= i32::MIN as i64 && id <= i32::MAX as i64 {
+                Some(id as i32)
+            } else {
+                None
+            })
+    })
+}
+
+fn seconds_field(request: &Value, field_name: &str) -> Result<Option<Duration>> {
+    let seconds = try!(field(request, field_name, "a length in seconds", Value::as_i64));
+    match seconds {
+        Some(seconds) if seconds < 0 => {
+            Err(Error::InvalidCommandError(format!("Invalid length in field {}: {}",
+                                                   field_name,
+                                                   seconds)))
+        }
+        _ => Ok(seconds.map(Duration::seconds)),
+    }
+}
+
+fn time_field(request: &Value, field_name: &str) -> Result<Option<DateTime<UTC>>> {
+    let time = try!(field(request, field_name, "a time", Value::as_str));
+    match time {
+        Some(time) => {
+            time.parse::<DateTime<UTC>>()
+                .map(Some)
+                .map_err(|_| {
+                    Error::InvalidCommandError(format!("Invalid time in field {}: {}", field_name, time))
+                })
+        }
+        None => Ok(None),
+    }
+}
+
+fn tags_field(request: &Value, field_name: &str) -> Result<Option<Vec<String>>> {
+    field(request, field_name, "a list of tags", |tags| {
+        tags.as_array().and_then(|tags| {
+            tags.iter().map(|tag| tag.as_str().map(String::from)).collect::<Option<Vec<String>>>()
+        })
+    })
+}
+
+/// Writes a corrected value the way EDIT takes it as text: null clears a time, numbers are
+/// seconds and a list holds tags.
+fn edit_value(value: &Value) -> String {
+    match *value {
+        Value::Null => String::from("none"),
+        Value::String(ref string) => string.clone(),
+        Value::Array(ref tags) => {
+            let tags: Vec<String> = tags.iter()
+                .map(|tag| tag.as_str().map(String::from).unwrap_or(tag.to_string()))
+                .collect();
+            tags.join(",")
+        }
+        ref other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use daemon::pomodoro::Pomodoro;
+    use daemon::pomodoro::PomodoroStatus;
+    use daemon::pomodoro_event::PomodoroEventKind;
+
+    fn pomodoro() -> Pomodoro {
+        Pomodoro {
+            id: 7,
+            work_start_time: "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap(),
+            work_end_time: None,
+            break_start_time: None,
+            break_end_time: None,
+            work_length: Duration::seconds(1500),
+            break_length: Duration::seconds(300),
+            tags: vec![],
+            pauses: vec![],
+            cycle_position: 2,
+            extensions: vec![],
+            interruptions: vec![],
+            notes: None,
+            status: PomodoroStatus::InProgress,
+        }
+    }
+
+    #[test]
+    fn reads_typed_requests() {
+        let current_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+
+        assert!(request_from_json(current_time,
+                                  r#"{"version": 1, "command": "start", "work_length": 60, "tags": ["foo"]}"#)
+            .unwrap() ==
+                Command::Start(current_time,
+                               Duration::seconds(60),
+                               Duration::seconds(300),
+                               vec![String::from("foo")]));
+        assert!(request_from_json(current_time, r#"{"version": 1, "command": "note", "id": 3, "text": "hi"}"#)
+            .unwrap() == Command::Note(Some(3), String::from("hi")));
+        assert!(request_from_json(current_time,
+                                  r#"{"version": 1, "command": "edit", "id": 3,
+                                      "edits": {"work_end_time": null, "tags": ["foo", "bar"]}}"#)
+            .unwrap() ==
+                Command::Edit(3,
+                              vec![PomodoroEdit::Tags(vec![String::from("foo"), String::from("bar")]),
+                                   PomodoroEdit::WorkEndTime(None)]));
+        assert!(request_from_json(current_time, r#"{"version": 1, "command": "undo"}"#).unwrap() ==
+                Command::Undo);
+        assert!(request_from_json(current_time, r#"{"version": 1, "command": "subscribe"}"#).unwrap() ==
+                Command::Subscribe);
+    }
+
+    #[test]
+    fn refuses_malformed_requests_and_other_versions() {
+        let current_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let code = |message: &str| request_from_json(current_time, message).unwrap_err().code();
+
+        assert!(code(r#"{"version": 2, "command": "stop"}"#) == "INVALID_COMMAND");
+        assert!(code(r#"{"command": "stop"}"#) == "INVALID_COMMAND");
+        assert!(code(r#"{"version": 1, "command": "launch"}"#) == "INVALID_COMMAND");
+        assert!(code(r#"{"version": 1, "command": "extend", "length": "long"}"#) == "INVALID_COMMAND");
+        assert!(code(r#"{"version": 1, "command": "log", "start_time": "2000-01-01T00:00:00+00:00",
+                         "work_length": 60, "break_length": 30, "status": "Paused"}"#) ==
+                "INVALID_COMMAND");
+        assert!(code(r#"{"version": 1, "command": "delete", "id": 4294967297}"#) == "INVALID_COMMAND");
+        assert!(code(r#"{"version": 1"#) == "INVALID_COMMAND");
+    }
+
+    #[test]
+    fn writes_remaining_times_into_the_payload() {
+        let response = Ok(Response::Status {
+            pomodoro: pomodoro(),
+            work_remaining: Duration::seconds(1200),
+            break_remaining: Duration::seconds(300),
+            cycle_length: 4,
+        });
+
+        let json = response_to_json(&response);
+
+        assert!(json["version"] == Value::from(PROTOCOL_VERSION));
+        assert!(json["status"] == Value::from("ok"));
+        assert!(json["code"] == Value::Null);
+        assert!(json["message"] == Value::from("20:00 | 05:00 | 2/4"));
+        assert!(json["payload"]["work_remaining"] == Value::from(1200));
+        assert!(json["payload"]["cycle_position"] == Value::from(2));
+        assert!(pomodoro_json::from_json(&json["payload"]["pomodoro"]).unwrap() == pomodoro());
+    }
+
+    #[test]
+    fn writes_errors_with_their_code() {
+        let json = response_to_json(&Err(Error::NotFoundError(String::from("No pomodoro to stop."))));
+
+        assert!(json["status"] == Value::from("error"));
+        assert!(json["code"] == Value::from("NOT_FOUND"));
+        assert!(json["message"] == Value::from("No pomodoro to stop."));
+        assert!(json["payload"] == Value::Null);
+    }
+
+    #[test]
+    fn writes_events_with_the_pomodoro_they_happened_to() {
+        let event = PomodoroEvent {
+            kind: PomodoroEventKind::BreakPending,
+            pomodoro: pomodoro(),
+        };
+
+        let json = event_to_json(&event);
+
+        assert!(json["version"] == Value::from(PROTOCOL_VERSION));
+        assert!(json["event"] == Value::from("break_pending"));
+        assert!(pomodoro_json::from_json(&json["pomodoro"]).unwrap() == pomodoro());
+    }
+}
@@ -0,0 +1,92 @@
+use daemon::chrono::Duration;
+
+use daemon::pomodoro::InterruptionKind;
+use daemon::pomodoro::Period;
+use daemon::pomodoro::Pomodoro;
+
+use std::fmt;
+
+/// Number of characters of a pomodoro's notes shown by LIST.
+const LISTED_NOTES_LENGTH: usize = 40;
+
+/// What a command produced. Text clients are sent its Display form; JSON clients get the
+/// records and times themselves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Response {
+    /// A change was made. Carries the pomodoro as it now stands, unless there is none left to
+    /// show, as after a DELETE.
+    Done(String, Option<Pomodoro>),
+    /// The most recent pomodoros, most recent first, along with the length of a cycle.
+    List(Vec<Pomodoro>, i32),
+    /// Time left in the work period and break of the most recent pomodoro.
+    Status {
+        pomodoro: Pomodoro,
+        work_remaining: Duration,
+        break_remaining: Duration,
+        cycle_length: i32,
+    },
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Response::Done(ref message, _) => write!(f, "{}", message),
+            Response::List(ref pomodoros, cycle_length) => {
+                for pomodoro in pomodoros {
+                    try!(write!(f,
+                                "#{} [{}]: {} {}/{} ({}) interruptions: {} internal, {} external{}\n",
+                                pomodoro.id,
+                                pomodoro.work_start_time.format("%F %H:%M:%S").to_string(),
+                                pomodoro.status,
+                                pomodoro.cycle_position,
+                                cycle_length,
+                                pomodoro.tags.join(","),
+                                pomodoro.interruption_count(InterruptionKind::Internal),
+                                pomodoro.interruption_count(InterruptionKind::External),
+                                notes_suffix(&pomodoro.notes)));
+                }
+                Ok(())
+            }
+            Response::Status { ref pomodoro, work_remaining, break_remaining, cycle_length } => {
+                write!(f,
+                       "{}{} | {}{} | {}/{}",
+                       format_time(work_remaining),
+                       extension_suffix(pomodoro.extended_time(Period::Work)),
+                       format_time(break_remaining),
+                       extension_suffix(pomodoro.extended_time(Period::Break)),
+                       pomodoro.cycle_position,
+                       cycle_length)
+            }
+        }
+    }
+}
+
+pub fn format_time(time: Duration) -> String {
+    let minutes = time.num_minutes();
+    let seconds = time.num_seconds() - minutes * 60;
+    format!("{:02}:{:02}", minutes, seconds)
+}
+
+/// Shows how much longer a period has been made, if at all.
+fn extension_suffix(extended_time: Duration) -> String {
+    if extended_time == Duration::zero() {
+        String::new()
+    } else {
+        format!(" (+{})", format_time(extended_time))
+    }
+}
+
+/// Shows the notes of a pomodoro on a single line, cut short if they run long.
+fn notes_suffix(notes: &Option<String>) -> String {
+    match *notes {
+        None => String::new(),
+        Some(ref notes) => {
+            let line = notes.lines().collect::<Vec<&str>>().join(" / ");
+            if line.chars().count() > LISTED_NOTES_LENGTH {
+                format!(" notes: {}...", line.chars().take(LISTED_NOTES_LENGTH).collect::<String>())
+            } else {
+                format!(" notes: {}", line)
+            }
+        }
+    }
+}
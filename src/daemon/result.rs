@@ -11,27 +11,60 @@ pub type Result<T> = result::Result<T, Error>;
 
 #[derive(Debug)]
 pub enum Error {
+    ConfigError(String),
     DbConnectError(daemon::postgres::error::ConnectError),
     DbError(daemon::postgres::error::Error),
     FailedStopError(channel::SendError<bool>),
     GenericError(String),
+    InvalidCommandError(String),
+    InvalidStateError(String),
     IoError(io::Error),
+    JsonError(daemon::serde_json::Error),
+    NotFoundError(String),
+    SqliteError(daemon::rusqlite::Error),
     CommandFromUtf8Error(FromUtf8Error),
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
         match *self {
+            Error::ConfigError(ref e) => write!(f, "Configuration error: {}", e),
             Error::DbConnectError(ref e) => write!(f, "Database connection error: {}", e),
             Error::DbError(ref e) => write!(f, "Database error: {}", e),
             Error::FailedStopError(_) => write!(f, "Failed to stop polling for events."),
             Error::GenericError(ref e) => write!(f, "{}", e),
+            Error::InvalidCommandError(ref e) => write!(f, "{}", e),
+            Error::InvalidStateError(ref e) => write!(f, "{}", e),
             Error::IoError(ref e) => write!(f, "IO error: {}", e),
+            Error::JsonError(ref e) => write!(f, "JSON error: {}", e),
+            Error::NotFoundError(ref e) => write!(f, "{}", e),
+            Error::SqliteError(ref e) => write!(f, "SQLite error: {}", e),
             Error::CommandFromUtf8Error(ref e) => write!(f, "Could not parse command from UTF-8: {}", e)
         }
     }
 }
 
+impl Error {
+    /// A short, stable name for the kind of error, sent to clients ahead of the message so that
+    /// they can tell a bad request from missing data or a storage failure.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            Error::InvalidCommandError(_) |
+            Error::CommandFromUtf8Error(_) => "INVALID_COMMAND",
+            Error::NotFoundError(_) => "NOT_FOUND",
+            Error::InvalidStateError(_) => "INVALID_STATE",
+            Error::DbConnectError(_) |
+            Error::DbError(_) |
+            Error::IoError(_) |
+            Error::JsonError(_) |
+            Error::SqliteError(_) => "STORAGE",
+            Error::ConfigError(_) |
+            Error::FailedStopError(_) |
+            Error::GenericError(_) => "INTERNAL",
+        }
+    }
+}
+
 impl From<daemon::postgres::error::ConnectError> for Error {
     fn from(err: daemon::postgres::error::ConnectError) -> Error {
         Error::DbConnectError(err)
@@ -50,6 +83,18 @@ impl From<io::Error> for Error {
     }
 }
 
+impl From<daemon::serde_json::Error> for Error {
+    fn from(err: daemon::serde_json::Error) -> Error {
+        Error::JsonError(err)
+    }
+}
+
+impl From<daemon::rusqlite::Error> for Error {
+    fn from(err: daemon::rusqlite::Error) -> Error {
+        Error::SqliteError(err)
+    }
+}
+
 impl From<channel::SendError<bool>> for Error {
     fn from(err: channel::SendError<bool>) -> Error {
         Error::FailedStopError(err)
@@ -0,0 +1,923 @@
+use daemon::chrono::Duration;
+use daemon::chrono::datetime::DateTime;
+use daemon::chrono::offset::TimeZone;
+use daemon::chrono::offset::utc::UTC;
+
+use daemon::migrations::SQLITE_MIGRATIONS;
+use daemon::pomodoro::Extension;
+use daemon::pomodoro::Interruption;
+use daemon::pomodoro::InterruptionKind;
+use daemon::pomodoro::Pause;
+use daemon::pomodoro::Period;
+use daemon::pomodoro::Pomodoro;
+use daemon::pomodoro::PomodoroStatus;
+use daemon::pomodoros::{PomodoroQuery, Pomodoros};
+
+use daemon::rusqlite;
+use daemon::rusqlite::types::ToSql;
+
+use daemon::result::Error;
+use daemon::result::Result;
+
+use std::collections::HashMap;
+use std::fs;
+use std::option::Option;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+/// Stores pomodoros in a single SQLite file, migrating the schema whenever it is opened.
+/// Timestamps are kept as milliseconds since the Unix epoch, and tags and pauses live in their
+/// own tables.
+pub struct SqlitePomodoroQueryMapper {
+    connection: Mutex<rusqlite::Connection>,
+}
+
+/// The view of the database given to the work of a transaction.
+struct SqliteTransaction<'a> {
+    conn: &'a rusqlite::Connection,
+}
+
+impl SqlitePomodoroQueryMapper {
+    pub fn open(path: &Path) -> Result<SqlitePomodoroQueryMapper> {
+        if let Some(parent) = path.parent() {
+            try!(fs::create_dir_all(parent));
+        }
+
+        let connection = try!(rusqlite::Connection::open(path));
+        SqlitePomodoroQueryMapper::with_connection(connection)
+    }
+
+    pub fn open_in_memory() -> Result<SqlitePomodoroQueryMapper> {
+        let connection = try!(rusqlite::Connection::open_in_memory());
+        SqlitePomodoroQueryMapper::with_connection(connection)
+    }
+
+    fn with_connection(mut connection: rusqlite::Connection) -> Result<SqlitePomodoroQueryMapper> {
+        try!(migrate(&mut connection));
+
+        Ok(SqlitePomodoroQueryMapper { connection: Mutex::new(connection) })
+    }
+
+    pub fn create_pomodoro(&self,
+                           start_time: DateTime<UTC>,
+                           work_duration: Duration,
+                           break_duration: Duration,
+                           tags: Vec<String>)
+                           -> Result<()> {
+        self.transaction(&mut |pomodoros: &Pomodoros| {
+            pomodoros.create(start_time, work_duration, break_duration, tags.clone())
+        })
+    }
+
+    pub fn get_most_recent_pomodoro(&self) -> Result<Option<Pomodoro>> {
+        self.list_most_recent_pomodoros(1).map(|mut pomodoros| {
+            if pomodoros.is_empty() {
+                None
+            } else {
+                Some(pomodoros.remove(0))
+            }
+        })
+    }
+
+    pub fn list_most_recent_pomodoros(&self, limit: usize) -> Result<Vec<Pomodoro>> {
+        self.query_pomodoros(&PomodoroQuery::most_recent(limit))
+    }
+
+    pub fn query_pomodoros(&self, query: &PomodoroQuery) -> Result<Vec<Pomodoro>> {
+        let conn = try!(self.acquire_connection());
+        select_pomodoros(&conn, query)
+    }
+
+    pub fn find_pomodoro(&self, id: i32) -> Result<Option<Pomodoro>> {
+        let conn = try!(self.acquire_connection());
+        select_pomodoro(&conn, id)
+    }
+
+    /// Updates the pomodoro along with its tags and pauses in one transaction.
+    pub fn update_pomodoro(&self, id: i32, pomodoro: Pomodoro) -> Result<()> {
+        self.transaction(&mut |pomodoros: &Pomodoros| pomodoros.update(id, pomodoro.clone()))
+    }
+
+    /// Inserts a complete pomodoro along with its tags and pauses in one transaction.
+    pub fn insert_pomodoro(&self, pomodoro: Pomodoro) -> Result<i32> {
+        let mut id = 0;
+        try!(self.transaction(&mut |pomodoros: &Pomodoros| {
+            id = try!(pomodoros.insert(pomodoro.clone()));
+            Ok(())
+        }));
+        Ok(id)
+    }
+
+    /// Deletes the pomodoro along with its tags and pauses in one transaction.
+    pub fn delete_pomodoro(&self, id: i32) -> Result<()> {
+        self.transaction(&mut |pomodoros: &Pomodoros| pomodoros.delete(id))
+    }
+
+    /// Restores the pomodoro along with its tags and pauses in one transaction.
+    pub fn restore_pomodoro(&self, pomodoro: Pomodoro) -> Result<()> {
+        self.transaction(&mut |pomodoros: &Pomodoros| pomodoros.restore(pomodoro.clone()))
+    }
+
+    fn acquire_connection(&self) -> Result<MutexGuard<rusqlite::Connection>> {
+        self.connection
+            .lock()
+            .map_err(|_| Error::from(String::from("SQLite connection lock was poisoned")))
+    }
+}
+
+impl Pomodoros for SqlitePomodoroQueryMapper {
+    fn create(&self,
+              start_time: DateTime<UTC>,
+              work_duration: Duration,
+              break_duration: Duration,
+              tags: Vec<String>)
+              -> Result<()> {
+        self.create_pomodoro(start_time, work_duration, break_duration, tags)
+    }
+
+    fn last(&self, count: usize) -> Result<Vec<Pomodoro>> {
+        self.list_most_recent_pomodoros(count)
+    }
+
+    fn query(&self, query: PomodoroQuery) -> Result<Vec<Pomodoro>> {
+        self.query_pomodoros(&query)
+    }
+
+    fn most_recent(&self) -> Result<Option<Pomodoro>> {
+        self.get_most_recent_pomodoro()
+    }
+
+    fn find(&self, id: i32) -> Result<Option<Pomodoro>> {
+        self.find_pomodoro(id)
+    }
+
+    fn update(&self, id: i32, pomodoro: Pomodoro) -> Result<()> {
+        self.update_pomodoro(id, pomodoro)
+    }
+
+    fn insert(&self, pomodoro: Pomodoro) -> Result<i32> {
+        self.insert_pomodoro(pomodoro)
+    }
+
+    fn delete(&self, id: i32) -> Result<()> {
+        self.delete_pomodoro(id)
+    }
+
+    fn restore(&self, pomodoro: Pomodoro) -> Result<()> {
+        self.restore_pomodoro(pomodoro)
+    }
+
+    /// Runs `work` in an IMMEDIATE transaction, which takes the write lock up front so that
+    /// another process cannot change the file between what `work` reads and what it writes.
+    fn transaction(&self, work: &mut FnMut(&Pomodoros) -> Result<()>) -> Result<()> {
+        let mut conn = try!(self.acquire_connection());
+        let transaction = try!(conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate));
+
+        try!(work(&SqliteTransaction { conn: &transaction }));
+        transaction.commit().map_err(|e| Error::from(e))
+    }
+}
+
+impl<'a> Pomodoros for SqliteTransaction<'a> {
+    fn create(&self,
+              start_time: DateTime<UTC>,
+              work_duration: Duration,
+              break_duration: Duration,
+              tags: Vec<String>)
+              -> Result<()> {
+        insert_pomodoro(self.conn, start_time, work_duration, break_duration, tags).map(|_| ())
+    }
+
+    fn last(&self, count: usize) -> Result<Vec<Pomodoro>> {
+        select_pomodoros(self.conn, &PomodoroQuery::most_recent(count))
+    }
+
+    fn query(&self, query: PomodoroQuery) -> Result<Vec<Pomodoro>> {
+        select_pomodoros(self.conn, &query)
+    }
+
+    fn most_recent(&self) -> Result<Option<Pomodoro>> {
+        self.last(1).map(|mut pomodoros| pomodoros.pop())
+    }
+
+    fn find(&self, id: i32) -> Result<Option<Pomodoro>> {
+        select_pomodoro(self.conn, id)
+    }
+
+    fn update(&self, id: i32, pomodoro: Pomodoro) -> Result<()> {
+        update_pomodoro_row(self.conn, id, pomodoro)
+    }
+
+    fn insert(&self, pomodoro: Pomodoro) -> Result<i32> {
+        insert_pomodoro_record(self.conn, pomodoro)
+    }
+
+    fn delete(&self, id: i32) -> Result<()> {
+        delete_pomodoro_row(self.conn, id)
+    }
+
+    fn restore(&self, pomodoro: Pomodoro) -> Result<()> {
+        restore_pomodoro_row(self.conn, pomodoro)
+    }
+
+    fn transaction(&self, work: &mut FnMut(&Pomodoros) -> Result<()>) -> Result<()> {
+        work(self)
+    }
+}
+
+/// Applies each migration newer than the file's user_version in its own transaction, recording
+/// the new version in the same transaction.
+fn migrate(conn: &mut rusqlite::Connection) -> Result<()> {
+    let version: i32 = try!(conn.query_row("PRAGMA user_version", rusqlite::NO_PARAMS, |row| row.get(0)));
+
+    for migration in SQLITE_MIGRATIONS.iter().filter(|migration| migration.version > version) {
+        let transaction = try!(conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate));
+        try!(transaction.execute_batch(migration.sql).map_err(|e| {
+            Error::from(format!("Migration {} ({}) failed: {}",
+                                migration.version,
+                                migration.description,
+                                e))
+        }));
+        try!(transaction.execute_batch(&format!("PRAGMA user_version = {}", migration.version)));
+        try!(transaction.commit());
+
+        info!("Applied migration {}: {}", migration.version, migration.description);
+    }
+    Ok(())
+}
+
+fn insert_pomodoro(conn: &rusqlite::Connection,
+                   start_time: DateTime<UTC>,
+                   work_duration: Duration,
+                   break_duration: Duration,
+                   tags: Vec<String>)
+                   -> Result<i64> {
+    try!(conn.execute("INSERT INTO pomodoros(
+            work_start_time,
+            work_end_time,
+            break_start_time,
+            break_end_time,
+            work_length,
+            break_length,
+            status
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                 &[&to_millis(start_time) as &ToSql,
+                   &None as &Option<i64>,
+                   &None as &Option<i64>,
+                   &None as &Option<i64>,
+                   &work_duration.num_seconds(),
+                   &break_duration.num_seconds(),
+                   &PomodoroStatus::InProgress.to_string()])
+        .map_err(|e| {
+            error!("{}", e);
+            Error::from(e)
+        }));
+
+    let id = conn.last_insert_rowid();
+    try!(replace_tags(conn, id, &tags));
+    Ok(id)
+}
+
+/// Inserts a complete record by creating a row for it and then filling the row in.
+fn insert_pomodoro_record(conn: &rusqlite::Connection, pomodoro: Pomodoro) -> Result<i32> {
+    let id = try!(insert_pomodoro(conn,
+                                  pomodoro.work_start_time,
+                                  pomodoro.work_length,
+                                  pomodoro.break_length,
+                                  vec![])) as i32;
+    try!(update_pomodoro_row(conn, id, pomodoro));
+    Ok(id)
+}
+
+/// Runs the query with a cached statement per combination of filters. SQLite indexes end in
+/// the rowid, so pomodoros_work_start_time_idx also orders ties by id.
+fn select_pomodoros(conn: &rusqlite::Connection, query: &PomodoroQuery) -> Result<Vec<Pomodoro>> {
+    let limit = query.limit as i64;
+    let offset = query.offset as i64;
+    let since = query.since.map(to_millis);
+    let until = query.until.map(to_millis);
+    let mut params: Vec<&ToSql> = vec![&limit as &ToSql, &offset];
+    let mut conditions: Vec<String> = Vec::new();
+
+    if let Some(ref since) = since {
+        params.push(since);
+        conditions.push(format!("work_start_time >= ?{}", params.len()));
+    }
+    if let Some(ref until) = until {
+        params.push(until);
+        conditions.push(format!("work_start_time < ?{}", params.len()));
+    }
+
+    select_where(conn, conditions, params)
+}
+
+fn select_pomodoro(conn: &rusqlite::Connection, id: i32) -> Result<Option<Pomodoro>> {
+    let limit = 1 as i64;
+    let offset = 0 as i64;
+    let id = id as i64;
+
+    select_where(conn, vec![String::from("id = ?3")], vec![&limit as &ToSql, &offset, &id])
+        .map(|mut pomodoros| pomodoros.pop())
+}
+
+/// Selects the pomodoros meeting every condition, along with their tags, pauses, extensions
+/// and interruptions. The first two parameters are the limit and the offset. The details of the
+/// whole page are read with one query per table, each selecting the page again by id, since
+/// SQLite has no array parameters to pass the ids in.
+fn select_where(conn: &rusqlite::Connection,
+                conditions: Vec<String>,
+                params: Vec<&ToSql>)
+                -> Result<Vec<Pomodoro>> {
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+    let page = format!("FROM pomodoros {} ORDER BY work_start_time DESC, id DESC LIMIT ?1 OFFSET ?2",
+                       where_clause);
+    let sql = format!("SELECT id, work_start_time, work_end_time, break_start_time, \
+                       break_end_time, work_length, break_length, status, cycle_position, notes {}",
+                      page);
+
+    let mut statement = try!(conn.prepare_cached(&sql));
+    let rows = try!(statement.query_map(&params, pomodoro_from_row));
+    let mut pomodoros = try!(rows.collect::<rusqlite::Result<Vec<Pomodoro>>>()
+        .map_err(|e| {
+            error!("{}", e);
+            Error::from(e)
+        }));
+
+    if pomodoros.is_empty() {
+        return Ok(pomodoros);
+    }
+
+    let ids = format!("SELECT id {}", page);
+    let mut tags = try!(load_tags(conn, &ids, &params));
+    let mut pauses = try!(load_pauses(conn, &ids, &params));
+    let mut extensions = try!(load_extensions(conn, &ids, &params));
+    let mut interruptions = try!(load_interruptions(conn, &ids, &params));
+    for pomodoro in pomodoros.iter_mut() {
+        pomodoro.tags = tags.remove(&pomodoro.id).unwrap_or(vec![]);
+        pomodoro.pauses = pauses.remove(&pomodoro.id).unwrap_or(vec![]);
+        pomodoro.extensions = extensions.remove(&pomodoro.id).unwrap_or(vec![]);
+        pomodoro.interruptions = interruptions.remove(&pomodoro.id).unwrap_or(vec![]);
+    }
+    Ok(pomodoros)
+}
+
+fn update_pomodoro_row(conn: &rusqlite::Connection, id: i32, pomodoro: Pomodoro) -> Result<()> {
+    let updated = try!(conn.execute("UPDATE pomodoros SET
+                work_start_time = ?2,
+                work_end_time = ?3,
+                break_start_time = ?4,
+                break_end_time = ?5,
+                work_length = ?6,
+                break_length = ?7,
+                status = ?8,
+                cycle_position = ?9,
+                notes = ?10
+            WHERE id = ?1",
+                 &[&id as &ToSql,
+                   &to_millis(pomodoro.work_start_time),
+                   &pomodoro.work_end_time.map(to_millis),
+                   &pomodoro.break_start_time.map(to_millis),
+                   &pomodoro.break_end_time.map(to_millis),
+                   &pomodoro.work_length.num_seconds(),
+                   &pomodoro.break_length.num_seconds(),
+                   &pomodoro.status.to_string(),
+                   &pomodoro.cycle_position,
+                   &pomodoro.notes])
+        .map_err(|e| {
+            error!("{}", e);
+            Error::from(e)
+        }));
+
+    if updated == 0 {
+        return Ok(());
+    }
+    try!(replace_tags(conn, id as i64, &pomodoro.tags));
+    try!(replace_pauses(conn, id as i64, &pomodoro.pauses));
+    try!(replace_extensions(conn, id as i64, &pomodoro.extensions));
+    replace_interruptions(conn, id as i64, &pomodoro.interruptions)
+}
+
+/// Recreates the row of the pomodoro if it has been deleted, then fills the row in.
+fn restore_pomodoro_row(conn: &rusqlite::Connection, pomodoro: Pomodoro) -> Result<()> {
+    try!(conn.execute("INSERT OR IGNORE INTO pomodoros(id, work_start_time, work_length, break_length, status) \
+                       VALUES (?1, ?2, ?3, ?4, ?5)",
+                      &[&(pomodoro.id as i64) as &ToSql,
+                        &to_millis(pomodoro.work_start_time),
+                        &pomodoro.work_length.num_seconds(),
+                        &pomodoro.break_length.num_seconds(),
+                        &pomodoro.status.to_string()]));
+    let id = pomodoro.id;
+    update_pomodoro_row(conn, id, pomodoro)
+}
+
+/// Deletes the details of the pomodoro before its row. Foreign keys are not enforced on the
+/// connection, so nothing would cascade.
+fn delete_pomodoro_row(conn: &rusqlite::Connection, id: i32) -> Result<()> {
+    let id = id as i64;
+    let tables = ["pomodoro_tags", "pomodoro_pauses", "pomodoro_extensions", "pomodoro_interruptions"];
+    for table in tables.iter() {
+        try!(conn.execute(&format!("DELETE FROM {} WHERE pomodoro_id = ?1", table), &[&id]));
+    }
+    try!(conn.execute("DELETE FROM pomodoros WHERE id = ?1", &[&id]));
+    Ok(())
+}
+
+fn pomodoro_from_row(row: &rusqlite::Row) -> rusqlite::Result<Pomodoro> {
+    let id: i64 = try!(row.get(0));
+    let work_start_time: i64 = try!(row.get(1));
+    let work_end_time: Option<i64> = try!(row.get(2));
+    let break_start_time: Option<i64> = try!(row.get(3));
+    let break_end_time: Option<i64> = try!(row.get(4));
+    let work_length: i64 = try!(row.get(5));
+    let break_length: i64 = try!(row.get(6));
+    let status: String = try!(row.get(7));
+    let cycle_position: i32 = try!(row.get(8));
+    let notes: Option<String> = try!(row.get(9));
+
+    Ok(Pomodoro {
+        id: id as i32,
+        work_start_time: from_millis(work_start_time),
+        work_end_time: work_end_time.map(from_millis),
+        break_start_time: break_start_time.map(from_millis),
+        break_end_time: break_end_time.map(from_millis),
+        work_length: Duration::seconds(work_length),
+        break_length: Duration::seconds(break_length),
+        status: PomodoroStatus::from(status),
+        tags: vec![],
+        pauses: vec![],
+        cycle_position: cycle_position,
+        extensions: vec![],
+        interruptions: vec![],
+        notes: notes,
+    })
+}
+
+/// Loads the tags of each pomodoro selected by `ids`, keyed by the id of the pomodoro they
+/// belong to.
+fn load_tags(conn: &rusqlite::Connection,
+             ids: &str,
+             params: &Vec<&ToSql>)
+             -> Result<HashMap<i32, Vec<String>>> {
+    let mut statement = try!(conn.prepare_cached(&format!("SELECT pomodoro_id, tag FROM pomodoro_tags \
+                                                           WHERE pomodoro_id IN ({}) ORDER BY \
+                                                           position",
+                                                          ids)));
+    let rows = try!(statement.query_map(params, |row| Ok((try!(row.get(0)), try!(row.get(1))))));
+
+    by_pomodoro(rows)
+}
+
+fn replace_tags(conn: &rusqlite::Connection, pomodoro_id: i64, tags: &Vec<String>) -> Result<()> {
+    try!(conn.execute("DELETE FROM pomodoro_tags WHERE pomodoro_id = ?1", &[&pomodoro_id]));
+    for (position, tag) in tags.iter().enumerate() {
+        try!(conn.execute("INSERT INTO pomodoro_tags(pomodoro_id, position, tag) VALUES (?1, ?2, ?3)",
+                          &[&pomodoro_id as &ToSql, &(position as i64), tag]));
+    }
+    Ok(())
+}
+
+/// Loads the pauses of each pomodoro selected by `ids`, keyed by the id of the pomodoro they
+/// belong to.
+fn load_pauses(conn: &rusqlite::Connection,
+               ids: &str,
+               params: &Vec<&ToSql>)
+               -> Result<HashMap<i32, Vec<Pause>>> {
+    let mut statement = try!(conn.prepare_cached(&format!("SELECT pomodoro_id, start_time, end_time \
+                                                           FROM pomodoro_pauses WHERE pomodoro_id \
+                                                           IN ({}) ORDER BY start_time",
+                                                          ids)));
+    let rows = try!(statement.query_map(params, |row| {
+        let start_time: i64 = try!(row.get(1));
+        let end_time: Option<i64> = try!(row.get(2));
+        Ok((try!(row.get(0)),
+            Pause {
+                start_time: from_millis(start_time),
+                end_time: end_time.map(from_millis),
+            }))
+    }));
+
+    by_pomodoro(rows)
+}
+
+fn replace_pauses(conn: &rusqlite::Connection, pomodoro_id: i64, pauses: &Vec<Pause>) -> Result<()> {
+    try!(conn.execute("DELETE FROM pomodoro_pauses WHERE pomodoro_id = ?1", &[&pomodoro_id]));
+    for pause in pauses.iter() {
+        try!(conn.execute("INSERT INTO pomodoro_pauses(pomodoro_id, start_time, end_time) \
+                           VALUES (?1, ?2, ?3)",
+                          &[&pomodoro_id as &ToSql,
+                            &to_millis(pause.start_time),
+                            &pause.end_time.map(to_millis)]));
+    }
+    Ok(())
+}
+
+/// Loads the extensions of each pomodoro selected by `ids`, keyed by the id of the pomodoro
+/// they belong to.
+fn load_extensions(conn: &rusqlite::Connection,
+                   ids: &str,
+                   params: &Vec<&ToSql>)
+                   -> Result<HashMap<i32, Vec<Extension>>> {
+    let mut statement = try!(conn.prepare_cached(&format!("SELECT pomodoro_id, time, period, length \
+                                                           FROM pomodoro_extensions WHERE \
+                                                           pomodoro_id IN ({}) ORDER BY time",
+                                                          ids)));
+    let rows = try!(statement.query_map(params, |row| {
+        let time: i64 = try!(row.get(1));
+        let period: String = try!(row.get(2));
+        let length: i64 = try!(row.get(3));
+        Ok((try!(row.get(0)),
+            Extension {
+                time: from_millis(time),
+                period: Period::from(period),
+                length: Duration::seconds(length),
+            }))
+    }));
+
+    by_pomodoro(rows)
+}
+
+fn replace_extensions(conn: &rusqlite::Connection,
+                      pomodoro_id: i64,
+                      extensions: &Vec<Extension>)
+                      -> Result<()> {
+    try!(conn.execute("DELETE FROM pomodoro_extensions WHERE pomodoro_id = ?1", &[&pomodoro_id]));
+    for extension in extensions.iter() {
+        try!(conn.execute("INSERT INTO pomodoro_extensions(pomodoro_id, time, period, length) \
+                           VALUES (?1, ?2, ?3, ?4)",
+                          &[&pomodoro_id as &ToSql,
+                            &to_millis(extension.time),
+                            &extension.period.to_string(),
+                            &extension.length.num_seconds()]));
+    }
+    Ok(())
+}
+
+/// Loads the interruptions of each pomodoro selected by `ids`, keyed by the id of the pomodoro
+/// they belong to.
+fn load_interruptions(conn: &rusqlite::Connection,
+                      ids: &str,
+                      params: &Vec<&ToSql>)
+                      -> Result<HashMap<i32, Vec<Interruption>>> {
+    let mut statement = try!(conn.prepare_cached(&format!("SELECT pomodoro_id, time, kind, note \
+                                                           FROM pomodoro_interruptions WHERE \
+                                                           pomodoro_id IN ({}) ORDER BY time",
+                                                          ids)));
+    let rows = try!(statement.query_map(params, |row| {
+        let time: i64 = try!(row.get(1));
+        let kind: String = try!(row.get(2));
+        Ok((try!(row.get(0)),
+            Interruption {
+                time: from_millis(time),
+                kind: InterruptionKind::from(kind),
+                note: try!(row.get(3)),
+            }))
+    }));
+
+    by_pomodoro(rows)
+}
+
+/// Groups the details read for a page of pomodoros by the pomodoro they belong to, keeping
+/// them in the order they were read.
+fn by_pomodoro<T, I>(rows: I) -> Result<HashMap<i32, Vec<T>>>
+    where I: Iterator<Item = rusqlite::Result<(i64, T)>>
+{
+    let mut details: HashMap<i32, Vec<T>> = HashMap::new();
+    for row in rows {
+        let (pomodoro_id, detail) = try!(row);
+        details.entry(pomodoro_id as i32).or_insert(vec![]).push(detail);
+    }
+    Ok(details)
+}
+
+fn replace_interruptions(conn: &rusqlite::Connection,
+                         pomodoro_id: i64,
+                         interruptions: &Vec<Interruption>)
+                         -> Result<()> {
+    try!(conn.execute("DELETE FROM pomodoro_interruptions WHERE pomodoro_id = ?1", &[&pomodoro_id]));
+    for interruption in interruptions.iter() {
+        try!(conn.execute("INSERT INTO pomodoro_interruptions(pomodoro_id, time, kind, note) \
+                           VALUES (?1, ?2, ?3, ?4)",
+                          &[&pomodoro_id as &ToSql,
+                            &to_millis(interruption.time),
+                            &interruption.kind.to_string(),
+                            &interruption.note]));
+    }
+    Ok(())
+}
+
+fn to_millis(time: DateTime<UTC>) -> i64 {
+    time.timestamp() * 1000 + time.timestamp_subsec_millis() as i64
+}
+
+fn from_millis(millis: i64) -> DateTime<UTC> {
+    UTC.timestamp(millis / 1000, ((millis % 1000) * 1_000_000) as u32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn returns_nothing_when_no_pomodoros_exist() {
+        let mapper = SqlitePomodoroQueryMapper::open_in_memory().unwrap();
+
+        assert!(mapper.get_most_recent_pomodoro().unwrap() == None);
+    }
+
+    #[test]
+    fn lists_pomodoros_most_recent_first() {
+        let mapper = SqlitePomodoroQueryMapper::open_in_memory().unwrap();
+        let first_start = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let second_start = "2000-01-01T01:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        mapper.create_pomodoro(second_start, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+        mapper.create_pomodoro(first_start, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+
+        let pomodoros = mapper.list_most_recent_pomodoros(5).unwrap();
+
+        assert!(pomodoros.len() == 2);
+        assert!(pomodoros[0].work_start_time == second_start);
+        assert!(pomodoros[1].work_start_time == first_start);
+    }
+
+    #[test]
+    fn pages_through_pomodoros_within_a_time_range() {
+        let mapper = SqlitePomodoroQueryMapper::open_in_memory().unwrap();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        for hour in 0..5 {
+            mapper.create_pomodoro(start_time + Duration::hours(hour),
+                                 Duration::seconds(5),
+                                 Duration::seconds(5),
+                                 vec![])
+                .unwrap();
+        }
+        let query = PomodoroQuery {
+            limit: 2,
+            offset: 1,
+            since: Some(start_time + Duration::hours(1)),
+            until: Some(start_time + Duration::hours(4)),
+        };
+
+        let pomodoros = mapper.query_pomodoros(&query).unwrap();
+
+        assert!(pomodoros.iter().map(|p| p.work_start_time).collect::<Vec<DateTime<UTC>>>() ==
+                vec![start_time + Duration::hours(2), start_time + Duration::hours(1)]);
+    }
+
+    #[test]
+    fn gives_each_pomodoro_on_a_page_its_own_details() {
+        let mapper = SqlitePomodoroQueryMapper::open_in_memory().unwrap();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        for (hour, tag) in vec!["one", "two", "three"].into_iter().enumerate() {
+            mapper.create_pomodoro(start_time + Duration::hours(hour as i64),
+                                 Duration::seconds(5),
+                                 Duration::seconds(5),
+                                 vec![String::from(tag)])
+                .unwrap();
+        }
+        for id in vec![1, 3] {
+            let mut pomodoro = mapper.find_pomodoro(id).unwrap().unwrap();
+            pomodoro.pauses = vec![Pause {
+                                       start_time: pomodoro.work_start_time + Duration::seconds(1),
+                                       end_time: Some(pomodoro.work_start_time + Duration::seconds(2)),
+                                   }];
+            mapper.update_pomodoro(id, pomodoro).unwrap();
+        }
+        let query = PomodoroQuery {
+            limit: 2,
+            offset: 1,
+            since: None,
+            until: None,
+        };
+
+        let pomodoros = mapper.query_pomodoros(&query).unwrap();
+
+        assert!(pomodoros.iter().map(|p| p.id).collect::<Vec<i32>>() == vec![2, 1]);
+        assert!(pomodoros[0].tags == vec![String::from("two")]);
+        assert!(pomodoros[0].pauses.is_empty());
+        assert!(pomodoros[1].tags == vec![String::from("one")]);
+        assert!(pomodoros[1].pauses ==
+                vec![Pause {
+                         start_time: start_time + Duration::seconds(1),
+                         end_time: Some(start_time + Duration::seconds(2)),
+                     }]);
+    }
+
+    #[test]
+    fn stores_tags_as_a_list() {
+        let mapper = SqlitePomodoroQueryMapper::open_in_memory().unwrap();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let tags = vec![String::from("foo"), String::from("bar")];
+        mapper.create_pomodoro(start_time, Duration::seconds(5), Duration::seconds(5), tags.clone())
+            .unwrap();
+
+        assert!(mapper.get_most_recent_pomodoro().unwrap().unwrap().tags == tags);
+    }
+
+    #[test]
+    fn rolls_back_a_failed_transaction() {
+        let mapper = SqlitePomodoroQueryMapper::open_in_memory().unwrap();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        mapper.create_pomodoro(start_time, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+        let original = mapper.get_most_recent_pomodoro().unwrap().unwrap();
+
+        let result = mapper.transaction(&mut |pomodoros: &Pomodoros| {
+            let mut aborted = original.clone();
+            aborted.status = PomodoroStatus::Aborted;
+            try!(pomodoros.update(aborted.id, aborted));
+            try!(pomodoros.create(start_time + Duration::seconds(10),
+                                  Duration::seconds(5),
+                                  Duration::seconds(5),
+                                  vec![String::from("foo")]));
+            Err(Error::from(String::from("interrupted")))
+        });
+
+        assert!(result.is_err());
+        assert!(mapper.list_most_recent_pomodoros(5).unwrap() == vec![original.clone()]);
+    }
+
+    #[test]
+    fn persists_pauses() {
+        let mapper = SqlitePomodoroQueryMapper::open_in_memory().unwrap();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        mapper.create_pomodoro(start_time, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+        let mut pomodoro = mapper.get_most_recent_pomodoro().unwrap().unwrap();
+        pomodoro.status = PomodoroStatus::Paused;
+        pomodoro.pauses = vec![Pause {
+                                   start_time: start_time + Duration::seconds(1),
+                                   end_time: Some(start_time + Duration::seconds(2)),
+                               },
+                               Pause {
+                                   start_time: start_time + Duration::seconds(3),
+                                   end_time: None,
+                               }];
+
+        mapper.update_pomodoro(pomodoro.id, pomodoro.clone()).unwrap();
+
+        assert!(mapper.get_most_recent_pomodoro().unwrap() == Some(pomodoro));
+    }
+
+    #[test]
+    fn persists_extensions() {
+        let mapper = SqlitePomodoroQueryMapper::open_in_memory().unwrap();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        mapper.create_pomodoro(start_time, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+        let mut pomodoro = mapper.get_most_recent_pomodoro().unwrap().unwrap();
+        pomodoro.work_length = Duration::seconds(65);
+        pomodoro.extensions = vec![Extension {
+                                       time: start_time + Duration::seconds(4),
+                                       period: Period::Work,
+                                       length: Duration::seconds(60),
+                                   }];
+
+        mapper.update_pomodoro(pomodoro.id, pomodoro.clone()).unwrap();
+
+        assert!(mapper.get_most_recent_pomodoro().unwrap() == Some(pomodoro));
+    }
+
+    #[test]
+    fn persists_interruptions() {
+        let mapper = SqlitePomodoroQueryMapper::open_in_memory().unwrap();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        mapper.create_pomodoro(start_time, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+        let mut pomodoro = mapper.get_most_recent_pomodoro().unwrap().unwrap();
+        pomodoro.interruptions = vec![Interruption {
+                                          time: start_time + Duration::seconds(1),
+                                          kind: InterruptionKind::Internal,
+                                          note: None,
+                                      },
+                                      Interruption {
+                                          time: start_time + Duration::seconds(2),
+                                          kind: InterruptionKind::External,
+                                          note: Some(String::from("phone")),
+                                      }];
+
+        mapper.update_pomodoro(pomodoro.id, pomodoro.clone()).unwrap();
+
+        assert!(mapper.get_most_recent_pomodoro().unwrap() == Some(pomodoro));
+    }
+
+    #[test]
+    fn persists_notes_and_finds_pomodoros_by_id() {
+        let mapper = SqlitePomodoroQueryMapper::open_in_memory().unwrap();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        mapper.create_pomodoro(start_time, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+        mapper.create_pomodoro(start_time + Duration::seconds(10),
+                             Duration::seconds(5),
+                             Duration::seconds(5),
+                             vec![])
+            .unwrap();
+        let mut pomodoro = mapper.find_pomodoro(1).unwrap().unwrap();
+        pomodoro.notes = Some(String::from("wrote the parser\nfixed a test"));
+
+        mapper.update_pomodoro(pomodoro.id, pomodoro.clone()).unwrap();
+
+        assert!(mapper.find_pomodoro(1).unwrap() == Some(pomodoro));
+        assert!(mapper.find_pomodoro(2).unwrap().unwrap().notes == None);
+        assert!(mapper.find_pomodoro(3).unwrap() == None);
+    }
+
+    #[test]
+    fn inserts_complete_records() {
+        let mapper = SqlitePomodoroQueryMapper::open_in_memory().unwrap();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        mapper.create_pomodoro(start_time, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+        let mut logged = mapper.get_most_recent_pomodoro().unwrap().unwrap();
+        logged.work_start_time = start_time - Duration::seconds(20);
+        logged.work_end_time = Some(start_time - Duration::seconds(15));
+        logged.break_start_time = Some(start_time - Duration::seconds(15));
+        logged.break_end_time = Some(start_time - Duration::seconds(10));
+        logged.tags = vec![String::from("offline")];
+        logged.status = PomodoroStatus::Completed;
+
+        let id = mapper.insert_pomodoro(logged.clone()).unwrap();
+
+        assert!(mapper.find_pomodoro(id).unwrap() == Some(Pomodoro { id: id, ..logged }));
+        assert!(mapper.get_most_recent_pomodoro().unwrap().unwrap().id == 1);
+    }
+
+    #[test]
+    fn deletes_a_pomodoro_along_with_its_details() {
+        let mapper = SqlitePomodoroQueryMapper::open_in_memory().unwrap();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let tags = vec![String::from("foo")];
+        mapper.create_pomodoro(start_time, Duration::seconds(5), Duration::seconds(5), tags).unwrap();
+        let mut pomodoro = mapper.get_most_recent_pomodoro().unwrap().unwrap();
+        pomodoro.pauses = vec![Pause {
+                                   start_time: start_time + Duration::seconds(1),
+                                   end_time: Some(start_time + Duration::seconds(2)),
+                               }];
+        mapper.update_pomodoro(pomodoro.id, pomodoro.clone()).unwrap();
+
+        mapper.delete_pomodoro(pomodoro.id).unwrap();
+
+        let conn = mapper.acquire_connection().unwrap();
+        let rows = |table: &str| -> i64 {
+            conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), rusqlite::NO_PARAMS, |row| row.get(0))
+                .unwrap()
+        };
+        assert!(select_pomodoro(&conn, pomodoro.id).unwrap() == None);
+        assert!(rows("pomodoro_tags") == 0);
+        assert!(rows("pomodoro_pauses") == 0);
+    }
+
+    #[test]
+    fn restores_a_deleted_pomodoro_under_its_own_id() {
+        let mapper = SqlitePomodoroQueryMapper::open_in_memory().unwrap();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        let tags = vec![String::from("foo")];
+        mapper.create_pomodoro(start_time, Duration::seconds(5), Duration::seconds(5), tags).unwrap();
+        let mut pomodoro = mapper.get_most_recent_pomodoro().unwrap().unwrap();
+        pomodoro.pauses = vec![Pause {
+                                   start_time: start_time + Duration::seconds(1),
+                                   end_time: Some(start_time + Duration::seconds(2)),
+                               }];
+        pomodoro.notes = Some(String::from("wrote the parser"));
+        mapper.update_pomodoro(pomodoro.id, pomodoro.clone()).unwrap();
+        mapper.delete_pomodoro(pomodoro.id).unwrap();
+
+        mapper.restore_pomodoro(pomodoro.clone()).unwrap();
+        mapper.restore_pomodoro(pomodoro.clone()).unwrap();
+
+        assert!(mapper.find_pomodoro(pomodoro.id).unwrap() == Some(pomodoro));
+        assert!(mapper.list_most_recent_pomodoros(5).unwrap().len() == 1);
+    }
+
+    #[test]
+    fn persists_updates_to_a_pomodoro() {
+        let mapper = SqlitePomodoroQueryMapper::open_in_memory().unwrap();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        mapper.create_pomodoro(start_time, Duration::seconds(5), Duration::seconds(5), vec![]).unwrap();
+        let mut pomodoro = mapper.get_most_recent_pomodoro().unwrap().unwrap();
+        pomodoro.work_end_time = Some("2000-01-01T00:00:01+00:00".parse::<DateTime<UTC>>().unwrap());
+        pomodoro.status = PomodoroStatus::Aborted;
+        pomodoro.tags = vec![String::from("baz")];
+        pomodoro.cycle_position = 3;
+
+        mapper.update_pomodoro(pomodoro.id, pomodoro.clone()).unwrap();
+
+        assert!(mapper.get_most_recent_pomodoro().unwrap() == Some(pomodoro));
+    }
+
+    #[test]
+    fn migrates_a_file_created_before_migrations_were_tracked() {
+        let connection = rusqlite::Connection::open_in_memory().unwrap();
+        connection.execute_batch(SQLITE_MIGRATIONS[0].sql).unwrap();
+        connection.execute("INSERT INTO pomodoros(work_start_time, work_length, break_length, status) \
+                            VALUES (0, 5, 5, 'Completed')",
+                     rusqlite::NO_PARAMS)
+            .unwrap();
+
+        let mapper = SqlitePomodoroQueryMapper::with_connection(connection).unwrap();
+        let version: i32 = mapper.acquire_connection()
+            .unwrap()
+            .query_row("PRAGMA user_version", rusqlite::NO_PARAMS, |row| row.get(0))
+            .unwrap();
+
+        assert!(version == SQLITE_MIGRATIONS.len() as i32);
+        assert!(mapper.get_most_recent_pomodoro().unwrap().unwrap().cycle_position == 1);
+    }
+}
@@ -8,13 +8,17 @@ mod spec {
 
     use std::process;
     use std::fs;
-    use std::io::Read;
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
     use std::path::Path;
 
     use self::nix::libc::pid_t;
     use self::nix::sys::signal;
     use self::nix::unistd::sleep;
 
+    // Drives target/debug/solanumd over the shared /tmp/solanum socket, so it needs a built
+    // daemon and none already running: `cargo build && cargo test --test acceptance_test --
+    // --ignored`.
     #[test]
     #[ignore]
     fn full_lifecycle_test() {
@@ -23,6 +27,9 @@ mod spec {
         client_can_start_a_pomodoro(&client);
         client_can_abort_a_pomodoro(&client);
         client_can_complete_a_pomodoro_work_period(&client);
+        client_can_send_several_commands_in_one_session(&client);
+        client_is_answered_while_another_is_stalled(&client);
+        client_is_sent_events_once_subscribed(&client);
         daemon_closes_listener_socket_on_sigterm();
     }
 
@@ -32,7 +39,7 @@ mod spec {
     }
 
     fn client_can_start_a_pomodoro(client: &client::Client) {
-        process::Command::new("target/debug/solanumd").spawn().unwrap();
+        process::Command::new("target/debug/solanumd").arg("--ephemeral").spawn().unwrap();
         sleep(1);
 
         let result = client.send_message(String::from("START"));
@@ -52,14 +59,44 @@ mod spec {
 
     fn client_can_complete_a_pomodoro_work_period(client: &client::Client) {
         client.send_message(String::from("START 1 1")).unwrap();
-        sleep(1);
-        client.send_message(String::from("STOP")).unwrap();
 
-        let list_response = client.send_message(String::from("LIST")).unwrap();
+        // The daemon ends the work period by itself once it runs out.
+        assert!(pomodoro_is_eventually_listed_as(client, "BreakPending"));
+    }
+
+    fn client_can_send_several_commands_in_one_session(client: &client::Client) {
+        let mut session = client.open_session().unwrap();
+
+        let status_response = session.send_message(String::from("STATUS")).unwrap();
+        let list_response = session.send_message(String::from("LIST")).unwrap();
 
+        assert!(!status_response.starts_with("ERROR "));
         assert!(list_response.contains("BreakPending"));
     }
 
+    fn client_is_answered_while_another_is_stalled(client: &client::Client) {
+        let mut stalled = UnixStream::connect("/tmp/solanum").unwrap();
+        stalled.write_all(&[0, 0]).unwrap();
+
+        let status_response = client.send_message(String::from("STATUS")).unwrap();
+
+        assert!(!status_response.starts_with("ERROR "));
+    }
+
+    fn client_is_sent_events_once_subscribed(client: &client::Client) {
+        let mut subscription = client.open_session().unwrap();
+        subscription.subscribe().unwrap();
+
+        client.send_message(String::from("START")).unwrap();
+
+        // Closing the previous pomodoro may come first, depending on how far it had got.
+        let mut event = subscription.receive_message().unwrap().unwrap();
+        if event.starts_with("completed ") {
+            event = subscription.receive_message().unwrap().unwrap();
+        }
+        assert!(event.starts_with("started #"));
+    }
+
     fn daemon_closes_listener_socket_on_sigterm() {
         let socket_path = Path::new("/tmp/solanum");
         let pidfile_path = Path::new("/tmp/solanum.pid");
@@ -89,4 +126,14 @@ mod spec {
     fn pomodoro_is_aborted(response: String) -> bool {
         response.contains("Pomodoro aborted")
     }
+
+    fn pomodoro_is_eventually_listed_as(client: &client::Client, status: &str) -> bool {
+        for _ in 0..5 {
+            sleep(1);
+            if client.send_message(String::from("LIST")).unwrap().contains(status) {
+                return true;
+            }
+        }
+        false
+    }
 }
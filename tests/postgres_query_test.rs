@@ -0,0 +1,201 @@
+#[cfg(test)]
+mod postgres_queries {
+    extern crate chrono;
+    extern crate postgres;
+    extern crate solanum;
+
+    use self::chrono::Duration;
+    use self::chrono::datetime::DateTime;
+    use self::chrono::offset::utc::UTC;
+
+    use self::solanum::daemon::PomodoroQueryMapper;
+    use self::solanum::daemon::config::{DatabaseConfig, SslMode};
+    use self::solanum::daemon::pomodoro::Pause;
+    use self::solanum::daemon::pomodoros::PomodoroQuery;
+
+    use std::env;
+
+    /// Connects to the database at $SOLANUM_DATABASE_URL in `schema`, which is emptied first, or
+    /// gives None if no database is configured. Each test has a schema of its own, apart from
+    /// any real history, since tests run at the same time. Nothing is migrated.
+    fn unmigrated_database(schema: &str) -> Option<(PomodoroQueryMapper, postgres::Connection)> {
+        let url = match env::var("SOLANUM_DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => {
+                println!("SOLANUM_DATABASE_URL is not set, so there is no database to query");
+                return None;
+            }
+        };
+
+        let conn = postgres::Connection::connect(url.as_str(), postgres::SslMode::None).unwrap();
+        conn.batch_execute(&format!("DROP SCHEMA IF EXISTS {0} CASCADE; CREATE SCHEMA {0};", schema))
+            .unwrap();
+
+        let mapper = PomodoroQueryMapper::new(&DatabaseConfig {
+                url: url,
+                ssl_mode: SslMode::Disable,
+                schema: Some(String::from(schema)),
+                user: None,
+                password: None,
+                pool_size: 1,
+            })
+            .unwrap();
+        Some((mapper, conn))
+    }
+
+    fn empty_database(schema: &str) -> Option<PomodoroQueryMapper> {
+        unmigrated_database(schema).map(|(mapper, _)| {
+            mapper.migrate().unwrap();
+            mapper
+        })
+    }
+
+    /// Creates pomodoros 1 to 5 a day apart from 2000-01-01, then pomodoro 6 at the same time as
+    /// pomodoro 3.
+    fn seeded_database(schema: &str) -> Option<(PomodoroQueryMapper, DateTime<UTC>)> {
+        let mapper = match empty_database(schema) {
+            Some(mapper) => mapper,
+            None => return None,
+        };
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        for day in vec![0, 1, 2, 3, 4, 2] {
+            mapper.create_pomodoro(start_time + Duration::days(day),
+                                 Duration::minutes(25),
+                                 Duration::minutes(5),
+                                 vec![])
+                .unwrap();
+        }
+        Some((mapper, start_time))
+    }
+
+    fn table_count(conn: &postgres::Connection, schema: &str) -> i64 {
+        conn.query("SELECT COUNT(*) FROM information_schema.tables WHERE table_schema = $1",
+                   &[&schema])
+            .unwrap()
+            .get(0)
+            .get(0)
+    }
+
+    fn ids(mapper: &PomodoroQueryMapper, query: PomodoroQuery) -> Vec<i32> {
+        mapper.query_pomodoros(&query).unwrap().into_iter().map(|pomodoro| pomodoro.id).collect()
+    }
+
+    // Run with `SOLANUM_DATABASE_URL=... cargo test --test postgres_query_test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn migrates_everything_listed_as_pending() {
+        let schema = "solanum_test_pending";
+        let (mapper, conn) = match unmigrated_database(schema) {
+            Some(unmigrated) => unmigrated,
+            None => return,
+        };
+
+        let pending = mapper.pending_migrations().unwrap();
+
+        assert!(pending.len() == mapper.migrate().unwrap().len());
+        assert!(pending[0].version == 1);
+        assert!(mapper.pending_migrations().unwrap().is_empty());
+        assert!(table_count(&conn, schema) > 0);
+    }
+
+    #[test]
+    #[ignore]
+    fn leaves_an_unmigrated_schema_empty_when_listing_pending_migrations() {
+        let schema = "solanum_test_dry_run";
+        let (mapper, conn) = match unmigrated_database(schema) {
+            Some(unmigrated) => unmigrated,
+            None => return,
+        };
+
+        assert!(!mapper.pending_migrations().unwrap().is_empty());
+        assert!(table_count(&conn, schema) == 0);
+    }
+
+    #[test]
+    #[ignore]
+    fn lists_the_most_recent_pomodoros_first() {
+        let (mapper, _) = match seeded_database("solanum_test_most_recent") {
+            Some(seeded) => seeded,
+            None => return,
+        };
+
+        let listed: Vec<i32> = mapper.list_most_recent_pomodoros(3)
+            .unwrap()
+            .into_iter()
+            .map(|pomodoro| pomodoro.id)
+            .collect();
+
+        assert!(listed == vec![5, 4, 6]);
+        assert!(mapper.get_most_recent_pomodoro().unwrap().unwrap().id == 5);
+    }
+
+    #[test]
+    #[ignore]
+    fn pages_through_the_history_without_skipping_or_repeating_pomodoros() {
+        let (mapper, _) = match seeded_database("solanum_test_pages") {
+            Some(seeded) => seeded,
+            None => return,
+        };
+        let page = |offset| {
+            PomodoroQuery {
+                limit: 2,
+                offset: offset,
+                since: None,
+                until: None,
+            }
+        };
+
+        assert!(ids(&mapper, page(0)) == vec![5, 4]);
+        assert!(ids(&mapper, page(2)) == vec![6, 3]);
+        assert!(ids(&mapper, page(4)) == vec![2, 1]);
+        assert!(ids(&mapper, page(6)).is_empty());
+    }
+
+    #[test]
+    #[ignore]
+    fn applies_the_time_range_before_the_offset_and_limit() {
+        let (mapper, start_time) = match seeded_database("solanum_test_range") {
+            Some(seeded) => seeded,
+            None => return,
+        };
+        let range = |limit, offset| {
+            PomodoroQuery {
+                limit: limit,
+                offset: offset,
+                since: Some(start_time + Duration::days(1)),
+                until: Some(start_time + Duration::days(4)),
+            }
+        };
+
+        assert!(ids(&mapper, range(10, 0)) == vec![4, 6, 3, 2]);
+        assert!(ids(&mapper, range(2, 1)) == vec![6, 3]);
+        assert!(ids(&mapper, range(10, 4)).is_empty());
+    }
+
+    #[test]
+    #[ignore]
+    fn gives_each_pomodoro_on_a_page_its_own_details() {
+        let (mapper, start_time) = match seeded_database("solanum_test_details") {
+            Some(seeded) => seeded,
+            None => return,
+        };
+        for id in vec![4, 6] {
+            let mut pomodoro = mapper.find_pomodoro(id).unwrap().unwrap();
+            pomodoro.pauses = vec![Pause {
+                                       start_time: pomodoro.work_start_time + Duration::seconds(1),
+                                       end_time: None,
+                                   }];
+            mapper.update_pomodoro(id, pomodoro).unwrap();
+        }
+
+        let page = mapper.list_most_recent_pomodoros(2).unwrap();
+
+        assert!(page.iter().map(|pomodoro| pomodoro.id).collect::<Vec<i32>>() == vec![5, 4]);
+        assert!(page[0].pauses.is_empty());
+        assert!(page[1].pauses ==
+                vec![Pause {
+                         start_time: start_time + Duration::days(3) + Duration::seconds(1),
+                         end_time: None,
+                     }]);
+    }
+}
@@ -0,0 +1,78 @@
+#[cfg(test)]
+mod benchmark {
+    extern crate chrono;
+    extern crate solanum;
+
+    use self::chrono::Duration;
+    use self::chrono::datetime::DateTime;
+    use self::chrono::offset::utc::UTC;
+
+    use self::solanum::daemon::SqlitePomodoroQueryMapper;
+    use self::solanum::daemon::pomodoros::PomodoroQuery;
+
+    use std::time::Instant;
+
+    const HISTORY_SIZE: i64 = 50_000;
+    const QUERIES: u32 = 200;
+
+    fn seeded_history() -> (SqlitePomodoroQueryMapper, DateTime<UTC>) {
+        let mapper = SqlitePomodoroQueryMapper::open_in_memory().unwrap();
+        let start_time = "2000-01-01T00:00:00+00:00".parse::<DateTime<UTC>>().unwrap();
+        for n in 0..HISTORY_SIZE {
+            mapper.create_pomodoro(start_time + Duration::minutes(30 * n),
+                                 Duration::minutes(25),
+                                 Duration::minutes(5),
+                                 vec![String::from("seeded")])
+                .unwrap();
+        }
+        (mapper, start_time)
+    }
+
+    fn average_millis<F: Fn()>(run_query: F) -> f64 {
+        let started = Instant::now();
+        for _ in 0..QUERIES {
+            run_query();
+        }
+        let elapsed = started.elapsed();
+        (elapsed.as_secs() as f64 * 1000.0 + elapsed.subsec_nanos() as f64 / 1_000_000.0) /
+        QUERIES as f64
+    }
+
+    // Run with `cargo test --test query_benchmark_test -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn queries_do_not_scan_a_large_history() {
+        let (mapper, start_time) = seeded_history();
+
+        let most_recent = average_millis(|| {
+            assert!(mapper.get_most_recent_pomodoro().unwrap().is_some());
+        });
+        let page = average_millis(|| {
+            let query = PomodoroQuery {
+                limit: 20,
+                offset: 100,
+                since: None,
+                until: None,
+            };
+            assert!(mapper.query_pomodoros(&query).unwrap().len() == 20);
+        });
+        let range = average_millis(|| {
+            let query = PomodoroQuery {
+                limit: 50,
+                offset: 0,
+                since: Some(start_time + Duration::days(100)),
+                until: Some(start_time + Duration::days(101)),
+            };
+            assert!(mapper.query_pomodoros(&query).unwrap().len() == 48);
+        });
+
+        println!("{} pomodoros: most recent {:.3}ms, page {:.3}ms, one-day range {:.3}ms",
+                 HISTORY_SIZE,
+                 most_recent,
+                 page,
+                 range);
+        assert!(most_recent < 5.0);
+        assert!(page < 5.0);
+        assert!(range < 5.0);
+    }
+}